@@ -7,6 +7,14 @@ fn main() -> Result<(), SpirvBuilderError> {
         "../shaders/simple_compute",
         "../shaders/fractal",
         "../shaders/simple_graphics",
+        "../shaders/raytracer",
+        "../shaders/fractal3d",
+        "../shaders/primitives",
+        "../shaders/gemm",
+        "../shaders/imaging",
+        "../shaders/fft",
+        "../shaders/nbody",
+        "../shaders/life",
     ];
 
     for path_to_shader in path_to_shaders {
@@ -0,0 +1,481 @@
+//! Tools started from the command line instead of the default demos.
+
+use std::path::Path;
+use std::sync::Arc;
+use image::RgbaImage;
+use shared::fractal::{FractalConstants, GradientStop};
+use shared::primitives::{MAX, MIN, SUM};
+use shared::host;
+use shared::ShaderConstants;
+use vulkano::device::{Device, Queue};
+use crate::engine::fft::Fft;
+use crate::engine::primitives::Primitives;
+use crate::{deep_zoom, engine, fractal, fractal3d_window, fractal_zoom, gemm, imaging, raytracer, simple_graphics, wavefront};
+
+pub fn run(device: Arc<Device>, queue: Arc<Queue>, args: &[String]) {
+    match args[0].as_str() {
+        "tiled" => tiled(device, queue, &args[1..]),
+        "frames" => frames(device, queue, &args[1..]),
+        "raytracer" => raytracer::raytracer(device, queue),
+        "photons" => raytracer::raytracer_photons(device, queue),
+        "lens" => lens(device, queue, &args[1..]),
+        "fractal" => fractal(device, queue, &args[1..]),
+        "deep" => deep(device, queue, &args[1..]),
+        "zoom" => zoom(device, queue, &args[1..]),
+        "primitives" => primitives(device, queue, &args[1..]),
+        "gemm" => gemm(device, queue, &args[1..]),
+        "image" => image(device, queue, &args[1..]),
+        "fft" => fft(device, queue, &args[1..]),
+        "wavefront" => wavefront::wavefront(device, queue),
+        "wavefront-benchmark" => wavefront::benchmark(device, queue),
+        command => println!("unknown command `{}`", command),
+    }
+}
+
+/// `cpu <fractal|fractal3d> <width> <height> <output.png>`
+///
+/// Runs the kernel of a fractal on the CPU through `shared::host`, one invocation after the
+/// other, so that it can be stepped through in a debugger. Needs no GPU.
+pub fn cpu(args: &[String]) {
+    let (kernel, [width, height], output) = match args {
+        [kernel, width, height, output] => (
+            kernel.as_str(),
+            [
+                width.parse().expect("width must be a number"),
+                height.parse().expect("height must be a number"),
+            ],
+            Path::new(output),
+        ),
+        _ => {
+            println!("usage: cpu <fractal|fractal3d> <width> <height> <output.png>");
+            return;
+        }
+    };
+
+    let mut image = host::Image2d::new([width, height]);
+    let workgroups = engine::workgroups([width, height]);
+    match kernel {
+        "fractal" => {
+            let constants = fractal::default_constants(width, height);
+            let palette = fractal::PALETTES[0].1;
+            host::dispatch([8, 8, 1], workgroups, |id| {
+                fractal_shader::fractal(id, &constants, &mut image, palette)
+            });
+        }
+        "fractal3d" => {
+            let constants = fractal3d_window::default_constants([width, height]);
+            host::dispatch([8, 8, 1], workgroups, |id| {
+                fractal3d_shader::main_cs(id, &constants, &mut image)
+            });
+        }
+        kernel => {
+            println!("unknown kernel `{}`", kernel);
+            return;
+        }
+    }
+
+    RgbaImage::from_raw(width, height, image.to_rgba8())
+        .unwrap()
+        .save(output)
+        .unwrap();
+}
+
+/// `frames <first> <last> <fps> <output>`, e.g. `frames 0 191 24 turntable_####.exr`
+///
+/// Renders a range of frames of the animated scene to numbered PNG or EXR files.
+fn frames(device: Arc<Device>, queue: Arc<Queue>, args: &[String]) {
+    let (first, last, fps, output) = match args {
+        [first, last, fps, output] => (
+            first.parse().expect("first frame must be a number"),
+            last.parse().expect("last frame must be a number"),
+            fps.parse().expect("fps must be a number"),
+            output.as_str(),
+        ),
+        _ => {
+            println!("usage: frames <first> <last> <fps> <output>");
+            return;
+        }
+    };
+
+    raytracer::raytracer_frames(device, queue, first, last, fps, output);
+}
+
+/// `fractal <width> <height> <center_x> <center_y> <zoom> <max_iterations> <output.png> [formula] [shading] [palette] [samples]`
+///
+/// Renders any view of the Mandelbrot set at any resolution, or of another fractal given as
+/// in `fractal::parse_formula`, e.g. `julia:-0.8,0.156`. The shading is one of `smooth`,
+/// `orbit-trap` or `distance`, the palette one of `fractal::PALETTES`, and the samples the
+/// number per side of the grid every pixel averages to smooth its edges.
+fn fractal(device: Arc<Device>, queue: Arc<Queue>, args: &[String]) {
+    let (constants, palette, output) = match args {
+        [width, height, center_x, center_y, zoom, max_iterations, output, options @ ..] if options.len() <= 4 => {
+            let (constants, palette) = fractal_options(
+                fractal::default_constants(
+                    width.parse().expect("width must be a number"),
+                    height.parse().expect("height must be a number"),
+                ),
+                options,
+            );
+            (
+                FractalConstants {
+                    center: [
+                        center_x.parse().expect("center_x must be a number"),
+                        center_y.parse().expect("center_y must be a number"),
+                    ],
+                    zoom: zoom.parse().expect("zoom must be a number"),
+                    max_iterations: max_iterations.parse().expect("max_iterations must be a number"),
+                    ..constants
+                },
+                palette,
+                Path::new(output),
+            )
+        }
+        _ => {
+            println!("usage: fractal <width> <height> <center_x> <center_y> <zoom> <max_iterations> <output.png> [formula] [shading] [palette] [samples]");
+            return;
+        }
+    };
+
+    fractal::fractal_tiled(device, queue, constants, palette, output);
+}
+
+/// `zoom <width> <height> <frames> <fps> <start_x> <start_y> <start_zoom> <end_x> <end_y> <end_zoom> <max_iterations> <output> [formula] [shading] [palette] [samples]`
+///
+/// Renders an animation zooming from one view of a fractal into another, e.g.
+/// `zoom 480 360 120 30 -0.5 0 0.8 -0.743643 0.131825 10000 1000 zoom.gif`. The output is an
+/// animated GIF or PNG, or a PNG sequence when it contains `#`. The options are those of
+/// `fractal`.
+fn zoom(device: Arc<Device>, queue: Arc<Queue>, args: &[String]) {
+    let usage = "usage: zoom <width> <height> <frames> <fps> <start_x> <start_y> <start_zoom> <end_x> <end_y> <end_zoom> <max_iterations> <output> [formula] [shading] [palette] [samples]";
+    let (start, end, frames, fps, palette, output) = match args {
+        [width, height, frames, fps, start_x, start_y, start_zoom, end_x, end_y, end_zoom, max_iterations, output, options @ ..]
+        if options.len() <= 4 => {
+            let (constants, palette) = fractal_options(
+                fractal::default_constants(
+                    width.parse().expect("width must be a number"),
+                    height.parse().expect("height must be a number"),
+                ),
+                options,
+            );
+            let constants = FractalConstants {
+                max_iterations: max_iterations.parse().expect("max_iterations must be a number"),
+                ..constants
+            };
+            let view = |x: &String, y: &String, zoom: &String| FractalConstants {
+                center: [
+                    x.parse().expect("center must be a number"),
+                    y.parse().expect("center must be a number"),
+                ],
+                zoom: zoom.parse().expect("zoom must be a number"),
+                ..constants
+            };
+            (
+                view(start_x, start_y, start_zoom),
+                view(end_x, end_y, end_zoom),
+                frames.parse().expect("frames must be a number"),
+                fps.parse().expect("fps must be a number"),
+                palette,
+                output.as_str(),
+            )
+        }
+        _ => {
+            println!("{}", usage);
+            return;
+        }
+    };
+    if frames == 0 || fps == 0 {
+        println!("frames and fps must be at least 1");
+        println!("{}", usage);
+        return;
+    }
+
+    fractal_zoom::fractal_zoom(device, queue, start, end, frames, fps, palette, output);
+}
+
+/// Apply the optional `[formula] [shading] [palette] [samples]` arguments of the fractal commands to
+/// `constants`, and pick the palette.
+fn fractal_options(constants: FractalConstants, options: &[String]) -> (FractalConstants, &'static [GradientStop]) {
+    let constants = match options.get(0) {
+        Some(formula) => fractal::parse_formula(formula, constants).expect("unknown formula"),
+        None => constants,
+    };
+    let shading = match options.get(1) {
+        Some(shading) => fractal::parse_shading(shading).expect("unknown shading"),
+        None => constants.shading,
+    };
+    let palette = match options.get(2) {
+        Some(palette) => fractal::palette(palette).expect("unknown palette"),
+        None => fractal::PALETTES[0].1,
+    };
+    let samples_per_side = match options.get(3) {
+        Some(samples) => samples.parse().expect("samples must be a number"),
+        None => constants.samples_per_side,
+    };
+    (FractalConstants { shading, samples_per_side, ..constants }, palette)
+}
+
+/// `deep <width> <height> <center_x> <center_y> <zoom> <max_iterations> <output.png>`
+///
+/// Renders a view of the Mandelbrot set at zooms far beyond the precision of `f32`, e.g.
+/// `deep 1920 1080 -1.7497219757034842770 -0.0000000000000001 1e20 20000 deep.png`. The centre
+/// is read at full precision.
+fn deep(device: Arc<Device>, queue: Arc<Queue>, args: &[String]) {
+    let (size, center, zoom, max_iterations, output) = match args {
+        [width, height, center_x, center_y, zoom, max_iterations, output] => (
+            [
+                width.parse().expect("width must be a number"),
+                height.parse().expect("height must be a number"),
+            ],
+            [center_x.as_str(), center_y.as_str()],
+            zoom.parse().expect("zoom must be a number"),
+            max_iterations.parse().expect("max_iterations must be a number"),
+            Path::new(output),
+        ),
+        _ => {
+            println!("usage: deep <width> <height> <center_x> <center_y> <zoom> <max_iterations> <output.png>");
+            return;
+        }
+    };
+
+    deep_zoom::deep_zoom(device, queue, size, center, zoom, max_iterations, output);
+}
+
+/// `lens <k1> <k2> <vignetting> <chromatic_aberration> <output.png>`
+///
+/// Renders the scene through an imperfect lens: `k1` and `k2` distort it radially, a non-zero
+/// `vignetting` darkens its edges and `chromatic_aberration` shifts red and blue apart.
+fn lens(device: Arc<Device>, queue: Arc<Queue>, args: &[String]) {
+    let (k1, k2, vignetting, chromatic_aberration, output) = match args {
+        [k1, k2, vignetting, chromatic_aberration, output] => (
+            k1.parse().expect("k1 must be a number"),
+            k2.parse().expect("k2 must be a number"),
+            vignetting.parse().expect("vignetting must be a number"),
+            chromatic_aberration.parse().expect("chromatic aberration must be a number"),
+            Path::new(output),
+        ),
+        _ => {
+            println!("usage: lens <k1> <k2> <vignetting> <chromatic_aberration> <output.png>");
+            return;
+        }
+    };
+
+    let constants = ShaderConstants {
+        lens_distortion: [k1, k2],
+        vignetting,
+        chromatic_aberration,
+        ..raytracer::default_constants()
+    };
+    raytracer::raytracer_to(device, queue, constants, output);
+}
+
+/// `primitives <len>`
+///
+/// Runs every parallel primitive over `len` pseudo random numbers and checks the results
+/// against the same operations on the CPU.
+fn primitives(device: Arc<Device>, queue: Arc<Queue>, args: &[String]) {
+    let len: u32 = match args {
+        [len] => len.parse().expect("len must be a number"),
+        _ => {
+            println!("usage: primitives <len>");
+            return;
+        }
+    };
+
+    // Keys of all magnitudes.
+    let mut random = engine::Xorshift::default();
+    let data: Vec<u32> = (0..len)
+        .map(|_| {
+            let x = random.next_u32();
+            x >> (x % 32)
+        })
+        .collect();
+    let primitives = Primitives::new(device, queue);
+
+    let sum = data.iter().fold(0u32, |sum, &x| sum.wrapping_add(x));
+    assert_eq!(primitives.reduce(primitives.upload(data.iter().copied()), SUM), sum);
+    assert_eq!(primitives.reduce(primitives.upload(data.iter().copied()), MIN), data.iter().copied().min().unwrap());
+    assert_eq!(primitives.reduce(primitives.upload(data.iter().copied()), MAX), data.iter().copied().max().unwrap());
+
+    let small: Vec<u32> = data.iter().map(|&x| x % 1000).collect();
+    let mut total = 0;
+    let scan: Vec<u32> = small
+        .iter()
+        .map(|&x| {
+            total += x;
+            total - x
+        })
+        .collect();
+    assert_eq!(primitives.exclusive_scan(primitives.upload(small.iter().copied())), scan);
+
+    let mut histogram = vec![0; 100];
+    for &x in &small {
+        histogram[(x >> 2).min(99) as usize] += 1;
+    }
+    assert_eq!(primitives.histogram(primitives.upload(small.iter().copied()), 2, 100), histogram);
+
+    let keys = primitives.upload(data.iter().copied());
+    let values = primitives.upload(0..len);
+    primitives.radix_sort(keys.clone(), values.clone());
+    let mut sorted: Vec<(u32, u32)> = data.iter().copied().zip(0..len).collect();
+    sorted.sort_by_key(|&(key, _)| key);
+    let keys = keys.read().unwrap();
+    let values = values.read().unwrap();
+    assert!(keys.iter().copied().zip(values.iter().copied()).eq(sorted));
+
+    println!("Everything succeded!");
+}
+
+/// `gemm <m> <n> <k>`, e.g. `gemm 1024 1024 1024`
+///
+/// Multiplies a random `m` by `k` matrix with a random `k` by `n` matrix, checks the product
+/// against the CPU and reports the GFLOP/s of both.
+fn gemm(device: Arc<Device>, queue: Arc<Queue>, args: &[String]) {
+    let size = match args {
+        [m, n, k] => [
+            m.parse().expect("m must be a number"),
+            n.parse().expect("n must be a number"),
+            k.parse().expect("k must be a number"),
+        ],
+        _ => {
+            println!("usage: gemm <m> <n> <k>");
+            return;
+        }
+    };
+
+    gemm::gemm_benchmark(device, queue, size);
+}
+
+/// `fft <width> [height]`, e.g. `fft 4096` or `fft 256 128`
+///
+/// Transforms pseudo random complex numbers forward and back on the GPU, in one dimension or
+/// in two with a height, and checks the transform against a DFT on the CPU and the inverse
+/// against the numbers transformed.
+fn fft(device: Arc<Device>, queue: Arc<Queue>, args: &[String]) {
+    let [width, height]: [u32; 2] = match args {
+        [width] => [width.parse().expect("width must be a number"), 1],
+        [width, height] => [
+            width.parse().expect("width must be a number"),
+            height.parse().expect("height must be a number"),
+        ],
+        _ => {
+            println!("usage: fft <width> [height]");
+            return;
+        }
+    };
+
+    let mut random = engine::Xorshift::default();
+    let data: Vec<[f32; 2]> = (0..width * height).map(|_| [random.signed(), random.signed()]).collect();
+    let fft = Fft::new(device, queue);
+    let (transform, inverse) = if height == 1 {
+        let transform = fft.fft(data.clone(), false);
+        (transform.clone(), fft.fft(transform, true))
+    } else {
+        let transform = fft.fft_2d(data.clone(), [width, height], false);
+        (transform.clone(), fft.fft_2d(transform, [width, height], true))
+    };
+
+    // The 2D DFT is the DFT of every row, then of every column.
+    let data: Vec<[f64; 2]> = data.iter().map(|&[re, im]| [re as f64, im as f64]).collect();
+    let mut expected = data.clone();
+    for row in expected.chunks_mut(width as usize) {
+        let transformed = dft(row.iter().copied());
+        row.copy_from_slice(&transformed);
+    }
+    for x in 0..width as usize {
+        let column = dft(expected.iter().skip(x).step_by(width as usize).copied());
+        for (y, value) in column.into_iter().enumerate() {
+            expected[y * width as usize + x] = value;
+        }
+    }
+
+    let largest_difference = |a: &[[f32; 2]], b: &[[f64; 2]]| {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| (a[0] as f64 - b[0]).abs().max((a[1] as f64 - b[1]).abs()))
+            .fold(0.0, f64::max)
+    };
+    let transform_error = largest_difference(&transform, &expected);
+    let inverse_error = largest_difference(&inverse, &data);
+    println!("largest difference with the DFT: {:e}", transform_error);
+    println!("largest difference after the inverse: {:e}", inverse_error);
+    // Rounding errors grow with the magnitude of the transform, about the square root of the
+    // number of elements.
+    assert!(transform_error <= 1e-5 * ((width * height) as f64).sqrt(), "the FFT and the DFT disagree");
+    assert!(inverse_error <= 1e-5, "the inverse FFT does not undo the FFT");
+
+    println!("Everything succeded!");
+}
+
+/// Forward discrete Fourier transform of `data`, straight from its definition.
+fn dft(data: impl Iterator<Item = [f64; 2]>) -> Vec<[f64; 2]> {
+    let data: Vec<[f64; 2]> = data.collect();
+    let n = data.len();
+    (0..n)
+        .map(|k| {
+            data.iter().enumerate().fold([0.0, 0.0], |[re, im], (j, &[x, y])| {
+                let angle = -2.0 * std::f64::consts::PI * ((j * k) % n) as f64 / n as f64;
+                let (sin, cos) = angle.sin_cos();
+                [re + x * cos - y * sin, im + x * sin + y * cos]
+            })
+        })
+        .collect()
+}
+
+/// `image <input> <output> <operation>...`, e.g. `image photo.jpg edges.png blur:1.5 sobel`
+///
+/// Runs an image through a chain of operations on the GPU, as given in
+/// `imaging::parse_operation`: `blur:<sigma>`, `sobel`, `convolve:<kernel file>`,
+/// `resize:<width>x<height>[:bilinear|lanczos]` and
+/// `adjust:brightness=<b>,contrast=<c>,saturation=<s>,gamma=<g>`.
+fn image(device: Arc<Device>, queue: Arc<Queue>, args: &[String]) {
+    let (input, output, operations) = match args {
+        [input, output, operations @ ..] => (
+            Path::new(input),
+            Path::new(output),
+            operations
+                .iter()
+                .map(|operation| {
+                    imaging::parse_operation(operation)
+                        .unwrap_or_else(|| panic!("invalid operation `{}`", operation))
+                })
+                .collect::<Vec<_>>(),
+        ),
+        _ => {
+            println!("usage: image <input> <output> <operation>...");
+            return;
+        }
+    };
+
+    imaging::process_file(device, queue, input, &operations, output);
+}
+
+/// `tiled <fractal|raytracer|triangle> <width> <height> <output.png>`
+///
+/// Running the same command again after an interruption resumes the render.
+fn tiled(device: Arc<Device>, queue: Arc<Queue>, args: &[String]) {
+    let (kernel, width, height, output) = match args {
+        [kernel, width, height, output] => (
+            kernel.as_str(),
+            width.parse().expect("width must be a number"),
+            height.parse().expect("height must be a number"),
+            Path::new(output),
+        ),
+        _ => {
+            println!("usage: tiled <fractal|raytracer|triangle> <width> <height> <output.png>");
+            return;
+        }
+    };
+
+    match kernel {
+        "fractal" => fractal::fractal_tiled(
+            device,
+            queue,
+            fractal::default_constants(width, height),
+            fractal::PALETTES[0].1,
+            output,
+        ),
+        "raytracer" => raytracer::raytracer_tiled(device, queue, width, height, output),
+        "triangle" => simple_graphics::simple_graphics_tiled(device, queue, width, height, output),
+        _ => println!("unknown kernel `{}`", kernel),
+    }
+}
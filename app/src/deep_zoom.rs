@@ -0,0 +1,232 @@
+//! Zooms into the Mandelbrot set far deeper than `f32` allows, using perturbation theory.
+//!
+//! A single reference orbit is iterated on the host with as many bits as the zoom needs, and
+//! the kernel only iterates the tiny offset of every pixel from it, which `f32` holds well
+//! enough. Where the offset loses its precision the pixel is marked as glitched, and rendered
+//! again from a new reference orbit starting inside the glitch.
+
+use std::path::Path;
+use std::sync::Arc;
+use image::{ImageBuffer, Rgba};
+use shared::fractal::{FractalConstants, PerturbationConstants};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::shader::ShaderModule;
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+use crate::engine;
+use crate::engine::fixed::Fixed;
+use crate::fractal;
+
+const SHADER_FRACTAL_DEEP: &[u8] = include_bytes!(env!("fractal.fractal_deep.spv"));
+
+/// Radius past which a point has escaped, for the reference orbit as well as the pixels.
+const ESCAPE_RADIUS: f32 = 4.0;
+
+/// Reference orbits tried before the pixels still glitched are left as they are.
+const MAX_REFERENCES: usize = 16;
+
+/// Bits kept beyond the size of a pixel, for the rounding errors of the reference orbit.
+const EXTRA_BITS: u32 = 64;
+
+/// A point of the complex plane at the precision of the zoom.
+#[derive(Clone)]
+struct Complex {
+    re: Fixed,
+    im: Fixed,
+}
+
+/// Render a `width` by `height` view of the Mandelbrot set centred on the decimal numbers
+/// `center_x` and `center_y`, where `zoom` 1 shows 2 units of the imaginary axis.
+pub fn deep_zoom(
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    [width, height]: [u32; 2],
+    [center_x, center_y]: [&str; 2],
+    zoom: f64,
+    max_iterations: u32,
+    output: &Path,
+) {
+    let pixel_size = 2.0 / (zoom * height as f64);
+    let fraction_limbs = Fixed::fraction_limbs_for(pixel_size, EXTRA_BITS);
+    let center = Complex {
+        re: Fixed::parse(center_x, fraction_limbs).expect("center_x must be a decimal number"),
+        im: Fixed::parse(center_y, fraction_limbs).expect("center_y must be a decimal number"),
+    };
+    println!("Zoom {:e} with {} bits of precision", zoom, 32 * fraction_limbs);
+
+    // Only the exponent of the pixel size may be out of the range of `f32`.
+    let pixel_size_exponent = pixel_size.log2().floor();
+    let pixel_size_mantissa = (pixel_size / 2f64.powf(pixel_size_exponent)) as f32;
+
+    let image = StorageImage::new(
+        device.clone(),
+        ImageDimensions::Dim2d {
+            width,
+            height,
+            array_layers: 1,
+        },
+        Format::R8G8B8A8_UNORM,
+        Some(queue.family()),
+    )
+        .unwrap();
+    let image_view = ImageView::new_default(image.clone()).unwrap();
+
+    let glitches = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::all(),
+        false,
+        (0..width * height).map(|_| 0u32),
+    )
+        .expect("failed to create buffer");
+    let buf = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::all(),
+        false,
+        (0..width * height * 4).map(|_| 0u8),
+    )
+        .expect("failed to create buffer");
+
+    let palette = fractal::palette_buffer(device.clone(), fractal::PALETTES[0].1);
+    let pipeline = get_pipeline(device.clone());
+
+    let mut reference = center.clone();
+    let mut reference_pixel = [width as f32 / 2.0, height as f32 / 2.0];
+    let mut glitched_only = 0;
+    for references in 1..=MAX_REFERENCES {
+        let orbit = reference_orbit(&reference, max_iterations);
+        let orbit_buf = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::all(),
+            false,
+            orbit.iter().copied(),
+        )
+            .expect("failed to create buffer");
+
+        let layout = pipeline.layout().set_layouts()
+            .get(0)
+            .unwrap();
+        let set = PersistentDescriptorSet::new(
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, image_view.clone()),
+                WriteDescriptorSet::buffer(1, orbit_buf),
+                WriteDescriptorSet::buffer(2, glitches.clone()),
+                WriteDescriptorSet::buffer(3, palette.clone()),
+            ],
+        )
+            .unwrap();
+
+        let constants = PerturbationConstants {
+            fractal: FractalConstants {
+                max_iterations,
+                escape_radius: ESCAPE_RADIUS,
+                ..fractal::default_constants(width, height)
+            },
+            reference_pixel,
+            reference_len: orbit.len() as u32,
+            pixel_size_mantissa,
+            pixel_size_exponent: pixel_size_exponent as i32,
+            glitched_only,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            device.clone(),
+            queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .push_constants(pipeline.layout().clone(), 0, constants)
+            .dispatch(engine::workgroups([width, height]))
+            .unwrap()
+            .copy_image_to_buffer(image.clone(), buf.clone())
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+
+        future.wait(None).unwrap();
+
+        let glitched = glitches.read().unwrap();
+        let count = glitched.iter().filter(|&&glitch| glitch != 0).count();
+        println!("Reference {}: {} iterations, {} pixels glitched", references, orbit.len() - 1, count);
+        if count == 0 {
+            break;
+        }
+
+        // Start the next reference orbit from the first glitched pixel.
+        let index = glitched.iter().position(|&glitch| glitch != 0).unwrap() as u32;
+        let pixel = [(index % width) as f32 + 0.5, (index / width) as f32 + 0.5];
+        let offset_x = Fixed::from_f64((pixel[0] as f64 - width as f64 / 2.0) * pixel_size, fraction_limbs);
+        let offset_y = Fixed::from_f64((pixel[1] as f64 - height as f64 / 2.0) * pixel_size, fraction_limbs);
+        reference = Complex {
+            re: &center.re + &offset_x,
+            im: &center.im - &offset_y,
+        };
+        reference_pixel = pixel;
+        glitched_only = 1;
+    }
+
+    let buffer_content = buf.read().unwrap();
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, &buffer_content[..]).unwrap();
+    image.save(output).unwrap();
+}
+
+/// Orbit of `c` under `z = z^2 + c` from `z = 0`, rounded to `f32`, until it escapes or after
+/// `max_iterations` iterations.
+fn reference_orbit(c: &Complex, max_iterations: u32) -> Vec<[f32; 2]> {
+    let mut orbit = vec![[0.0, 0.0]];
+    let mut z = c.clone();
+    while orbit.len() <= max_iterations as usize {
+        let (re, im) = (z.re.to_f64(), z.im.to_f64());
+        orbit.push([re as f32, im as f32]);
+        if re * re + im * im > (ESCAPE_RADIUS * ESCAPE_RADIUS) as f64 {
+            break;
+        }
+        let re_squared = &z.re * &z.re;
+        let im_squared = &z.im * &z.im;
+        let product = &z.re * &z.im;
+        z = Complex {
+            re: &(&re_squared - &im_squared) + &c.re,
+            im: &(&product + &product) + &c.im,
+        };
+    }
+    orbit
+}
+
+fn get_pipeline(device: Arc<Device>) -> Arc<ComputePipeline> {
+    assert_eq!(SHADER_FRACTAL_DEEP.len() % 4, 0);
+    let shader = unsafe {
+        ShaderModule::from_bytes(device.clone(), SHADER_FRACTAL_DEEP)
+            .unwrap()
+    };
+
+    ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("fractal_deep").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline")
+}
\ No newline at end of file
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use shared::fft::{FftConstants, WORKGROUP_SIZE};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::{Device, Queue};
+use crate::engine::kernel::ComputeKernel;
+
+const SHADER_FFT: &[u8] = include_bytes!(env!("fft.fft.spv"));
+
+/// Complex numbers as `[re, im]`.
+pub type ComplexBuffer = Arc<CpuAccessibleBuffer<[[f32; 2]]>>;
+
+/// Fast Fourier transforms of complex data of power of two lengths, in one or two dimensions.
+///
+/// Forward transforms are left unnormalised and inverse ones are divided by the number of
+/// elements, so that the inverse transform undoes the forward one. Every pass writes to the
+/// other of two buffers, so the `record_*` versions return the one the result ends up in.
+pub struct Fft {
+    device: Arc<Device>,
+    kernel: ComputeKernel,
+}
+
+impl Fft {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>) -> Self {
+        let kernel = ComputeKernel::new(device.clone(), queue, SHADER_FFT, "fft");
+        assert_eq!(kernel.local_size(), [WORKGROUP_SIZE, 1, 1]);
+        Self { device, kernel }
+    }
+
+    pub fn upload(&self, data: impl ExactSizeIterator<Item = [f32; 2]>) -> ComplexBuffer {
+        CpuAccessibleBuffer::from_iter(self.device.clone(), BufferUsage::all(), false, data)
+            .expect("failed to create buffer")
+    }
+
+    /// Transform of `data`, whose length is a power of two.
+    pub fn fft(&self, data: Vec<[f32; 2]>, inverse: bool) -> Vec<[f32; 2]> {
+        let n = data.len() as u32;
+        self.transform(data, |builder, data, scratch| self.record_fft(builder, data, scratch, n, inverse))
+    }
+
+    /// Transform of `data`, the rows of an image of `size` whose sides are powers of two.
+    pub fn fft_2d(&self, data: Vec<[f32; 2]>, size: [u32; 2], inverse: bool) -> Vec<[f32; 2]> {
+        assert_eq!(data.len() as u32, size[0] * size[1]);
+        self.transform(data, |builder, data, scratch| self.record_fft_2d(builder, data, scratch, size, inverse))
+    }
+
+    fn transform(
+        &self,
+        data: Vec<[f32; 2]>,
+        record: impl FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, ComplexBuffer, ComplexBuffer) -> ComplexBuffer,
+    ) -> Vec<[f32; 2]> {
+        let scratch = self.upload(data.iter().map(|_| [0.0; 2]));
+        let data = self.upload(data.into_iter());
+        let mut builder = self.kernel.builder();
+        let result = record(&mut builder, data, scratch);
+        self.kernel.submit(builder).wait(None).unwrap();
+        let content = result.read().unwrap();
+        content.to_vec()
+    }
+
+    /// Record the transform of the `n` elements of `data`, using `scratch` of the same length.
+    pub fn record_fft(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        data: ComplexBuffer,
+        scratch: ComplexBuffer,
+        n: u32,
+        inverse: bool,
+    ) -> ComplexBuffer {
+        self.record_passes(builder, (data, scratch), n, [1, 1, n], inverse).0
+    }
+
+    /// Record the transform of the rows of an image of `size` in `data`, using `scratch` of the
+    /// same length: every row is transformed, then every column.
+    pub fn record_fft_2d(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        data: ComplexBuffer,
+        scratch: ComplexBuffer,
+        [width, height]: [u32; 2],
+        inverse: bool,
+    ) -> ComplexBuffer {
+        let buffers = self.record_passes(builder, (data, scratch), width, [height, 1, width], inverse);
+        self.record_passes(builder, buffers, height, [width, width, 1], inverse).0
+    }
+
+    /// Record the passes transforming `batches` transforms of length `n` laid out as in
+    /// `FftConstants`, from the first buffer to whichever of the two is returned first.
+    fn record_passes(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        buffers: (ComplexBuffer, ComplexBuffer),
+        n: u32,
+        [batches, element_stride, batch_stride]: [u32; 3],
+        inverse: bool,
+    ) -> (ComplexBuffer, ComplexBuffer) {
+        assert!(n.is_power_of_two(), "the FFT only transforms powers of two, not {}", n);
+        let twiddles = self.twiddles(n);
+        let (mut from, mut to) = buffers;
+        let mut span = 1;
+        while span < n {
+            let radix = if (n / span) % 4 == 0 { 4 } else { 2 };
+            let last = span * radix == n;
+            let constants = FftConstants {
+                n,
+                radix,
+                span,
+                batches,
+                element_stride,
+                batch_stride,
+                inverse: inverse as u32,
+                scale: if inverse && last { 1.0 / n as f32 } else { 1.0 },
+            };
+            self.kernel.clone()
+                .with_buffer(0, from.clone())
+                .with_buffer(1, to.clone())
+                .with_buffer(2, twiddles.clone())
+                .record(builder, constants, [n / radix, batches, 1]);
+            std::mem::swap(&mut from, &mut to);
+            span *= radix;
+        }
+        (from, to)
+    }
+
+    /// `exp(2 pi i m / n)` for every `m` below `n`, computed in double precision.
+    fn twiddles(&self, n: u32) -> ComplexBuffer {
+        self.upload((0..n).map(|m| {
+            let angle = 2.0 * std::f64::consts::PI * m as f64 / n as f64;
+            [angle.cos() as f32, angle.sin() as f32]
+        }))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,275 @@
+//! Fixed point numbers of any precision, for computations that outgrow `f64`.
+
+use std::cmp::Ordering;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// A signed fixed point number with 32 integer bits and 32 fractional bits per extra limb.
+///
+/// All numbers taking part in an operation must have the same precision.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fixed {
+    negative: bool,
+    /// Magnitude, least significant limb first. The last limb is the integer part.
+    limbs: Vec<u32>,
+}
+
+impl Fixed {
+    pub fn zero(fraction_limbs: usize) -> Self {
+        Self {
+            negative: false,
+            limbs: vec![0; fraction_limbs + 1],
+        }
+    }
+
+    /// Fractional limbs needed to tell apart numbers `resolution` apart, with `extra_bits` to
+    /// spare for the rounding errors that accumulate.
+    pub fn fraction_limbs_for(resolution: f64, extra_bits: u32) -> usize {
+        let bits = (-resolution.log2()).max(0.0).ceil() as u32 + extra_bits;
+        ((bits + 31) / 32) as usize
+    }
+
+    pub fn from_f64(value: f64, fraction_limbs: usize) -> Self {
+        let mut result = Self::zero(fraction_limbs);
+        let bits = value.abs().to_bits();
+        let exponent = (bits >> 52) as i64;
+        let fraction = bits & ((1 << 52) - 1);
+        // The magnitude is `mantissa * 2^(exponent - 1075)`.
+        let (mantissa, exponent) = if exponent == 0 {
+            (fraction << 1, exponent)
+        } else {
+            (fraction | (1 << 52), exponent)
+        };
+        let shift = exponent - 1075 + 32 * fraction_limbs as i64;
+        for bit in 0..53 {
+            let position = bit + shift;
+            if mantissa >> bit & 1 == 1 && position >= 0 && position < 32 * result.limbs.len() as i64 {
+                result.limbs[position as usize / 32] |= 1 << (position % 32);
+            }
+        }
+        result.negative = value < 0.0 && !result.is_zero();
+        result
+    }
+
+    /// Parse a decimal number such as `-0.743643887037158704752191506114774`, keeping all the
+    /// digits that fit the precision.
+    pub fn parse(text: &str, fraction_limbs: usize) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if integer.is_empty() && fraction.is_empty() {
+            return None;
+        }
+
+        let mut result = Self::zero(fraction_limbs);
+        // Horner's scheme from the last digit: x = (x + digit) / 10.
+        for digit in fraction.chars().rev() {
+            *result.limbs.last_mut().unwrap() += digit.to_digit(10)?;
+            result.divide_small(10);
+        }
+        if !integer.is_empty() {
+            *result.limbs.last_mut().unwrap() += integer.parse::<u32>().ok()?;
+        }
+        result.negative = negative && !result.is_zero();
+        Some(result)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // Horner's scheme from the least significant limb, so that no power of two on the way
+        // overflows and the result is exact whenever it fits an `f64`.
+        let magnitude = self.limbs.iter().fold(0.0, |x, &limb| x * 2f64.powi(-32) + limb as f64);
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.iter().all(|&limb| limb == 0)
+    }
+
+    fn divide_small(&mut self, divisor: u32) {
+        let mut remainder = 0u64;
+        for limb in self.limbs.iter_mut().rev() {
+            let current = (remainder << 32) | *limb as u64;
+            *limb = (current / divisor as u64) as u32;
+            remainder = current % divisor as u64;
+        }
+    }
+
+    fn with_sign(negative: bool, limbs: Vec<u32>) -> Self {
+        let mut result = Self { negative, limbs };
+        result.negative &= !result.is_zero();
+        result
+    }
+}
+
+impl<'a> Add for &'a Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: &'a Fixed) -> Fixed {
+        assert_eq!(self.limbs.len(), other.limbs.len(), "precisions differ");
+        if self.negative == other.negative {
+            return Fixed::with_sign(self.negative, add_magnitudes(&self.limbs, &other.limbs));
+        }
+        match compare_magnitudes(&self.limbs, &other.limbs) {
+            Ordering::Less => Fixed::with_sign(other.negative, subtract_magnitudes(&other.limbs, &self.limbs)),
+            _ => Fixed::with_sign(self.negative, subtract_magnitudes(&self.limbs, &other.limbs)),
+        }
+    }
+}
+
+impl<'a> Sub for &'a Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: &'a Fixed) -> Fixed {
+        self + &-other
+    }
+}
+
+impl<'a> Neg for &'a Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed::with_sign(!self.negative, self.limbs.clone())
+    }
+}
+
+impl<'a> Mul for &'a Fixed {
+    type Output = Fixed;
+
+    /// Product truncated to the common precision.
+    fn mul(self, other: &'a Fixed) -> Fixed {
+        assert_eq!(self.limbs.len(), other.limbs.len(), "precisions differ");
+        let len = self.limbs.len();
+        let mut product = vec![0u32; 2 * len];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let current = product[i + j] as u64 + a as u64 * b as u64 + carry;
+                product[i + j] = current as u32;
+                carry = current >> 32;
+            }
+            product[i + len] = carry as u32;
+        }
+        // Drop the extra fractional limbs, and the integer limb that overflowed.
+        let fraction_limbs = len - 1;
+        Fixed::with_sign(
+            self.negative != other.negative,
+            product[fraction_limbs..fraction_limbs + len].to_vec(),
+        )
+    }
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut carry = 0u64;
+    a.iter()
+        .zip(b)
+        .map(|(&a, &b)| {
+            let sum = a as u64 + b as u64 + carry;
+            carry = sum >> 32;
+            sum as u32
+        })
+        .collect()
+}
+
+/// `a - b` for `a` at least as large as `b`.
+fn subtract_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut borrow = 0i64;
+    a.iter()
+        .zip(b)
+        .map(|(&a, &b)| {
+            let mut difference = a as i64 - b as i64 - borrow;
+            borrow = 0;
+            if difference < 0 {
+                difference += 1 << 32;
+                borrow = 1;
+            }
+            difference as u32
+        })
+        .collect()
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.iter().rev().cmp(b.iter().rev())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_f64_round_trips() {
+        for value in [0.0, 1.0, -0.5, 1234.5678, -0.7436438870371587, 3.0 * 2f64.powi(-60)] {
+            assert_eq!(Fixed::from_f64(value, 3).to_f64(), value);
+        }
+        // Subnormals, which need more than a thousand fractional bits.
+        for value in [f64::MIN_POSITIVE / 2.0, -3.0 * f64::MIN_POSITIVE / 1024.0, f64::from_bits(1)] {
+            assert!(value.is_subnormal());
+            assert_eq!(Fixed::from_f64(value, 34).to_f64(), value);
+        }
+    }
+
+    #[test]
+    fn parse_round_trips() {
+        for (text, value) in [("0", 0.0), ("-2.25", -2.25), ("+17", 17.0), ("0.125", 0.125), ("-.5", -0.5)] {
+            assert_eq!(Fixed::parse(text, 2).unwrap().to_f64(), value, "{}", text);
+        }
+        let parsed = Fixed::parse("-0.743643887037158704752191506114774", 3).unwrap();
+        assert_eq!(parsed.to_f64(), -0.7436438870371587);
+        assert_eq!(Fixed::parse("-0", 2).unwrap(), Fixed::zero(2));
+        for text in ["", "1.2x", "abc", "1.-2"] {
+            assert!(Fixed::parse(text, 2).is_none(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn parse_keeps_thirty_digits() {
+        // 2^-30 has exactly 30 decimals.
+        let parsed = Fixed::parse("0.000000000931322574615478515625", 2).unwrap();
+        assert_eq!(parsed.limbs, [0, 4, 0]);
+        // A third cut after 30 decimals, just under 1/3 at 64 fractional bits.
+        let parsed = Fixed::parse("0.333333333333333333333333333333", 2).unwrap();
+        assert_eq!(parsed.limbs, [0x55555555, 0x55555555, 0]);
+    }
+
+    #[test]
+    fn adding_numbers_of_opposite_signs() {
+        let a = Fixed::from_f64(1.5, 2);
+        let b = Fixed::from_f64(2.25, 2);
+        assert_eq!((&a + &-&b).to_f64(), -0.75);
+        assert_eq!((&-&a + &b).to_f64(), 0.75);
+        assert_eq!((&a - &b).to_f64(), -0.75);
+        assert_eq!((&b - &a).to_f64(), 0.75);
+        // A borrow across every limb.
+        let tiny = Fixed::from_f64(2f64.powi(-64), 2);
+        assert_eq!((&Fixed::from_f64(1.0, 2) - &tiny).limbs, [u32::MAX, u32::MAX, 0]);
+    }
+
+    #[test]
+    fn differences_to_itself_are_positive_zero() {
+        for value in [0.0, 3.75, -3.75] {
+            let x = Fixed::from_f64(value, 2);
+            let difference = &x - &x;
+            assert!(difference.is_zero());
+            assert_eq!(difference, Fixed::zero(2));
+            assert_eq!(&x + &-&x, Fixed::zero(2));
+        }
+    }
+
+    #[test]
+    fn products_carry_across_limbs() {
+        // (2^32 - 1) 2^-32 squared is 1 - 2^-31 + 2^-64.
+        let x = Fixed {
+            negative: false,
+            limbs: vec![0, u32::MAX, 0],
+        };
+        assert_eq!((&x * &x).limbs, [1, u32::MAX - 1, 0]);
+        let square = &x * &-&x;
+        assert!(square.negative);
+        assert_eq!(square.limbs, [1, u32::MAX - 1, 0]);
+        // The fractional limbs past the precision are truncated.
+        assert_eq!((&Fixed::from_f64(-1.5, 2) * &Fixed::from_f64(2.5, 2)).to_f64(), -3.75);
+        let tiny = Fixed::from_f64(2f64.powi(-40), 2);
+        assert!((&tiny * &tiny).is_zero());
+    }
+}
\ No newline at end of file
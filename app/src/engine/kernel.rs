@@ -0,0 +1,207 @@
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferExecFuture, CommandBufferUsage, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::image::view::ImageView;
+use vulkano::image::StorageImage;
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::shader::ShaderModule;
+use vulkano::sync;
+use vulkano::sync::{FenceSignalFuture, GpuFuture, NowFuture};
+
+/// Future of a dispatch submitted by `ComputeKernel::submit`, waited on with `wait(None)`.
+pub type KernelFuture = FenceSignalFuture<CommandBufferExecFuture<NowFuture, PrimaryAutoCommandBuffer>>;
+
+/// A compute pipeline built from one entry point, along with the buffers and images bound to it.
+///
+/// Buffers and images are bound by the binding index declared in the shader, and dispatches
+/// are sized in invocations rather than workgroups. Clones share the pipeline, so that the same
+/// kernel can run over other buffers without being built again.
+#[derive(Clone)]
+pub struct ComputeKernel {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    pipeline: Arc<ComputePipeline>,
+    /// Invocations per workgroup, as declared by `threads(...)`.
+    local_size: [u32; 3],
+    bindings: Vec<(u32, Binding)>,
+    set: Arc<PersistentDescriptorSet>,
+}
+
+#[derive(Clone)]
+enum Binding {
+    Buffer(Arc<dyn BufferAccess>),
+    Image(Arc<ImageView<StorageImage>>),
+}
+
+impl ComputeKernel {
+    /// Kernel running `entry_point` of the SPIR-V module `bytes`, with nothing bound yet.
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, bytes: &[u8], entry_point: &str) -> Self {
+        assert_eq!(bytes.len() % 4, 0);
+        let local_size = local_size(bytes, entry_point)
+            .unwrap_or_else(|| panic!("no local size declared for `{}`", entry_point));
+        let shader = unsafe {
+            ShaderModule::from_bytes(device.clone(), bytes)
+                .unwrap()
+        };
+
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point(entry_point).unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        let layout = pipeline.layout().set_layouts()
+            .get(0)
+            .unwrap();
+        let set = PersistentDescriptorSet::new(layout.clone(), []).unwrap();
+
+        Self {
+            device,
+            queue,
+            pipeline,
+            local_size,
+            bindings: Vec::new(),
+            set,
+        }
+    }
+
+    pub fn local_size(&self) -> [u32; 3] {
+        self.local_size
+    }
+
+    /// Bind `buffer` to `binding`, replacing what was bound to it before.
+    pub fn with_buffer(self, binding: u32, buffer: Arc<dyn BufferAccess>) -> Self {
+        self.with_binding(binding, Binding::Buffer(buffer))
+    }
+
+    /// Bind a view of the whole of `image` to `binding`, replacing what was bound to it before.
+    pub fn with_image(self, binding: u32, image: Arc<StorageImage>) -> Self {
+        self.with_binding(binding, Binding::Image(ImageView::new_default(image).unwrap()))
+    }
+
+    fn with_binding(mut self, binding: u32, resource: Binding) -> Self {
+        self.bindings.retain(|&(bound, _)| bound != binding);
+        self.bindings.push((binding, resource));
+        self.update_set();
+        self
+    }
+
+    fn update_set(&mut self) {
+        let layout = self.pipeline.layout().set_layouts()
+            .get(0)
+            .unwrap();
+        let writes = self.bindings.iter().map(|(binding, resource)| match resource {
+            Binding::Buffer(buffer) => WriteDescriptorSet::buffer(*binding, buffer.clone()),
+            Binding::Image(view) => WriteDescriptorSet::image_view(*binding, view.clone()),
+        });
+        self.set = PersistentDescriptorSet::new(layout.clone(), writes).unwrap();
+    }
+
+    /// Workgroups covering `size` invocations, rounded up. The invocations past `size` are left
+    /// for the kernel to skip.
+    pub fn workgroups(&self, size: [u32; 3]) -> [u32; 3] {
+        [0, 1, 2].map(|i| (size[i] + self.local_size[i] - 1) / self.local_size[i])
+    }
+
+    /// Record a dispatch of at least `size` invocations into `builder`, to run along with other
+    /// commands.
+    pub fn record<Pc: Send + Sync + 'static>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        constants: Pc,
+        size: [u32; 3],
+    ) {
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                self.set.clone(),
+            );
+        if !self.pipeline.layout().push_constant_ranges().is_empty() {
+            builder.push_constants(self.pipeline.layout().clone(), 0, constants);
+        }
+        builder
+            .dispatch(self.workgroups(size))
+            .unwrap();
+    }
+
+    /// Command buffer builder for the queue of the kernel.
+    pub fn builder(&self) -> AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        AutoCommandBufferBuilder::primary(
+            self.device.clone(),
+            self.queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap()
+    }
+
+    /// Submit the commands recorded into `builder`.
+    pub fn submit(&self, builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> KernelFuture {
+        let command_buffer = builder.build().unwrap();
+
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+    }
+
+    /// Submit a dispatch of at least `size` invocations on its own.
+    pub fn dispatch<Pc: Send + Sync + 'static>(&self, constants: Pc, size: [u32; 3]) -> KernelFuture {
+        let mut builder = self.builder();
+        self.record(&mut builder, constants, size);
+        self.submit(builder)
+    }
+
+    /// Dispatch at least `size` invocations and wait for them to finish.
+    pub fn run<Pc: Send + Sync + 'static>(&self, constants: Pc, size: [u32; 3]) {
+        self.dispatch(constants, size).wait(None).unwrap();
+    }
+}
+
+/// The `LocalSize` execution mode of `entry_point` in the SPIR-V module `bytes`, which
+/// `threads(...)` compiles to.
+fn local_size(bytes: &[u8], entry_point: &str) -> Option<[u32; 3]> {
+    const OP_ENTRY_POINT: u32 = 15;
+    const OP_EXECUTION_MODE: u32 = 16;
+    const LOCAL_SIZE: u32 = 17;
+
+    let words: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    // Skip the header of the module.
+    let mut instructions = Vec::new();
+    let mut i = 5;
+    while i < words.len() {
+        let len = (words[i] >> 16) as usize;
+        instructions.push(&words[i..i + len.max(1)]);
+        i += len.max(1);
+    }
+
+    // Entry points are named by a nul terminated string packed in little endian words.
+    let id = instructions.iter().find_map(|instruction| {
+        if instruction[0] & 0xffff != OP_ENTRY_POINT {
+            return None;
+        }
+        let name: Vec<u8> = instruction[3..]
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .take_while(|&byte| byte != 0)
+            .collect();
+        (name == entry_point.as_bytes()).then(|| instruction[2])
+    })?;
+    instructions.iter().find_map(|instruction| match instruction {
+        [opcode, entry, LOCAL_SIZE, x, y, z] if opcode & 0xffff == OP_EXECUTION_MODE && *entry == id => {
+            Some([*x, *y, *z])
+        }
+        _ => None,
+    })
+}
\ No newline at end of file
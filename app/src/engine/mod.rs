@@ -1,3 +1,4 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use vulkano::device::DeviceExtensions;
 use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType, QueueFamily};
@@ -5,6 +6,11 @@ use vulkano::instance::Instance;
 use vulkano::swapchain::Surface;
 use winit::window::Window;
 
+pub mod fft;
+pub mod fixed;
+pub mod kernel;
+pub mod primitives;
+pub mod tiles;
 pub mod vec;
 
 pub fn select_physical_device<'a>(
@@ -30,3 +36,65 @@ pub fn select_physical_device<'a>(
 
     (physical_device, queue_family)
 }
+
+/// Workgroups of 8 by 8 threads covering an image of `width` by `height` pixels. The threads past
+/// its edges are left for the kernel to skip.
+pub fn workgroups([width, height]: [u32; 2]) -> [u32; 3] {
+    [(width + 7) / 8, (height + 7) / 8, 1]
+}
+
+/// Marsaglia's xorshift generator, for reproducible test data and starting states.
+#[derive(Clone, Debug)]
+pub struct Xorshift {
+    state: u32,
+}
+
+impl Default for Xorshift {
+    fn default() -> Self {
+        Self::new(2463534242)
+    }
+}
+
+impl Xorshift {
+    /// Generator starting from `seed`, which must not be zero.
+    pub fn new(seed: u32) -> Self {
+        assert_ne!(seed, 0, "the seed must not be zero");
+        Self { state: seed }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+
+    /// Uniform in [0, 1).
+    pub fn unit(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Uniform in [-1, 1).
+    pub fn signed(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 23) as f32 - 1.0
+    }
+}
+
+/// Path of frame `frame` of an animation saved to `output`.
+///
+/// The first run of `#` in `output` is replaced by the zero padded frame number, which is
+/// otherwise appended to the file name. Any `#` after that run is kept as it is.
+pub fn frame_path(output: &str, frame: u32) -> PathBuf {
+    if let Some(start) = output.find('#') {
+        let digits = output[start..].chars().take_while(|&c| c == '#').count();
+        let number = format!("{:0width$}", frame, width = digits);
+        return PathBuf::from(format!("{}{}{}", &output[..start], number, &output[start + digits..]));
+    }
+    let path = Path::new(output);
+    let stem = path.file_stem().unwrap().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}_{:04}.{}", stem, frame, extension.to_string_lossy()),
+        None => format!("{}_{:04}", stem, frame),
+    };
+    path.with_file_name(name)
+}
\ No newline at end of file
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+use shared::primitives::{PrimitiveConstants, MAX_BINS, RADIX, RADIX_BITS, SCAN_BLOCK, WORKGROUP_SIZE};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, TypedBufferAccess};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::{Device, Queue};
+use crate::engine::kernel::ComputeKernel;
+
+const SHADER_REDUCE: &[u8] = include_bytes!(env!("primitives.reduce.spv"));
+const SHADER_SCAN_BLOCKS: &[u8] = include_bytes!(env!("primitives.scan_blocks.spv"));
+const SHADER_SCAN_ADD: &[u8] = include_bytes!(env!("primitives.scan_add.spv"));
+const SHADER_RADIX_COUNT: &[u8] = include_bytes!(env!("primitives.radix_count.spv"));
+const SHADER_RADIX_SCATTER: &[u8] = include_bytes!(env!("primitives.radix_scatter.spv"));
+const SHADER_HISTOGRAM: &[u8] = include_bytes!(env!("primitives.histogram.spv"));
+
+pub type U32Buffer = Arc<CpuAccessibleBuffer<[u32]>>;
+
+/// Reduction, prefix scan, radix sort and histogram over `u32` buffers of any length.
+///
+/// Every operation records all of its dispatches in one command buffer and waits for them.
+/// The `record_*` versions only record them, for other kernels to use the results right away.
+pub struct Primitives {
+    device: Arc<Device>,
+    reduce: ComputeKernel,
+    scan_blocks: ComputeKernel,
+    scan_add: ComputeKernel,
+    radix_count: ComputeKernel,
+    radix_scatter: ComputeKernel,
+    histogram: ComputeKernel,
+}
+
+impl Primitives {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>) -> Self {
+        let kernel = |bytes: &[u8], entry_point: &str| {
+            let kernel = ComputeKernel::new(device.clone(), queue.clone(), bytes, entry_point);
+            assert_eq!(kernel.local_size(), [WORKGROUP_SIZE, 1, 1]);
+            kernel
+        };
+        Self {
+            reduce: kernel(SHADER_REDUCE, "reduce"),
+            scan_blocks: kernel(SHADER_SCAN_BLOCKS, "scan_blocks"),
+            scan_add: kernel(SHADER_SCAN_ADD, "scan_add"),
+            radix_count: kernel(SHADER_RADIX_COUNT, "radix_count"),
+            radix_scatter: kernel(SHADER_RADIX_SCATTER, "radix_scatter"),
+            histogram: kernel(SHADER_HISTOGRAM, "histogram"),
+            device,
+        }
+    }
+
+    /// Buffer of `len` zeros.
+    pub fn buffer(&self, len: u32) -> U32Buffer {
+        self.upload((0..len).map(|_| 0))
+    }
+
+    pub fn upload(&self, data: impl ExactSizeIterator<Item = u32>) -> U32Buffer {
+        CpuAccessibleBuffer::from_iter(self.device.clone(), BufferUsage::all(), false, data)
+            .expect("failed to create buffer")
+    }
+
+    /// All of `input` combined by `op`, one of `SUM`, `MIN` or `MAX`. Sums wrap around.
+    pub fn reduce(&self, input: U32Buffer, op: u32) -> u32 {
+        let mut builder = self.reduce.builder();
+        let result = self.record_reduce(&mut builder, input, op);
+        self.reduce.submit(builder).wait(None).unwrap();
+        let content = result.read().unwrap();
+        content[0]
+    }
+
+    /// Record the reduction of `input` by `op`, into the first element of the buffer returned.
+    pub fn record_reduce(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        input: U32Buffer,
+        op: u32,
+    ) -> U32Buffer {
+        // Every pass leaves one element per workgroup, until a single workgroup is left.
+        let mut input = input;
+        let mut len = input.len() as u32;
+        loop {
+            let groups = div_ceil(len.max(1), WORKGROUP_SIZE);
+            let output = self.buffer(groups);
+            let constants = PrimitiveConstants { len, op, ..Default::default() };
+            self.reduce.clone()
+                .with_buffer(0, input)
+                .with_buffer(1, output.clone())
+                .record(builder, constants, [len.max(1), 1, 1]);
+            if groups == 1 {
+                return output;
+            }
+            input = output;
+            len = groups;
+        }
+    }
+
+    /// Exclusive prefix sum of `input`: every element of the result is the sum of the elements
+    /// of `input` before it.
+    pub fn exclusive_scan(&self, input: U32Buffer) -> Vec<u32> {
+        let output = self.buffer(input.len() as u32);
+        let mut builder = self.scan_blocks.builder();
+        self.record_exclusive_scan(&mut builder, input, output.clone());
+        self.scan_blocks.submit(builder).wait(None).unwrap();
+        let content = output.read().unwrap();
+        content.to_vec()
+    }
+
+    /// Record the exclusive prefix sum of `input` into `output`, of the same length.
+    pub fn record_exclusive_scan(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        input: U32Buffer,
+        output: U32Buffer,
+    ) {
+        let len = input.len() as u32;
+        if len == 0 {
+            return;
+        }
+        let groups = div_ceil(len, SCAN_BLOCK);
+        let constants = PrimitiveConstants { len, ..Default::default() };
+        let block_sums = self.buffer(groups);
+        self.scan_blocks.clone()
+            .with_buffer(0, input)
+            .with_buffer(1, output.clone())
+            .with_buffer(2, block_sums.clone())
+            .record(builder, constants, [groups * WORKGROUP_SIZE, 1, 1]);
+        if groups == 1 {
+            return;
+        }
+
+        // The blocks are offset by the scan of their sums, scanned the same way.
+        let block_offsets = self.buffer(groups);
+        self.record_exclusive_scan(builder, block_sums, block_offsets.clone());
+        self.scan_add.clone()
+            .with_buffer(0, output)
+            .with_buffer(1, block_offsets)
+            .record(builder, constants, [len, 1, 1]);
+    }
+
+    /// Sort `keys` and `values`, of the same length, by key in place. Keys that are equal keep
+    /// the order of their values.
+    pub fn radix_sort(&self, keys: U32Buffer, values: U32Buffer) {
+        let mut builder = self.radix_count.builder();
+        self.record_radix_sort(&mut builder, keys, values);
+        self.radix_count.submit(builder).wait(None).unwrap();
+    }
+
+    /// Record the sort of `keys` and `values` by key, in place.
+    pub fn record_radix_sort(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        keys: U32Buffer,
+        values: U32Buffer,
+    ) {
+        let len = keys.len() as u32;
+        assert_eq!(values.len() as u32, len);
+        if len == 0 {
+            return;
+        }
+        let groups = div_ceil(len, WORKGROUP_SIZE);
+        let counts = self.buffer(RADIX * groups);
+        let offsets = self.buffer(RADIX * groups);
+
+        // Every pass sorts into the other pair of buffers, and there is an even number of them,
+        // so the last one sorts back into `keys` and `values`.
+        let mut from = (keys, values);
+        let mut to = (self.buffer(len), self.buffer(len));
+        let mut shift = 0;
+        while shift < u32::BITS {
+            let constants = PrimitiveConstants { len, shift, ..Default::default() };
+            self.radix_count.clone()
+                .with_buffer(0, from.0.clone())
+                .with_buffer(1, counts.clone())
+                .record(builder, constants, [len, 1, 1]);
+            self.record_exclusive_scan(builder, counts.clone(), offsets.clone());
+            self.radix_scatter.clone()
+                .with_buffer(0, from.0.clone())
+                .with_buffer(1, from.1.clone())
+                .with_buffer(2, offsets.clone())
+                .with_buffer(3, to.0.clone())
+                .with_buffer(4, to.1.clone())
+                .record(builder, constants, [len, 1, 1]);
+            std::mem::swap(&mut from, &mut to);
+            shift += RADIX_BITS;
+        }
+    }
+
+    /// How many keys of `input`, shifted right by `shift`, fall in each of `bins` bins, the keys
+    /// past the last bin being counted in it.
+    pub fn histogram(&self, input: U32Buffer, shift: u32, bins: u32) -> Vec<u32> {
+        let output = self.buffer(bins);
+        let mut builder = self.histogram.builder();
+        self.record_histogram(&mut builder, input, shift, output.clone());
+        self.histogram.submit(builder).wait(None).unwrap();
+        let content = output.read().unwrap();
+        content.to_vec()
+    }
+
+    /// Record the histogram of `input` into `bins`, adding to the counts already there.
+    pub fn record_histogram(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        input: U32Buffer,
+        shift: u32,
+        bins: U32Buffer,
+    ) {
+        let len = input.len() as u32;
+        let bin_count = bins.len() as u32;
+        assert!(bin_count >= 1 && bin_count <= MAX_BINS, "histograms have 1 to {} bins", MAX_BINS);
+        if len == 0 {
+            return;
+        }
+        let constants = PrimitiveConstants { len, shift, bins: bin_count, ..Default::default() };
+        self.histogram.clone()
+            .with_buffer(0, input)
+            .with_buffer(1, bins)
+            .record(builder, constants, [len, 1, 1]);
+    }
+}
+
+fn div_ceil(a: u32, b: u32) -> u32 {
+    (a + b - 1) / b
+}
\ No newline at end of file
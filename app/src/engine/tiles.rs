@@ -0,0 +1,253 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Rectangle of the output image rendered in one go.
+#[derive(Copy, Clone, Debug)]
+pub struct Tile {
+    pub index: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Renders images of any size one square tile at a time, stitching them together on disk.
+///
+/// Finished tiles are recorded next to the output, so rendering the same output again after an
+/// interruption only renders the tiles that are still missing. The record names the kernel and
+/// holds a fingerprint of its parameters, so that a render of anything else to the same output
+/// starts over instead of stitching tiles of two different images together.
+pub struct TiledRenderer {
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: u32,
+}
+
+impl TiledRenderer {
+    pub fn new(width: u32, height: u32, tile_size: u32) -> Self {
+        Self { width, height, tile_size }
+    }
+
+    /// All the tiles of the image, row by row. Tiles on the right and bottom edges are cropped.
+    pub fn tiles(&self) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for y in (0..self.height).step_by(self.tile_size as usize) {
+            for x in (0..self.width).step_by(self.tile_size as usize) {
+                tiles.push(Tile {
+                    index: tiles.len(),
+                    x,
+                    y,
+                    width: self.tile_size.min(self.width - x),
+                    height: self.tile_size.min(self.height - y),
+                });
+            }
+        }
+        tiles
+    }
+
+    /// Render every missing tile with `render_tile` and write the whole image to `output` as PNG.
+    ///
+    /// `render_tile` returns the RGBA8 pixels of a full `tile_size` square, of which only the part
+    /// covered by the tile is kept. `kernel` names what renders the tiles, without whitespace,
+    /// and `parameters` are the bytes of everything else the image depends on, such as its push
+    /// constants.
+    pub fn render(
+        &self,
+        output: &Path,
+        kernel: &str,
+        parameters: &[u8],
+        mut render_tile: impl FnMut(&Tile) -> Vec<u8>,
+    ) -> io::Result<()> {
+        let raw_path = with_suffix(output, "rgba.part");
+        let checkpoint_path = with_suffix(output, "tiles");
+        let header = format!(
+            "{} {} {} {} {:016x}",
+            self.width,
+            self.height,
+            self.tile_size,
+            kernel,
+            fingerprint(parameters),
+        );
+
+        let tiles = self.tiles();
+        let (mut checkpoint, done) = match self.read_checkpoint(&checkpoint_path, &raw_path, &header)? {
+            Some(done) => {
+                let count = done.iter().filter(|&&d| d).count();
+                println!("Resuming, {}/{} tiles already rendered", count, tiles.len());
+                (OpenOptions::new().append(true).open(&checkpoint_path)?, done)
+            }
+            None => {
+                let mut checkpoint = File::create(&checkpoint_path)?;
+                writeln!(checkpoint, "{}", header)?;
+                (checkpoint, vec![false; tiles.len()])
+            }
+        };
+
+        let mut raw = OpenOptions::new().read(true).write(true).create(true).open(&raw_path)?;
+        raw.set_len(self.width as u64 * self.height as u64 * 4)?;
+
+        for tile in tiles.iter().filter(|tile| !done[tile.index]) {
+            let pixels = render_tile(tile);
+            assert_eq!(pixels.len(), (self.tile_size * self.tile_size * 4) as usize);
+            for row in 0..tile.height {
+                let start = (row * self.tile_size * 4) as usize;
+                let end = start + (tile.width * 4) as usize;
+                let offset = ((tile.y + row) as u64 * self.width as u64 + tile.x as u64) * 4;
+                raw.seek(SeekFrom::Start(offset))?;
+                raw.write_all(&pixels[start..end])?;
+            }
+            // Only record the tile once its pixels are safely on disk.
+            raw.sync_data()?;
+            writeln!(checkpoint, "{}", tile.index)?;
+            checkpoint.sync_data()?;
+            println!("Tile {}/{} done", tile.index + 1, tiles.len());
+        }
+
+        self.write_png(&mut raw, output)?;
+        drop(raw);
+        fs::remove_file(&raw_path)?;
+        fs::remove_file(&checkpoint_path)?;
+        Ok(())
+    }
+
+    /// Which tiles were already rendered, or `None` if the checkpoint is missing or belongs to a
+    /// different render.
+    fn read_checkpoint(&self, checkpoint_path: &Path, raw_path: &Path, header: &str) -> io::Result<Option<Vec<bool>>> {
+        if !checkpoint_path.exists() || !raw_path.exists() {
+            return Ok(None);
+        }
+        let mut content = String::new();
+        File::open(checkpoint_path)?.read_to_string(&mut content)?;
+        // The last element is whatever follows the final newline, i.e. a line cut short by an
+        // interruption, and is ignored.
+        let mut lines = content.split('\n');
+        if lines.next() != Some(header) {
+            println!("Checkpoint {} is for a different render, starting over", checkpoint_path.display());
+            return Ok(None);
+        }
+        let complete_lines = content.matches('\n').count().saturating_sub(1);
+        let mut done = vec![false; self.tiles().len()];
+        for line in lines.take(complete_lines) {
+            if let Some(d) = line.parse::<usize>().ok().and_then(|index| done.get_mut(index)) {
+                *d = true;
+            }
+        }
+        Ok(Some(done))
+    }
+
+    /// Stream the stitched pixels into a PNG without ever holding the whole image in memory.
+    fn write_png(&self, raw: &mut File, output: &Path) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(output)?), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        let mut stream = writer.stream_writer()?;
+
+        raw.seek(SeekFrom::Start(0))?;
+        let mut row = vec![0u8; self.width as usize * 4];
+        for _ in 0..self.height {
+            raw.read_exact(&mut row)?;
+            stream.write_all(&row)?;
+        }
+        stream.finish()?;
+        Ok(())
+    }
+}
+
+/// 64 bit FNV-1a hash of `bytes`, which stays the same from one run and build to the next.
+fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    /// A 5 by 3 image of 2 by 2 tiles, 6 tiles with the right column and bottom row cropped.
+    fn renderer() -> TiledRenderer {
+        TiledRenderer::new(5, 3, 2)
+    }
+
+    /// Output in a directory of its own, so that the tests can run in parallel.
+    fn output(test: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("tiles-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        directory.join("out.png")
+    }
+
+    /// Every pixel of a tile holds the index of the tile, offset by `shade`.
+    fn tile_pixels(tile: &Tile, shade: u8) -> Vec<u8> {
+        vec![tile.index as u8 + shade; 2 * 2 * 4]
+    }
+
+    /// Render until the tile `stop` is reached, as if interrupted there.
+    fn interrupted(output: &Path, kernel: &str, parameters: &[u8], stop: usize) {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            renderer().render(output, kernel, parameters, |tile| {
+                assert!(tile.index != stop, "interrupted");
+                tile_pixels(tile, 0)
+            })
+        }));
+        assert!(result.is_err());
+    }
+
+    /// Finish the render, returning the indices of the tiles it had to render.
+    fn resume(output: &Path, kernel: &str, parameters: &[u8], shade: u8) -> Vec<usize> {
+        let mut rendered = Vec::new();
+        renderer()
+            .render(output, kernel, parameters, |tile| {
+                rendered.push(tile.index);
+                tile_pixels(tile, shade)
+            })
+            .unwrap();
+        rendered
+    }
+
+    /// The tile index each pixel of the saved image holds, row by row.
+    fn saved(output: &Path) -> Vec<u8> {
+        image::open(output).unwrap().into_rgba8().pixels().map(|pixel| pixel.0[0]).collect()
+    }
+
+    #[test]
+    fn resumes_after_an_interruption() {
+        let output = output("resume");
+        interrupted(&output, "fractal", &[1, 2, 3], 3);
+        assert!(with_suffix(&output, "tiles").exists());
+
+        // Tiles 0 to 2 are on disk, and a different shade would show if they were rendered again.
+        assert_eq!(resume(&output, "fractal", &[1, 2, 3], 100), [3, 4, 5]);
+        let expected = [
+            0, 0, 1, 1, 2,
+            0, 0, 1, 1, 2,
+            103, 103, 104, 104, 105,
+        ];
+        assert_eq!(saved(&output), expected);
+        assert!(!with_suffix(&output, "tiles").exists());
+        assert!(!with_suffix(&output, "rgba.part").exists());
+    }
+
+    #[test]
+    fn starts_over_for_other_parameters() {
+        let output = output("parameters");
+        interrupted(&output, "fractal", &[1, 2, 3], 3);
+        assert_eq!(resume(&output, "fractal", &[1, 2, 4], 100), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(saved(&output)[0], 100);
+    }
+
+    #[test]
+    fn starts_over_for_another_kernel() {
+        let output = output("kernel");
+        interrupted(&output, "fractal", &[], 3);
+        assert_eq!(resume(&output, "raytracer", &[], 100), [0, 1, 2, 3, 4, 5]);
+    }
+}
\ No newline at end of file
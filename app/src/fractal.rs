@@ -1,22 +1,172 @@
+use std::path::Path;
 use std::sync::Arc;
 use image::{ImageBuffer, Rgba};
+use shared::fractal::{
+    FractalConstants, GradientStop, BURNING_SHIP, DISTANCE, JULIA, MANDELBROT, MULTIBROT, NEWTON,
+    ORBIT_TRAP, SMOOTH, TRICORN,
+};
 use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
-use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
-use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::device::{Device, Queue};
 use vulkano::format::ClearValue;
 use vulkano::format::Format;
 use vulkano::image::{ImageDimensions, StorageImage};
-use vulkano::image::view::ImageView;
 use vulkano::image::ImageAccess;
-use vulkano::pipeline::{ComputePipeline, PipelineBindPoint};
-use vulkano::pipeline::Pipeline;
+use vulkano::pipeline::ComputePipeline;
 use vulkano::shader::ShaderModule;
-use vulkano::sync;
-use vulkano::sync::GpuFuture;
+use crate::engine::kernel::ComputeKernel;
+use crate::engine::tiles::TiledRenderer;
 
 const SHADER_FRACTAL: &[u8] = include_bytes!(env!("fractal.fractal.spv"));
 
+const TILE_SIZE: u32 = 1024;
+
+/// Palettes to colour the fractals with, by name. Those used for cycling end with the colour
+/// they start with.
+pub const PALETTES: &[(&str, &[GradientStop])] = &[
+    ("classic", &[
+        stop(0.0, [0.0, 0.027, 0.392]),
+        stop(0.16, [0.125, 0.42, 0.796]),
+        stop(0.42, [0.929, 1.0, 1.0]),
+        stop(0.6425, [1.0, 0.667, 0.0]),
+        stop(0.8575, [0.0, 0.008, 0.0]),
+        stop(1.0, [0.0, 0.027, 0.392]),
+    ]),
+    ("fire", &[
+        stop(0.0, [0.0, 0.0, 0.0]),
+        stop(0.25, [0.5, 0.0, 0.0]),
+        stop(0.5, [1.0, 0.4, 0.0]),
+        stop(0.75, [1.0, 0.9, 0.3]),
+        stop(1.0, [0.0, 0.0, 0.0]),
+    ]),
+    ("ocean", &[
+        stop(0.0, [0.0, 0.05, 0.15]),
+        stop(0.5, [0.1, 0.6, 0.8]),
+        stop(0.75, [0.9, 1.0, 1.0]),
+        stop(1.0, [0.0, 0.05, 0.15]),
+    ]),
+    ("rainbow", &[
+        stop(0.0, [1.0, 0.0, 0.0]),
+        stop(1.0 / 6.0, [1.0, 1.0, 0.0]),
+        stop(2.0 / 6.0, [0.0, 1.0, 0.0]),
+        stop(3.0 / 6.0, [0.0, 1.0, 1.0]),
+        stop(4.0 / 6.0, [0.0, 0.0, 1.0]),
+        stop(5.0 / 6.0, [1.0, 0.0, 1.0]),
+        stop(1.0, [1.0, 0.0, 0.0]),
+    ]),
+    ("grey", &[
+        stop(0.0, [0.0, 0.0, 0.0]),
+        stop(0.5, [1.0, 1.0, 1.0]),
+        stop(1.0, [0.0, 0.0, 0.0]),
+    ]),
+];
+
+const fn stop(position: f32, color: [f32; 3]) -> GradientStop {
+    GradientStop { position, color }
+}
+
+/// Parse a shading: `smooth`, `orbit-trap` or `distance`.
+pub fn parse_shading(text: &str) -> Option<u32> {
+    match text {
+        "smooth" => Some(SMOOTH),
+        "orbit-trap" => Some(ORBIT_TRAP),
+        "distance" => Some(DISTANCE),
+        _ => None,
+    }
+}
+
+/// Built in palette called `name`.
+pub fn palette(name: &str) -> Option<&'static [GradientStop]> {
+    PALETTES.iter().find(|(n, _)| *n == name).map(|(_, stops)| *stops)
+}
+
+/// The palette the kernels look colours up in.
+pub fn palette_buffer(device: Arc<Device>, stops: &[GradientStop]) -> Arc<CpuAccessibleBuffer<[GradientStop]>> {
+    CpuAccessibleBuffer::from_iter(
+        device,
+        BufferUsage::all(),
+        false,
+        stops.iter().copied(),
+    )
+        .expect("failed to create buffer")
+}
+
+/// The whole Mandelbrot set in an image of `width` by `height` pixels.
+///
+/// The other formulas start from a Julia set of the Douady rabbit, a cubic Multibrot set and
+/// the Newton fractal of `z^3 - 1`.
+pub fn default_constants(width: u32, height: u32) -> FractalConstants {
+    FractalConstants {
+        image_size: [width, height],
+        center: [-0.5, 0.0],
+        zoom: 0.8,
+        max_iterations: 200,
+        escape_radius: 4.0,
+        formula: MANDELBROT,
+        julia_c: [-0.123, 0.745],
+        exponent: 3.0,
+        root_count: 3,
+        roots: [[1.0, 0.0], [-0.5, 0.866025], [-0.5, -0.866025], [0.0, 0.0]],
+        shading: SMOOTH,
+        palette_cycles: 4.0,
+        samples_per_side: 1,
+        ..FractalConstants::default()
+    }
+}
+
+/// Switch to `formula`, looking at the whole fractal.
+pub fn with_formula(constants: FractalConstants, formula: u32) -> FractalConstants {
+    let (center, zoom) = match formula {
+        MANDELBROT => ([-0.5, 0.0], 0.8),
+        BURNING_SHIP => ([-0.4, -0.5], 0.7),
+        TRICORN => ([-0.3, 0.0], 0.8),
+        _ => ([0.0, 0.0], 0.8),
+    };
+    FractalConstants {
+        formula,
+        center,
+        zoom,
+        ..constants
+    }
+}
+
+/// Parse a formula such as `mandelbrot`, `julia:-0.8,0.156`, `burning-ship`, `multibrot:3`,
+/// `tricorn` or `newton:1,0;-1,0;0,1` where the Newton fractal is given the roots of its
+/// polynomial. Parameters left out keep their value in `constants`.
+pub fn parse_formula(text: &str, constants: FractalConstants) -> Option<FractalConstants> {
+    let (name, parameters) = text.split_once(':').unwrap_or((text, ""));
+    let mut constants = match name {
+        "mandelbrot" => with_formula(constants, MANDELBROT),
+        "julia" => with_formula(constants, JULIA),
+        "burning-ship" => with_formula(constants, BURNING_SHIP),
+        "multibrot" => with_formula(constants, MULTIBROT),
+        "tricorn" => with_formula(constants, TRICORN),
+        "newton" => with_formula(constants, NEWTON),
+        _ => return None,
+    };
+    if parameters.is_empty() {
+        return Some(constants);
+    }
+
+    let parse_complex = |text: &str| -> Option<[f32; 2]> {
+        let (re, im) = text.split_once(',')?;
+        Some([re.trim().parse().ok()?, im.trim().parse().ok()?])
+    };
+    match constants.formula {
+        JULIA => constants.julia_c = parse_complex(parameters)?,
+        MULTIBROT => constants.exponent = parameters.parse().ok()?,
+        NEWTON => {
+            let roots = parameters.split(';').map(parse_complex).collect::<Option<Vec<_>>>()?;
+            if roots.is_empty() || roots.len() > constants.roots.len() {
+                return None;
+            }
+            constants.roots[..roots.len()].copy_from_slice(&roots);
+            constants.root_count = roots.len() as u32;
+        }
+        _ => return None,
+    }
+    Some(constants)
+}
+
 #[allow(dead_code)]
 pub fn fractal(device: Arc<Device>, queue: Arc<Queue>) {
     let image = StorageImage::new(
@@ -34,88 +184,138 @@ pub fn fractal(device: Arc<Device>, queue: Arc<Queue>) {
     let image_dimensions = image.dimensions();
     let image_dimensions_buf = [image_dimensions.width(), image_dimensions.height()];
 
-    let image_view = ImageView::new_default(image.clone()).unwrap();
+    let kernel = ComputeKernel::new(device.clone(), queue.clone(), SHADER_FRACTAL, "fractal")
+        .with_image(0, image.clone()) // 0 is the binding
+        .with_buffer(1, palette_buffer(device.clone(), PALETTES[0].1));
 
-    let image_size_vkbuffer = CpuAccessibleBuffer::from_data(
+    let buf = CpuAccessibleBuffer::from_iter(
         device.clone(),
         BufferUsage::all(),
         false,
-        image_dimensions_buf,
+        (0..image_dimensions.width() * image_dimensions.height() * 4).map(|_| 0u8),
     )
-        .expect("failed to create image size buffer");
+        .expect("failed to create buffer");
+
+    let mut builder = kernel.builder();
+    builder
+        .clear_color_image(image.clone(), ClearValue::Float([0.0, 0.0, 1.0, 1.0]))
+        .unwrap();
+    kernel.record(
+        &mut builder,
+        default_constants(image_dimensions_buf[0], image_dimensions_buf[1]),
+        [image_dimensions_buf[0], image_dimensions_buf[1], 1],
+    );
+    builder
+        .copy_image_to_buffer(image.clone(), buf.clone())
+        .unwrap();
 
+    kernel.submit(builder).wait(None).unwrap();
+
+    let buffer_content = buf.read().unwrap();
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
 
+    image.save("fractal.png").unwrap();
+
+    println!("Image stuff succeded!");
+}
+
+pub fn get_pipeline(device: Arc<Device>) -> Arc<ComputePipeline> {
     assert_eq!(SHADER_FRACTAL.len() % 4, 0);
     let fractal_shader = unsafe {
         ShaderModule::from_bytes(device.clone(), SHADER_FRACTAL)
             .unwrap()
     };
 
-    let compute_fractal = ComputePipeline::new(
+    ComputePipeline::new(
         device.clone(),
         fractal_shader.entry_point("fractal").unwrap(),
         &(),
         None,
         |_| {},
     )
-        .expect("failed to create compute pipeline");
+        .expect("failed to create compute pipeline")
+}
 
-    let layout = compute_fractal.layout().set_layouts()
-        .get(0)
-        .unwrap();
-    let set = PersistentDescriptorSet::new(
-        layout.clone(),
-        [
-            WriteDescriptorSet::image_view(0, image_view.clone()), // 0 is the binding
-            // WriteDescriptorSet::buffer(1, image_size_vkbuffer.clone()),
-        ],
-    )
-        .unwrap();
+/// Renders views of the fractals into an image of a fixed size, and reads them back.
+pub struct FractalRenderer {
+    kernel: ComputeKernel,
+    image: Arc<StorageImage>,
+    buf: Arc<CpuAccessibleBuffer<[u8]>>,
+    size: [u32; 2],
+}
 
-    let mut builder = AutoCommandBufferBuilder::primary(
-        device.clone(),
-        queue.family(),
-        CommandBufferUsage::OneTimeSubmit,
-    )
-        .unwrap();
+impl FractalRenderer {
+    /// Renderer of images of `width` by `height` pixels, coloured with the gradient through
+    /// `palette`.
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, [width, height]: [u32; 2], palette: &[GradientStop]) -> Self {
+        let image = StorageImage::new(
+            device.clone(),
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            Some(queue.family()),
+        )
+            .unwrap();
 
-    let buf = CpuAccessibleBuffer::from_iter(
-        device.clone(),
-        BufferUsage::all(),
-        false,
-        (0..image_dimensions.width() * image_dimensions.height() * 4).map(|_| 0u8),
-    )
-        .expect("failed to create buffer");
+        let kernel = ComputeKernel::new(device.clone(), queue, SHADER_FRACTAL, "fractal")
+            .with_image(0, image.clone())
+            .with_buffer(1, palette_buffer(device.clone(), palette));
 
-    builder
-        .clear_color_image(image.clone(), ClearValue::Float([0.0, 0.0, 1.0, 1.0]))
-        .unwrap()
-        .bind_pipeline_compute(compute_fractal.clone())
-        .bind_descriptor_sets(
-            PipelineBindPoint::Compute,
-            compute_fractal.layout().clone(),
-            0,
-            set,
+        let buf = CpuAccessibleBuffer::from_iter(
+            device,
+            BufferUsage::all(),
+            false,
+            (0..width * height * 4).map(|_| 0u8),
         )
-        .dispatch([image_dimensions.width() / 8, image_dimensions.height() / 8, 1])
-        .unwrap()
-        .copy_image_to_buffer(image.clone(), buf.clone())
-        .unwrap();
+            .expect("failed to create buffer");
 
-    let command_buffer_2 = builder.build().unwrap();
+        Self {
+            kernel,
+            image,
+            buf,
+            size: [width, height],
+        }
+    }
 
-    let future = sync::now(device.clone())
-        .then_execute(queue.clone(), command_buffer_2)
-        .unwrap()
-        .then_signal_fence_and_flush()
-        .unwrap();
+    /// RGBA8 pixels of the view described by `constants`, row by row.
+    pub fn render(&self, constants: FractalConstants) -> Vec<u8> {
+        let [width, height] = self.size;
+        let mut builder = self.kernel.builder();
+        self.kernel.record(&mut builder, constants, [width, height, 1]);
+        builder
+            .copy_image_to_buffer(self.image.clone(), self.buf.clone())
+            .unwrap();
 
-    future.wait(None).unwrap();
+        self.kernel.submit(builder).wait(None).unwrap();
 
-    let buffer_content = buf.read().unwrap();
-    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
+        let buffer_content = self.buf.read().unwrap();
+        buffer_content.to_vec()
+    }
+}
 
-    image.save("fractal.png").unwrap();
+/// Render the view of the fractal described by `constants` at any resolution, one tile at a time,
+/// coloured with the gradient through `palette`.
+pub fn fractal_tiled(
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    constants: FractalConstants,
+    palette: &[GradientStop],
+    output: &Path,
+) {
+    let [width, height] = constants.image_size;
+    let renderer = FractalRenderer::new(device, queue, [TILE_SIZE, TILE_SIZE], palette);
 
-    println!("Image stuff succeded!");
+    let parameters = [bytemuck::bytes_of(&constants), bytemuck::cast_slice(palette)].concat();
+
+    TiledRenderer::new(width, height, TILE_SIZE)
+        .render(output, "fractal", &parameters, |tile| {
+            renderer.render(FractalConstants {
+                tile_offset: [tile.x, tile.y],
+                ..constants
+            })
+        })
+        .expect("failed to render tiles");
 }
\ No newline at end of file
@@ -0,0 +1,350 @@
+use std::sync::Arc;
+use shared::fractal3d::{Fractal3dConstants, MANDELBOX, MANDELBULB, MENGER_SPONGE};
+use shared::glam::{vec3, Vec2, Vec3};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage, SwapchainImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sampler::Filter;
+use vulkano::shader::ShaderModule;
+use vulkano::swapchain::{AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError};
+use vulkano::{swapchain, sync};
+use vulkano::sync::{FlushError, GpuFuture};
+use winit::event::{ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::Window;
+use crate::engine;
+
+const SHADER_FRACTAL3D: &[u8] = include_bytes!(env!("fractal3d.main_cs.spv"));
+
+/// Radians the camera turns per pixel dragged.
+const ORBIT_SPEED: f32 = 0.01;
+
+/// Factor the camera distance changes by per step of the mouse wheel.
+const DOLLY_STEP: f32 = 0.9;
+
+/// Camera circling the origin, where the fractals are centred.
+#[derive(Copy, Clone)]
+struct Orbit {
+    /// Angle around the vertical axis, in radians.
+    yaw: f32,
+    /// Angle above the horizontal plane, in radians.
+    pitch: f32,
+    distance: f32,
+}
+
+impl Orbit {
+    fn eye(&self) -> Vec3 {
+        self.distance * vec3(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+}
+
+/// Fly around the Mandelbulb, the Mandelbox and the Menger sponge in the window.
+///
+/// Dragging with the left button orbits the camera around the fractal and the mouse wheel moves
+/// it closer or further. The keys 1 to 3 switch between the fractals, the up and down arrows
+/// change the iterations of their distance estimators and the left and right arrows the power
+/// of the Mandelbulb or the scale of the Mandelbox. A cycles through 1 to 4 samples per side of
+/// every pixel, to smooth the edges. The fractal is only rendered again when the view changed.
+pub fn fractal3d_window(event_loop: EventLoop<()>,
+                        device: Arc<Device>,
+                        queue: Arc<Queue>,
+                        surface: Arc<Surface<Window>>,
+                        mut swapchain: Arc<Swapchain<Window>>,
+                        mut images: Vec<Arc<SwapchainImage<Window>>>)
+{
+    let pipeline = get_pipeline(device.clone());
+
+    let mut constants = default_constants(swapchain.image_extent());
+    let mut orbit = home(constants.fractal);
+    let (mut image, mut set) = get_storage_image(device.clone(), queue.clone(), pipeline.clone(), constants.image_size);
+
+    let mut recreate_swapchain = false;
+    let mut dirty = true;
+    let mut dragging = false;
+    let mut cursor = Vec2::ZERO;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                WindowEvent::Resized(_) => {
+                    recreate_swapchain = true;
+                    surface.window().request_redraw();
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    let position = Vec2::new(position.x as f32, position.y as f32);
+                    if dragging {
+                        let delta = position - cursor;
+                        orbit.yaw -= delta.x * ORBIT_SPEED;
+                        // Stop short of the poles, where the up vector of the camera is undefined.
+                        orbit.pitch = (orbit.pitch + delta.y * ORBIT_SPEED).clamp(-1.5, 1.5);
+                        dirty = true;
+                    }
+                    cursor = position;
+                }
+                WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                    dragging = state == ElementState::Pressed;
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let steps = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 100.0,
+                    };
+                    orbit.distance *= DOLLY_STEP.powf(steps);
+                    dirty = true;
+                }
+                WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                    ..
+                } => {
+                    match key {
+                        VirtualKeyCode::Up => constants.iterations += 1,
+                        VirtualKeyCode::Down => constants.iterations = (constants.iterations - 1).max(1),
+                        VirtualKeyCode::Left if constants.fractal == MANDELBULB => constants.power -= 0.5,
+                        VirtualKeyCode::Right if constants.fractal == MANDELBULB => constants.power += 0.5,
+                        VirtualKeyCode::Left if constants.fractal == MANDELBOX => constants.scale -= 0.1,
+                        VirtualKeyCode::Right if constants.fractal == MANDELBOX => constants.scale += 0.1,
+                        VirtualKeyCode::A => constants.samples_per_side = constants.samples_per_side % 4 + 1,
+                        VirtualKeyCode::Key1 => constants = with_fractal(constants, MANDELBULB),
+                        VirtualKeyCode::Key2 => constants = with_fractal(constants, MANDELBOX),
+                        VirtualKeyCode::Key3 => constants = with_fractal(constants, MENGER_SPONGE),
+                        _ => return,
+                    }
+                    if matches!(key, VirtualKeyCode::Key1 | VirtualKeyCode::Key2 | VirtualKeyCode::Key3) {
+                        orbit = home(constants.fractal);
+                    }
+                    println!(
+                        "fractal {}, {} iterations, power {}, scale {}, {} samples per side",
+                        constants.fractal,
+                        constants.iterations,
+                        constants.power,
+                        constants.scale,
+                        constants.samples_per_side,
+                    );
+                    dirty = true;
+                }
+                _ => (),
+            },
+            Event::RedrawRequested(_) => {
+                if recreate_swapchain {
+                    let (new_swapchain, new_images) = match swapchain.recreate(SwapchainCreateInfo {
+                        image_extent: surface.window().inner_size().into(),
+                        ..swapchain.create_info()
+                    }) {
+                        Ok(r) => r,
+                        // This error tends to happen when the user is manually resizing the window.
+                        // Simply restarting the loop is the easiest way to fix this issue.
+                        Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                        Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
+                    };
+                    swapchain = new_swapchain;
+                    images = new_images;
+                    recreate_swapchain = false;
+
+                    constants.image_size = swapchain.image_extent();
+                    let (new_image, new_set) = get_storage_image(
+                        device.clone(),
+                        queue.clone(),
+                        pipeline.clone(),
+                        constants.image_size,
+                    );
+                    image = new_image;
+                    set = new_set;
+                    dirty = true;
+                }
+
+                let (image_i, suboptimal, acquire_future) =
+                    match swapchain::acquire_next_image(swapchain.clone(), None) {
+                        Ok(r) => r,
+                        Err(AcquireError::OutOfDate) => {
+                            recreate_swapchain = true;
+                            surface.window().request_redraw();
+                            return;
+                        }
+                        Err(e) => panic!("Failed to acquire next image: {:?}", e),
+                    };
+                if suboptimal {
+                    recreate_swapchain = true;
+                }
+
+                let [width, height] = constants.image_size;
+                let mut builder = AutoCommandBufferBuilder::primary(
+                    device.clone(),
+                    queue.family(),
+                    CommandBufferUsage::OneTimeSubmit,
+                )
+                    .unwrap();
+
+                if dirty {
+                    constants.eye = orbit.eye().into();
+                    builder
+                        .bind_pipeline_compute(pipeline.clone())
+                        .bind_descriptor_sets(
+                            PipelineBindPoint::Compute,
+                            pipeline.layout().clone(),
+                            0,
+                            set.clone(),
+                        )
+                        .push_constants(pipeline.layout().clone(), 0, constants)
+                        .dispatch(engine::workgroups([width, height]))
+                        .unwrap();
+                    dirty = false;
+                }
+
+                builder
+                    .blit_image(
+                        image.clone(),
+                        [0, 0, 0],
+                        [width as i32, height as i32, 1],
+                        0,
+                        0,
+                        images[image_i].clone(),
+                        [0, 0, 0],
+                        [width as i32, height as i32, 1],
+                        0,
+                        0,
+                        1,
+                        Filter::Nearest,
+                    )
+                    .unwrap();
+
+                let command_buffer = builder.build().unwrap();
+
+                let execution = sync::now(device.clone())
+                    .join(acquire_future)
+                    .then_execute(queue.clone(), command_buffer)
+                    .unwrap()
+                    .then_swapchain_present(queue.clone(), swapchain.clone(), image_i)
+                    .then_signal_fence_and_flush();
+
+                match execution {
+                    Ok(future) => {
+                        future.wait(None).unwrap(); //wait for the gpu to finish
+                    }
+                    Err(FlushError::OutOfDate) => {
+                        recreate_swapchain = true;
+                    }
+                    Err(e) => {
+                        println!("Failed to flush future: {:?}", e);
+                    }
+                }
+            }
+            _ => ()
+        }
+
+        if dirty || recreate_swapchain {
+            surface.window().request_redraw();
+        }
+    });
+}
+
+/// The Mandelbulb of power 8 seen in an image of `width` by `height` pixels.
+pub fn default_constants([width, height]: [u32; 2]) -> Fractal3dConstants {
+    Fractal3dConstants {
+        image_size: [width, height],
+        samples_per_side: 1,
+        fractal: MANDELBULB,
+        iterations: 8,
+        power: 8.0,
+        scale: -1.5,
+        eye: home(MANDELBULB).eye().into(),
+        target: [0.0, 0.0, 0.0],
+        vfov: 0.8,
+        max_steps: 256,
+        epsilon: 0.001,
+        light_direction: [0.6, 0.8, 0.4],
+        shadow_sharpness: 16.0,
+    }
+}
+
+/// Switch to `fractal`, with the iterations its details need.
+fn with_fractal(constants: Fractal3dConstants, fractal: u32) -> Fractal3dConstants {
+    let iterations = match fractal {
+        MANDELBOX => 12,
+        MENGER_SPONGE => 5,
+        _ => 8,
+    };
+    Fractal3dConstants {
+        fractal,
+        iterations,
+        ..constants
+    }
+}
+
+/// Camera seeing the whole of `fractal`.
+fn home(fractal: u32) -> Orbit {
+    let distance = match fractal {
+        MANDELBOX => 7.0,
+        MENGER_SPONGE => 4.0,
+        _ => 3.0,
+    };
+    Orbit {
+        yaw: 0.6,
+        pitch: 0.4,
+        distance,
+    }
+}
+
+fn get_pipeline(device: Arc<Device>) -> Arc<ComputePipeline> {
+    assert_eq!(SHADER_FRACTAL3D.len() % 4, 0);
+    let shader = unsafe {
+        ShaderModule::from_bytes(device.clone(), SHADER_FRACTAL3D)
+            .unwrap()
+    };
+
+    ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main_cs").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline")
+}
+
+/// Image the fractal is rendered to before being copied to the window.
+fn get_storage_image(
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    pipeline: Arc<ComputePipeline>,
+    [width, height]: [u32; 2],
+) -> (Arc<StorageImage>, Arc<PersistentDescriptorSet>) {
+    let image = StorageImage::new(
+        device.clone(),
+        ImageDimensions::Dim2d {
+            width,
+            height,
+            array_layers: 1,
+        },
+        Format::R8G8B8A8_UNORM,
+        Some(queue.family()),
+    )
+        .unwrap();
+    let image_view = ImageView::new_default(image.clone()).unwrap();
+
+    let layout = pipeline.layout().set_layouts()
+        .get(0)
+        .unwrap();
+    let set = PersistentDescriptorSet::new(
+        layout.clone(),
+        [WriteDescriptorSet::image_view(0, image_view)],
+    )
+        .unwrap();
+
+    (image, set)
+}
\ No newline at end of file
@@ -0,0 +1,322 @@
+use std::sync::Arc;
+use shared::fractal::{
+    FractalConstants, GradientStop, BURNING_SHIP, JULIA, MANDELBROT, MULTIBROT, NEWTON, TRICORN,
+};
+use shared::glam::{vec2, Vec2};
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage, SwapchainImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sampler::Filter;
+use vulkano::swapchain::{AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError};
+use vulkano::{swapchain, sync};
+use vulkano::sync::{FlushError, GpuFuture};
+use winit::event::{ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::Window;
+use crate::engine;
+use crate::fractal;
+
+/// Magnification of one step of the mouse wheel.
+const ZOOM_STEP: f32 = 1.25;
+
+/// Most iterations the up arrow goes to, which keeps a render of the whole window well within
+/// the time drivers allow a dispatch before resetting the GPU.
+const MAX_ITERATIONS: u32 = 1 << 14;
+
+/// Explore the Mandelbrot set and its relatives in the window.
+///
+/// The mouse wheel zooms around the cursor, dragging with the left button pans, and the up and
+/// down arrows double or halve the iteration count. The keys 1 to 6 switch between the
+/// Mandelbrot set, Julia sets, the Burning Ship, Multibrot sets, the Tricorn and the Newton
+/// fractal of `z^3 - 1`. Dragging with the right button moves the constant of the Julia set to
+/// the point of the Mandelbrot set under the cursor, and the left and right arrows change the
+/// power of the Multibrot set. S switches between smooth, orbit trap and distance shading, and
+/// P between the palettes. A cycles through 1 to 4 samples per side of every pixel, to smooth
+/// the edges. The fractal is only rendered again when the view changed, otherwise the last
+/// render is shown.
+pub fn fractal_window(event_loop: EventLoop<()>,
+                      device: Arc<Device>,
+                      queue: Arc<Queue>,
+                      surface: Arc<Surface<Window>>,
+                      mut swapchain: Arc<Swapchain<Window>>,
+                      mut images: Vec<Arc<SwapchainImage<Window>>>)
+{
+    let pipeline = fractal::get_pipeline(device.clone());
+
+    let [width, height] = swapchain.image_extent();
+    let mut constants = fractal::default_constants(width, height);
+    let mut palette = 0;
+    let mut palette_buffer = fractal::palette_buffer(device.clone(), fractal::PALETTES[palette].1);
+    let (mut image, mut set) = get_storage_image(
+        device.clone(),
+        queue.clone(),
+        pipeline.clone(),
+        palette_buffer.clone(),
+        [width, height],
+    );
+
+    let mut recreate_swapchain = false;
+    let mut dirty = true;
+    let mut dragging = false;
+    let mut picking_julia = false;
+    let mut cursor = Vec2::ZERO;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                WindowEvent::Resized(_) => {
+                    recreate_swapchain = true;
+                    surface.window().request_redraw();
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    let position = vec2(position.x as f32, position.y as f32);
+                    if dragging {
+                        // Move the view so that the point grabbed stays under the cursor.
+                        let delta = constants.pixel_to_complex(position) - constants.pixel_to_complex(cursor);
+                        constants.center = (Vec2::from(constants.center) - delta).into();
+                        dirty = true;
+                    }
+                    if picking_julia {
+                        constants.julia_c = julia_constant(&constants, position).into();
+                        dirty = true;
+                    }
+                    cursor = position;
+                }
+                WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                    dragging = state == ElementState::Pressed;
+                }
+                WindowEvent::MouseInput { state, button: MouseButton::Right, .. } => {
+                    picking_julia = state == ElementState::Pressed && constants.formula == JULIA;
+                    if picking_julia {
+                        constants.julia_c = julia_constant(&constants, cursor).into();
+                        dirty = true;
+                    }
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let steps = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 100.0,
+                    };
+                    // Zoom around the point under the cursor.
+                    let before = constants.pixel_to_complex(cursor);
+                    constants.zoom *= ZOOM_STEP.powf(steps);
+                    let after = constants.pixel_to_complex(cursor);
+                    constants.center = (Vec2::from(constants.center) + before - after).into();
+                    dirty = true;
+                }
+                WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                    ..
+                } => {
+                    match key {
+                        VirtualKeyCode::Up => constants.max_iterations = constants.max_iterations.saturating_mul(2).min(MAX_ITERATIONS),
+                        VirtualKeyCode::Down => constants.max_iterations = (constants.max_iterations / 2).max(16),
+                        VirtualKeyCode::Left if constants.formula == MULTIBROT => {
+                            constants.exponent = (constants.exponent - 0.5).max(1.5)
+                        }
+                        VirtualKeyCode::Right if constants.formula == MULTIBROT => constants.exponent += 0.5,
+                        VirtualKeyCode::Key1 => constants = fractal::with_formula(constants, MANDELBROT),
+                        VirtualKeyCode::Key2 => constants = fractal::with_formula(constants, JULIA),
+                        VirtualKeyCode::Key3 => constants = fractal::with_formula(constants, BURNING_SHIP),
+                        VirtualKeyCode::Key4 => constants = fractal::with_formula(constants, MULTIBROT),
+                        VirtualKeyCode::Key5 => constants = fractal::with_formula(constants, TRICORN),
+                        VirtualKeyCode::Key6 => constants = fractal::with_formula(constants, NEWTON),
+                        VirtualKeyCode::S => constants.shading = (constants.shading + 1) % 3,
+                        VirtualKeyCode::A => constants.samples_per_side = constants.samples_per_side % 4 + 1,
+                        VirtualKeyCode::P => {
+                            palette = (palette + 1) % fractal::PALETTES.len();
+                            palette_buffer = fractal::palette_buffer(device.clone(), fractal::PALETTES[palette].1);
+                            let (new_image, new_set) = get_storage_image(
+                                device.clone(),
+                                queue.clone(),
+                                pipeline.clone(),
+                                palette_buffer.clone(),
+                                constants.image_size,
+                            );
+                            image = new_image;
+                            set = new_set;
+                        }
+                        _ => return,
+                    }
+                    println!(
+                        "formula {}, {} iterations, julia constant {:?}, power {}, shading {}, palette {}, {} samples per side",
+                        constants.formula,
+                        constants.max_iterations,
+                        constants.julia_c,
+                        constants.exponent,
+                        constants.shading,
+                        fractal::PALETTES[palette].0,
+                        constants.samples_per_side,
+                    );
+                    dirty = true;
+                }
+                _ => (),
+            },
+            Event::RedrawRequested(_) => {
+                if recreate_swapchain {
+                    let (new_swapchain, new_images) = match swapchain.recreate(SwapchainCreateInfo {
+                        image_extent: surface.window().inner_size().into(),
+                        ..swapchain.create_info()
+                    }) {
+                        Ok(r) => r,
+                        // This error tends to happen when the user is manually resizing the window.
+                        // Simply restarting the loop is the easiest way to fix this issue.
+                        Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                        Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
+                    };
+                    swapchain = new_swapchain;
+                    images = new_images;
+                    recreate_swapchain = false;
+
+                    // The view keeps its centre and zoom, only the image changes size.
+                    constants.image_size = swapchain.image_extent();
+                    let (new_image, new_set) = get_storage_image(
+                        device.clone(),
+                        queue.clone(),
+                        pipeline.clone(),
+                        palette_buffer.clone(),
+                        constants.image_size,
+                    );
+                    image = new_image;
+                    set = new_set;
+                    dirty = true;
+                }
+
+                let (image_i, suboptimal, acquire_future) =
+                    match swapchain::acquire_next_image(swapchain.clone(), None) {
+                        Ok(r) => r,
+                        Err(AcquireError::OutOfDate) => {
+                            recreate_swapchain = true;
+                            surface.window().request_redraw();
+                            return;
+                        }
+                        Err(e) => panic!("Failed to acquire next image: {:?}", e),
+                    };
+                if suboptimal {
+                    recreate_swapchain = true;
+                }
+
+                let [width, height] = constants.image_size;
+                let mut builder = AutoCommandBufferBuilder::primary(
+                    device.clone(),
+                    queue.family(),
+                    CommandBufferUsage::OneTimeSubmit,
+                )
+                    .unwrap();
+
+                if dirty {
+                    builder
+                        .bind_pipeline_compute(pipeline.clone())
+                        .bind_descriptor_sets(
+                            PipelineBindPoint::Compute,
+                            pipeline.layout().clone(),
+                            0,
+                            set.clone(),
+                        )
+                        .push_constants(pipeline.layout().clone(), 0, constants)
+                        .dispatch(engine::workgroups([width, height]))
+                        .unwrap();
+                    dirty = false;
+                }
+
+                builder
+                    .blit_image(
+                        image.clone(),
+                        [0, 0, 0],
+                        [width as i32, height as i32, 1],
+                        0,
+                        0,
+                        images[image_i].clone(),
+                        [0, 0, 0],
+                        [width as i32, height as i32, 1],
+                        0,
+                        0,
+                        1,
+                        Filter::Nearest,
+                    )
+                    .unwrap();
+
+                let command_buffer = builder.build().unwrap();
+
+                let execution = sync::now(device.clone())
+                    .join(acquire_future)
+                    .then_execute(queue.clone(), command_buffer)
+                    .unwrap()
+                    .then_swapchain_present(queue.clone(), swapchain.clone(), image_i)
+                    .then_signal_fence_and_flush();
+
+                match execution {
+                    Ok(future) => {
+                        future.wait(None).unwrap(); //wait for the gpu to finish
+                    }
+                    Err(FlushError::OutOfDate) => {
+                        recreate_swapchain = true;
+                    }
+                    Err(e) => {
+                        println!("Failed to flush future: {:?}", e);
+                    }
+                }
+            }
+            _ => ()
+        }
+
+        if dirty || recreate_swapchain {
+            surface.window().request_redraw();
+        }
+    });
+}
+
+/// Constant of the Julia set for the cursor at `pixel`: the point under it if the whole
+/// Mandelbrot set filled the window, since the Julia sets of points inside it are connected.
+fn julia_constant(constants: &FractalConstants, pixel: Vec2) -> Vec2 {
+    fractal::with_formula(*constants, MANDELBROT).pixel_to_complex(pixel)
+}
+
+/// Image the fractal is rendered to before being copied to the window.
+fn get_storage_image(
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    pipeline: Arc<ComputePipeline>,
+    palette: Arc<CpuAccessibleBuffer<[GradientStop]>>,
+    [width, height]: [u32; 2],
+) -> (Arc<StorageImage>, Arc<PersistentDescriptorSet>) {
+    let image = StorageImage::new(
+        device.clone(),
+        ImageDimensions::Dim2d {
+            width,
+            height,
+            array_layers: 1,
+        },
+        Format::R8G8B8A8_UNORM,
+        Some(queue.family()),
+    )
+        .unwrap();
+    let image_view = ImageView::new_default(image.clone()).unwrap();
+
+    let layout = pipeline.layout().set_layouts()
+        .get(0)
+        .unwrap();
+    let set = PersistentDescriptorSet::new(
+        layout.clone(),
+        [
+            WriteDescriptorSet::image_view(0, image_view),
+            WriteDescriptorSet::buffer(1, palette),
+        ],
+    )
+        .unwrap();
+
+    (image, set)
+}
@@ -0,0 +1,101 @@
+//! Animations zooming from one view of a fractal into another, for demos and for comparing
+//! renders between versions.
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use shared::fractal::{FractalConstants, GradientStop};
+use vulkano::device::{Device, Queue};
+use crate::engine;
+use crate::fractal::FractalRenderer;
+
+/// Render `frames` frames zooming from the view `start` to the view `end`, at `fps` frames per
+/// second, coloured with the gradient through `palette`.
+///
+/// `output` ending in `.gif` is saved as an animated GIF and ending in `.png` or `.apng` as an
+/// animated PNG. A run of `#` in `output` saves every frame to its own PNG file instead, with
+/// the `#` replaced by the zero padded frame number.
+///
+/// `frames` and `fps` must be at least 1.
+pub fn fractal_zoom(
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    start: FractalConstants,
+    end: FractalConstants,
+    frames: u32,
+    fps: u32,
+    palette: &[GradientStop],
+    output: &str,
+) {
+    let [width, height] = start.image_size;
+    let renderer = FractalRenderer::new(device, queue, [width, height], palette);
+    let render = |frame: u32| {
+        let t = frame as f64 / (frames - 1).max(1) as f64;
+        let pixels = renderer.render(interpolate(&start, &end, t));
+        println!("Frame {}/{} rendered", frame + 1, frames);
+        RgbaImage::from_raw(width, height, pixels).unwrap()
+    };
+
+    if output.contains('#') {
+        for frame in 0..frames {
+            render(frame).save(engine::frame_path(output, frame)).unwrap();
+        }
+        return;
+    }
+
+    let file = BufWriter::new(File::create(output).expect("failed to create output"));
+    match Path::new(output).extension().and_then(|extension| extension.to_str()) {
+        Some("gif") => {
+            let mut encoder = GifEncoder::new(file);
+            encoder.set_repeat(Repeat::Infinite).unwrap();
+            for frame in 0..frames {
+                let delay = Delay::from_numer_denom_ms(1000, fps);
+                encoder.encode_frame(Frame::from_parts(render(frame), 0, 0, delay)).unwrap();
+            }
+        }
+        Some("png") | Some("apng") => {
+            let mut encoder = png::Encoder::new(file, width, height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            // Loops forever.
+            encoder.set_animated(frames, 0).unwrap();
+            let fps = u16::try_from(fps).expect("fps too high for an animated PNG");
+            encoder.set_frame_delay(1, fps).unwrap();
+            let mut writer = encoder.write_header().unwrap();
+            for frame in 0..frames {
+                writer.write_image_data(&render(frame)).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        _ => panic!("unsupported animation format `{}`, expected .gif, .png or .apng", output),
+    }
+}
+
+/// View at `t`, from 0 to 1, of the zoom from `start` to `end`.
+///
+/// The zoom grows by the same factor every frame, and the centre moves by the same fraction of
+/// the view, so the motion looks as fast when deep in as when far out. Everything but the view
+/// is taken from `start`.
+pub fn interpolate(start: &FractalConstants, end: &FractalConstants, t: f64) -> FractalConstants {
+    let (start_zoom, end_zoom) = (start.zoom as f64, end.zoom as f64);
+    let zoom = start_zoom * (end_zoom / start_zoom).powf(t);
+    // The view is `1 / zoom` wide, so the centre covers the same distance along `1 / zoom`.
+    let s = if (end_zoom - start_zoom).abs() > 1e-9 * start_zoom {
+        (1.0 / zoom - 1.0 / start_zoom) / (1.0 / end_zoom - 1.0 / start_zoom)
+    } else {
+        t
+    };
+    let center = [0, 1].map(|i| {
+        let (a, b) = (start.center[i] as f64, end.center[i] as f64);
+        (a + (b - a) * s) as f32
+    });
+    FractalConstants {
+        center,
+        zoom: zoom as f32,
+        ..*start
+    }
+}
\ No newline at end of file
@@ -0,0 +1,90 @@
+//! Multiplication of `f32` matrices, checked against and timed with a naive multiplication on
+//! the CPU.
+
+use std::sync::Arc;
+use std::time::Instant;
+use shared::gemm::{GemmConstants, THREADS_M, THREADS_N, TILE_M, TILE_N};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::device::{Device, Queue};
+use crate::engine::kernel::ComputeKernel;
+use crate::engine::Xorshift;
+
+const SHADER_GEMM: &[u8] = include_bytes!(env!("gemm.gemm.spv"));
+
+/// Multiplications timed on the GPU, after one untimed warm up multiplication.
+const BENCHMARK_RUNS: u32 = 10;
+
+/// Largest difference allowed between the GPU and the CPU per term of the sums, for rounding
+/// in a different order.
+const TOLERANCE: f32 = 1e-5;
+
+/// Multiply a random `m` by `k` matrix with a random `k` by `n` matrix on the GPU and on the
+/// CPU, compare the products and report how fast both went.
+pub fn gemm_benchmark(device: Arc<Device>, queue: Arc<Queue>, [m, n, k]: [u32; 3]) {
+    println!("Multiplying {}x{} by {}x{}", m, k, k, n);
+
+    let mut random = Xorshift::default();
+    let a: Vec<f32> = (0..m * k).map(|_| random.signed()).collect();
+    let b: Vec<f32> = (0..k * n).map(|_| random.signed()).collect();
+
+    let buffer = |data: &[f32]| {
+        CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, data.iter().copied())
+            .expect("failed to create buffer")
+    };
+    let c = buffer(&vec![0.0; (m * n) as usize]);
+    let kernel = ComputeKernel::new(device.clone(), queue, SHADER_GEMM, "gemm")
+        .with_buffer(0, buffer(&a))
+        .with_buffer(1, buffer(&b))
+        .with_buffer(2, c.clone());
+
+    let constants = GemmConstants { m, n, k };
+    // One workgroup per tile of `C`.
+    let size = [
+        (n + TILE_N - 1) / TILE_N * THREADS_N,
+        (m + TILE_M - 1) / TILE_M * THREADS_M,
+        1,
+    ];
+    kernel.run(constants, size);
+    let mut builder = kernel.builder();
+    for _ in 0..BENCHMARK_RUNS {
+        kernel.record(&mut builder, constants, size);
+    }
+    let start = Instant::now();
+    kernel.submit(builder).wait(None).unwrap();
+    let gpu_time = start.elapsed() / BENCHMARK_RUNS;
+
+    let start = Instant::now();
+    let expected = matmul(&a, &b, [m, n, k]);
+    let cpu_time = start.elapsed();
+
+    let c = c.read().unwrap();
+    let error = c
+        .iter()
+        .zip(&expected)
+        .map(|(x, y)| (x - y).abs())
+        .fold(0.0, f32::max);
+
+    let flop = 2.0 * m as f64 * n as f64 * k as f64;
+    for (name, duration) in [("gpu", gpu_time), ("cpu", cpu_time)] {
+        println!(
+            "{:>4}: {:10.3} ms, {:8.2} GFLOP/s",
+            name,
+            duration.as_secs_f64() * 1000.0,
+            flop / duration.as_secs_f64() / 1e9,
+        );
+    }
+    println!("largest difference: {:e}", error);
+    assert!(error <= TOLERANCE * k as f32, "the GPU and the CPU disagree");
+}
+
+/// `A B` for the row major matrices `A` of `m` by `k` and `B` of `k` by `n`, one term at a time.
+fn matmul(a: &[f32], b: &[f32], [m, n, k]: [u32; 3]) -> Vec<f32> {
+    let (m, n, k) = (m as usize, n as usize, k as usize);
+    let mut c = vec![0.0; m * n];
+    for row in 0..m {
+        for col in 0..n {
+            c[row * n + col] = (0..k).map(|i| a[row * k + i] * b[i * n + col]).sum();
+        }
+    }
+    c
+}
\ No newline at end of file
@@ -0,0 +1,374 @@
+//! Image processing: images loaded with the `image` crate go through a chain of kernels, from
+//! one storage image to the next, and are read back once at the end.
+
+use std::path::Path;
+use std::sync::Arc;
+use image::{DynamicImage, Rgba32FImage};
+use shared::imaging::{ImagingConstants, BILINEAR, LANCZOS};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::{ImageDimensions, StorageImage};
+use crate::engine::kernel::ComputeKernel;
+
+const SHADER_BLUR: &[u8] = include_bytes!(env!("imaging.blur.spv"));
+const SHADER_SOBEL: &[u8] = include_bytes!(env!("imaging.sobel.spv"));
+const SHADER_CONVOLVE: &[u8] = include_bytes!(env!("imaging.convolve.spv"));
+const SHADER_RESIZE: &[u8] = include_bytes!(env!("imaging.resize.spv"));
+const SHADER_ADJUST: &[u8] = include_bytes!(env!("imaging.adjust.spv"));
+
+/// One step of the chain run by `ImageProcessor::process`.
+pub enum Operation {
+    /// Gaussian blur of standard deviation `sigma`, in texels.
+    Blur { sigma: f32 },
+    /// Edges, as the magnitude of the gradient of the luminance.
+    Sobel,
+    /// Weighted sum of the texels around every texel, with the row major `weights` of `size`.
+    Convolve { size: [u32; 2], weights: Vec<f32> },
+    /// Resample to `size` with `filter`, `BILINEAR` or `LANCZOS`.
+    Resize { size: [u32; 2], filter: u32 },
+    /// The brightness, contrast, saturation and gamma of the constants.
+    Adjust(ImagingConstants),
+}
+
+/// Constants leaving the colours as they are.
+pub fn default_constants() -> ImagingConstants {
+    ImagingConstants {
+        brightness: 0.0,
+        contrast: 1.0,
+        saturation: 1.0,
+        gamma: 1.0,
+        ..ImagingConstants::default()
+    }
+}
+
+/// Parse an operation: `blur:<sigma>`, `sobel`, `convolve:<kernel file>` with the file as in
+/// `parse_kernel`, `resize:<width>x<height>[:bilinear|lanczos]` (Lanczos by default) or
+/// `adjust:brightness=0.1,contrast=1.2,saturation=0.8,gamma=2.2` with any of the parameters.
+pub fn parse_operation(text: &str) -> Option<Operation> {
+    let (name, parameters) = text.split_once(':').unwrap_or((text, ""));
+    let operation = match name {
+        "blur" => Operation::Blur { sigma: parameters.parse().ok().filter(|&sigma| sigma > 0.0)? },
+        "sobel" if parameters.is_empty() => Operation::Sobel,
+        "convolve" => {
+            let (size, weights) = parse_kernel(&std::fs::read_to_string(parameters).ok()?)?;
+            Operation::Convolve { size, weights }
+        }
+        "resize" => {
+            let (size, filter) = parameters.split_once(':').unwrap_or((parameters, "lanczos"));
+            let (width, height) = size.split_once('x')?;
+            let size = [width.parse().ok()?, height.parse().ok()?];
+            if size.contains(&0) {
+                return None;
+            }
+            let filter = match filter {
+                "bilinear" => BILINEAR,
+                "lanczos" => LANCZOS,
+                _ => return None,
+            };
+            Operation::Resize { size, filter }
+        }
+        "adjust" => {
+            let mut constants = default_constants();
+            for parameter in parameters.split(',').filter(|parameter| !parameter.is_empty()) {
+                let (key, value) = parameter.split_once('=')?;
+                let value = value.trim().parse().ok()?;
+                match key.trim() {
+                    "brightness" => constants.brightness = value,
+                    "contrast" => constants.contrast = value,
+                    "saturation" => constants.saturation = value,
+                    "gamma" if value > 0.0 => constants.gamma = value,
+                    _ => return None,
+                }
+            }
+            Operation::Adjust(constants)
+        }
+        _ => return None,
+    };
+    Some(operation)
+}
+
+/// Parse the weights of a convolution, one row per line with the weights separated by spaces
+/// or commas, e.g. `1 2 1` over three lines for a blur. Lines starting with `#` are comments.
+///
+/// The weights are divided by their sum, unless it is zero as for edge detection, so that the
+/// image keeps its brightness.
+pub fn parse_kernel(text: &str) -> Option<([u32; 2], Vec<f32>)> {
+    let rows = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|weight| !weight.is_empty())
+                .map(|weight| weight.parse().ok())
+                .collect::<Option<Vec<f32>>>()
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let columns = rows.first()?.len();
+    if columns == 0 || rows.iter().any(|row| row.len() != columns) {
+        return None;
+    }
+
+    let mut weights: Vec<f32> = rows.concat();
+    let sum: f32 = weights.iter().sum();
+    if sum.abs() > f32::EPSILON {
+        weights.iter_mut().for_each(|weight| *weight /= sum);
+    }
+    Some(([columns as u32, rows.len() as u32], weights))
+}
+
+/// Runs chains of operations on images, one command buffer per chain.
+pub struct ImageProcessor {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    blur: ComputeKernel,
+    sobel: ComputeKernel,
+    convolve: ComputeKernel,
+    resize: ComputeKernel,
+    adjust: ComputeKernel,
+}
+
+impl ImageProcessor {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>) -> Self {
+        let kernel = |bytes: &[u8], entry_point: &str| {
+            ComputeKernel::new(device.clone(), queue.clone(), bytes, entry_point)
+        };
+        Self {
+            blur: kernel(SHADER_BLUR, "blur"),
+            sobel: kernel(SHADER_SOBEL, "sobel"),
+            convolve: kernel(SHADER_CONVOLVE, "convolve"),
+            resize: kernel(SHADER_RESIZE, "resize"),
+            adjust: kernel(SHADER_ADJUST, "adjust"),
+            device,
+            queue,
+        }
+    }
+
+    /// `input` after every one of `operations`, in order.
+    pub fn process(&self, input: &Rgba32FImage, operations: &[Operation]) -> Rgba32FImage {
+        let (width, height) = input.dimensions();
+        let upload = CpuAccessibleBuffer::from_iter(
+            self.device.clone(),
+            BufferUsage::all(),
+            false,
+            input.as_raw().iter().copied(),
+        )
+            .expect("failed to create buffer");
+        let mut image = self.image([width, height]);
+        let mut size = [width, height];
+
+        // Every operation waits for the one before it to be done with its image, which the
+        // command buffer takes care of.
+        let mut builder = self.blur.builder();
+        builder
+            .copy_buffer_to_image(upload, image.clone())
+            .unwrap();
+        for operation in operations {
+            let (output, output_size) = self.record(&mut builder, operation, image, size);
+            image = output;
+            size = output_size;
+        }
+
+        let download = CpuAccessibleBuffer::from_iter(
+            self.device.clone(),
+            BufferUsage::all(),
+            false,
+            (0..size[0] * size[1] * 4).map(|_| 0.0f32),
+        )
+            .expect("failed to create buffer");
+        builder
+            .copy_image_to_buffer(image, download.clone())
+            .unwrap();
+        self.blur.submit(builder).wait(None).unwrap();
+
+        let content = download.read().unwrap();
+        Rgba32FImage::from_raw(size[0], size[1], content.to_vec()).unwrap()
+    }
+
+    /// Record `operation` on `input` of `size`, returning the image it writes to and its size.
+    fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        operation: &Operation,
+        input: Arc<StorageImage>,
+        size: [u32; 2],
+    ) -> (Arc<StorageImage>, [u32; 2]) {
+        let output_size = match operation {
+            Operation::Resize { size, .. } => *size,
+            _ => size,
+        };
+        let output = self.image(output_size);
+        let constants = ImagingConstants {
+            input_size: size,
+            output_size,
+            ..default_constants()
+        };
+        match operation {
+            Operation::Blur { sigma } => {
+                let along_x = self.image(size);
+                let constants = ImagingConstants { sigma: *sigma, ..constants };
+                let pass = |direction| ImagingConstants { direction, ..constants };
+                record_kernel(builder, self.blur.clone(), pass([1, 0]), input, along_x.clone());
+                record_kernel(builder, self.blur.clone(), pass([0, 1]), along_x, output.clone());
+            }
+            Operation::Sobel => record_kernel(builder, self.sobel.clone(), constants, input, output.clone()),
+            Operation::Convolve { size: kernel_size, weights } => {
+                let weights = CpuAccessibleBuffer::from_iter(
+                    self.device.clone(),
+                    BufferUsage::all(),
+                    false,
+                    weights.iter().copied(),
+                )
+                    .expect("failed to create buffer");
+                let constants = ImagingConstants { kernel_size: *kernel_size, ..constants };
+                let kernel = self.convolve.clone().with_buffer(2, weights);
+                record_kernel(builder, kernel, constants, input, output.clone());
+            }
+            Operation::Resize { filter, .. } => {
+                let constants = ImagingConstants { filter: *filter, ..constants };
+                record_kernel(builder, self.resize.clone(), constants, input, output.clone());
+            }
+            Operation::Adjust(adjustments) => {
+                let constants = ImagingConstants { input_size: size, output_size, ..*adjustments };
+                record_kernel(builder, self.adjust.clone(), constants, input, output.clone());
+            }
+        }
+        (output, output_size)
+    }
+
+    /// Storage image of `size` with a `f32` per channel, so that the operations in between do
+    /// not round the colours.
+    fn image(&self, [width, height]: [u32; 2]) -> Arc<StorageImage> {
+        StorageImage::new(
+            self.device.clone(),
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            Format::R32G32B32A32_SFLOAT,
+            Some(self.queue.family()),
+        )
+            .unwrap()
+    }
+}
+
+/// Record `kernel` reading `input` and writing every texel of `output`.
+fn record_kernel(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    kernel: ComputeKernel,
+    constants: ImagingConstants,
+    input: Arc<StorageImage>,
+    output: Arc<StorageImage>,
+) {
+    let [width, height] = constants.output_size;
+    kernel
+        .with_image(0, input)
+        .with_image(1, output)
+        .record(builder, constants, [width, height, 1]);
+}
+
+/// Load the image at `input`, run it through `operations` and save the result to `output`,
+/// in the formats of their extensions.
+pub fn process_file(device: Arc<Device>, queue: Arc<Queue>, input: &Path, operations: &[Operation], output: &Path) {
+    let image = image::open(input)
+        .expect("failed to open the input image")
+        .into_rgba32f();
+    println!("Processing a {}x{} image", image.width(), image.height());
+
+    let result = ImageProcessor::new(device, queue).process(&image, operations);
+    DynamicImage::ImageRgba32F(result)
+        .into_rgba8()
+        .save(output)
+        .expect("failed to save the output image");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernels_are_normalised() {
+        let (size, weights) = parse_kernel("1 2 1\n2 4 2\n1 2 1").unwrap();
+        assert_eq!(size, [3, 3]);
+        assert_eq!(weights, [1.0, 2.0, 1.0, 2.0, 4.0, 2.0, 1.0, 2.0, 1.0].map(|weight| weight / 16.0));
+    }
+
+    #[test]
+    fn kernels_summing_to_zero_are_kept() {
+        let text = "# Sobel along x\n-1, 0, 1\n\n  -2,0,2  \n-1 0 1\n";
+        let (size, weights) = parse_kernel(text).unwrap();
+        assert_eq!(size, [3, 3]);
+        assert_eq!(weights, [-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0]);
+        // Rows and columns may differ.
+        assert_eq!(parse_kernel("1 1 1 1 1").unwrap().0, [5, 1]);
+    }
+
+    #[test]
+    fn malformed_kernels_are_rejected() {
+        for text in ["", "# nothing\n", "1 2\n3", "1 x 1", "1 2 1\n\n2 4\n"] {
+            assert!(parse_kernel(text).is_none(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn operations_are_parsed() {
+        assert!(matches!(parse_operation("blur:1.5"), Some(Operation::Blur { sigma }) if sigma == 1.5));
+        assert!(matches!(parse_operation("sobel"), Some(Operation::Sobel)));
+        assert!(matches!(
+            parse_operation("resize:640x480"),
+            Some(Operation::Resize { size: [640, 480], filter: LANCZOS })
+        ));
+        assert!(matches!(
+            parse_operation("resize:32x16:bilinear"),
+            Some(Operation::Resize { size: [32, 16], filter: BILINEAR })
+        ));
+        match parse_operation("adjust:brightness=0.1, gamma=2.2") {
+            Some(Operation::Adjust(constants)) => {
+                assert_eq!(constants.brightness, 0.1);
+                assert_eq!(constants.contrast, 1.0);
+                assert_eq!(constants.saturation, 1.0);
+                assert_eq!(constants.gamma, 2.2);
+            }
+            _ => panic!("adjust not parsed"),
+        }
+        assert!(matches!(parse_operation("adjust"), Some(Operation::Adjust(constants)) if constants.gamma == 1.0));
+    }
+
+    #[test]
+    fn convolutions_read_their_kernel_file() {
+        let path = std::env::temp_dir().join(format!("imaging-kernel-{}.txt", std::process::id()));
+        std::fs::write(&path, "0 1 0\n1 -4 1\n0 1 0\n").unwrap();
+        let operation = parse_operation(&format!("convolve:{}", path.display()));
+        std::fs::remove_file(&path).unwrap();
+        match operation {
+            Some(Operation::Convolve { size, weights }) => {
+                assert_eq!(size, [3, 3]);
+                assert_eq!(weights, [0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0]);
+            }
+            _ => panic!("convolve not parsed"),
+        }
+        assert!(parse_operation("convolve:no such kernel file").is_none());
+    }
+
+    #[test]
+    fn malformed_operations_are_rejected() {
+        for text in [
+            "blur",
+            "blur:0",
+            "blur:-1",
+            "sobel:1",
+            "resize:640",
+            "resize:0x480",
+            "resize:640x480:cubic",
+            "adjust:gamma=0",
+            "adjust:hue=1",
+            "adjust:brightness",
+            "sharpen",
+        ] {
+            assert!(parse_operation(text).is_none(), "{}", text);
+        }
+    }
+}
\ No newline at end of file
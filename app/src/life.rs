@@ -0,0 +1,248 @@
+//! Outer totalistic cellular automata such as Conway's Game of Life, stepped on the GPU from
+//! one storage image to the other and shown by `life_window::life_window`.
+
+use std::sync::Arc;
+use shared::life::LifeConstants;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::{Device, Queue};
+use vulkano::format::{ClearValue, Format};
+use vulkano::image::{ImageDimensions, StorageImage};
+use crate::engine::kernel::ComputeKernel;
+use crate::engine::Xorshift;
+
+const SHADER_STEP: &[u8] = include_bytes!(env!("life.step.spv"));
+const SHADER_PAINT: &[u8] = include_bytes!(env!("life.paint.spv"));
+const SHADER_DRAW: &[u8] = include_bytes!(env!("life.draw.spv"));
+
+/// Rule followed when none is given, Conway's Game of Life.
+pub const DEFAULT_RULE: &str = "B3/S23";
+
+/// Columns and rows of the grid when none are given.
+pub const DEFAULT_SIZE: [u32; 2] = [512, 512];
+
+/// Named rules, accepted by `parse_rule` along with rule strings.
+pub const PRESETS: [(&str, &str); 5] = [
+    ("life", "B3/S23"),
+    ("highlife", "B36/S23"),
+    ("seeds", "B2/S"),
+    ("daynight", "B3678/S34678"),
+    ("replicator", "B1357/S1357"),
+];
+
+/// Share of the cells alive after `Life::randomize`.
+const DENSITY: f32 = 0.3;
+
+/// Parse a rule as the neighbour counts for which cells are born and survive, e.g. `B3/S23`
+/// for Conway's Game of Life, in either order and any case, or one of the names of `PRESETS`.
+/// Returns the `birth` and `survival` bit masks of `LifeConstants`.
+pub fn parse_rule(text: &str) -> Option<(u32, u32)> {
+    let text = PRESETS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(text))
+        .map_or(text, |(_, rule)| rule);
+    let (mut birth, mut survival) = (None, None);
+    for part in text.split('/') {
+        let mut chars = part.trim().chars();
+        let counts = match chars.next()?.to_ascii_uppercase() {
+            'B' => &mut birth,
+            'S' => &mut survival,
+            _ => return None,
+        };
+        if counts.is_some() {
+            return None;
+        }
+        let mut mask = 0;
+        for count in chars {
+            mask |= 1 << count.to_digit(10).filter(|&count| count <= 8)?;
+        }
+        *counts = Some(mask);
+    }
+    Some((birth?, survival?))
+}
+
+/// The rule string of the `birth` and `survival` bit masks, as parsed by `parse_rule`.
+pub fn rule_string(birth: u32, survival: u32) -> String {
+    let counts = |mask: u32| {
+        (0..=8u32)
+            .filter(|count| (mask >> count) & 1 != 0)
+            .map(|count| count.to_string())
+            .collect::<String>()
+    };
+    format!("B{}/S{}", counts(birth), counts(survival))
+}
+
+/// A grid of cells in two storage images, the `step` kernel reading the current generation
+/// from either and writing the next one to the other.
+pub struct Life {
+    device: Arc<Device>,
+    constants: LifeConstants,
+    images: [Arc<StorageImage>; 2],
+    /// The kernels stepping from either image to the other.
+    steps: [ComputeKernel; 2],
+    /// The kernels painting into either image.
+    paints: [ComputeKernel; 2],
+    draw: ComputeKernel,
+    /// Which of `images` holds the current generation.
+    current: usize,
+    generation: u64,
+    /// Generator of `randomize`, carrying on from one call to the next.
+    random: Xorshift,
+}
+
+impl Life {
+    /// A grid of `size` cells following the rule of the `birth` and `survival` bit masks, with
+    /// random cells alive.
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, size: [u32; 2], (birth, survival): (u32, u32)) -> Self {
+        assert!(size[0] > 0 && size[1] > 0, "the grid must have cells");
+        let [width, height] = size;
+        let images = [0, 1].map(|_| {
+            StorageImage::new(
+                device.clone(),
+                ImageDimensions::Dim2d {
+                    width,
+                    height,
+                    array_layers: 1,
+                },
+                Format::R8G8B8A8_UNORM,
+                Some(queue.family()),
+            )
+                .unwrap()
+        });
+
+        let step = ComputeKernel::new(device.clone(), queue.clone(), SHADER_STEP, "step");
+        let steps = [0, 1].map(|from| {
+            step.clone()
+                .with_image(0, images[from].clone())
+                .with_image(1, images[1 - from].clone())
+        });
+        let paint = ComputeKernel::new(device.clone(), queue.clone(), SHADER_PAINT, "paint");
+        let paints = [0, 1].map(|i| paint.clone().with_image(0, images[i].clone()));
+
+        let mut life = Self {
+            constants: LifeConstants {
+                size,
+                birth,
+                survival,
+                ..LifeConstants::default()
+            },
+            images,
+            steps,
+            paints,
+            draw: ComputeKernel::new(device.clone(), queue, SHADER_DRAW, "draw"),
+            device,
+            current: 0,
+            generation: 0,
+            random: Xorshift::default(),
+        };
+        life.randomize();
+        life
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.constants.size
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The `birth` and `survival` bit masks of the rule.
+    pub fn rule(&self) -> (u32, u32) {
+        (self.constants.birth, self.constants.survival)
+    }
+
+    /// Follow the rule of the `birth` and `survival` bit masks from the next step on.
+    pub fn set_rule(&mut self, (birth, survival): (u32, u32)) {
+        self.constants.birth = birth;
+        self.constants.survival = survival;
+    }
+
+    /// Record a step into `builder`. Commands recorded after it see the next generation.
+    pub fn record_step(&mut self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        let [width, height] = self.constants.size;
+        self.steps[self.current].record(builder, self.constants, [width, height, 1]);
+        self.current = 1 - self.current;
+        self.generation += 1;
+    }
+
+    /// Record bringing the cells within `radius` of `cell` alive into `builder`.
+    pub fn record_paint(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        cell: [u32; 2],
+        radius: u32,
+    ) {
+        // Past half the grid, the brush would wrap around onto itself.
+        let radius = radius.min((self.constants.size[0].min(self.constants.size[1]) - 1) / 2);
+        let constants = LifeConstants {
+            brush_center: cell,
+            brush_radius: radius,
+            ..self.constants
+        };
+        let side = 2 * radius + 1;
+        self.paints[self.current].record(builder, constants, [side, side, 1]);
+    }
+
+    /// Constants drawing the grid into an image of `image_size` pixels, `zoom` pixels per cell
+    /// around `center`, in cells.
+    pub fn view(&self, image_size: [u32; 2], center: [f32; 2], zoom: f32) -> LifeConstants {
+        LifeConstants {
+            image_size,
+            view_center: center,
+            zoom,
+            ..self.constants
+        }
+    }
+
+    /// Record drawing the current generation into `image` with the constants of `view` into
+    /// `builder`.
+    pub fn record_draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: Arc<StorageImage>,
+        view: LifeConstants,
+    ) {
+        let [width, height] = view.image_size;
+        self.draw.clone()
+            .with_image(0, self.images[self.current].clone())
+            .with_image(1, image)
+            .record(builder, view, [width, height, 1]);
+    }
+
+    /// Kill every cell, and wait for it.
+    pub fn clear(&mut self) {
+        let mut builder = self.draw.builder();
+        builder
+            .clear_color_image(self.images[self.current].clone(), ClearValue::Float([0.0, 0.0, 0.0, 1.0]))
+            .unwrap();
+        self.draw.submit(builder).wait(None).unwrap();
+        self.generation = 0;
+    }
+
+    /// Bring a random `DENSITY` of the cells alive and kill the others, and wait for it.
+    pub fn randomize(&mut self) {
+        let [width, height] = self.constants.size;
+        let random = &mut self.random;
+        let texels: Vec<u8> = (0..width * height)
+            .flat_map(|_| {
+                let value = if random.unit() < DENSITY { 255 } else { 0 };
+                [value, value, value, 255]
+            })
+            .collect();
+
+        let upload = CpuAccessibleBuffer::from_iter(
+            self.device.clone(),
+            BufferUsage::transfer_source(),
+            false,
+            texels.into_iter(),
+        )
+            .expect("failed to create buffer");
+        let mut builder = self.draw.builder();
+        builder
+            .copy_buffer_to_image(upload, self.images[self.current].clone())
+            .unwrap();
+        self.draw.submit(builder).wait(None).unwrap();
+        self.generation = 0;
+    }
+}
\ No newline at end of file
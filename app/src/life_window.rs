@@ -0,0 +1,297 @@
+use std::sync::Arc;
+use shared::glam::{vec2, Vec2};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::{ImageDimensions, StorageImage, SwapchainImage};
+use vulkano::sampler::Filter;
+use vulkano::swapchain::{AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError};
+use vulkano::{swapchain, sync};
+use vulkano::sync::{FlushError, GpuFuture};
+use winit::event::{ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::Window;
+use crate::life::{self, Life};
+
+/// Magnification of one step of the mouse wheel.
+const ZOOM_STEP: f32 = 1.25;
+
+/// Most steps run per frame.
+const MAX_STEPS_PER_FRAME: u32 = 64;
+
+/// Largest radius of the brush, in cells.
+const MAX_BRUSH_RADIUS: u32 = 16;
+
+/// Run a cellular automaton of `size` cells following the rule of the `birth` and `survival`
+/// bit masks in the window, from random cells.
+///
+/// The mouse wheel zooms around the cursor and dragging with the right button pans. Dragging
+/// with the left button brings the cells under the cursor alive, with a brush whose radius the
+/// `[` and `]` keys change. Space pauses and N runs a single step, the up and down arrows
+/// double or halve the steps run per frame, C kills every cell, R brings random cells alive
+/// and the keys 1 to 5 switch to the rules of `life::PRESETS`.
+///
+/// Every frame records the painting, the steps, the drawing of the grid into an image the
+/// size of the window and its copy to the window into a single command buffer, which makes
+/// every command wait for the ones before it.
+pub fn life_window(event_loop: EventLoop<()>,
+                   device: Arc<Device>,
+                   queue: Arc<Queue>,
+                   surface: Arc<Surface<Window>>,
+                   mut swapchain: Arc<Swapchain<Window>>,
+                   mut images: Vec<Arc<SwapchainImage<Window>>>,
+                   size: [u32; 2],
+                   rule: (u32, u32))
+{
+    let mut life = Life::new(device.clone(), queue.clone(), size, rule);
+    let mut image = get_storage_image(device.clone(), queue.clone(), swapchain.image_extent());
+
+    // The whole grid, as large as it fits.
+    let [width, height] = swapchain.image_extent();
+    let mut zoom = (width as f32 / size[0] as f32).min(height as f32 / size[1] as f32);
+    let mut center = 0.5 * vec2(size[0] as f32, size[1] as f32);
+
+    let mut paused = false;
+    let mut single_step = false;
+    let mut steps_per_frame = 1;
+    let mut brush_radius = 0;
+    let mut painting = false;
+    let mut panning = false;
+    let mut cursor = Vec2::ZERO;
+    // Cells to paint at the next frame.
+    let mut strokes = Vec::new();
+    let mut window_resized = false;
+    let mut recreate_swapchain = false;
+    print_state(&life, paused, steps_per_frame, brush_radius);
+
+    event_loop.run(move |event, _, control_flow| {
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                WindowEvent::Resized(_) => {
+                    window_resized = true;
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    let position = vec2(position.x as f32, position.y as f32);
+                    let view = life.view(swapchain.image_extent(), center.into(), zoom);
+                    if panning {
+                        // Move the view so that the cell grabbed stays under the cursor.
+                        center -= view.pixel_to_cell(position) - view.pixel_to_cell(cursor);
+                    }
+                    if painting {
+                        // Every cell along the way, even when the cursor moves several cells
+                        // between two events.
+                        let from = view.pixel_to_cell(cursor);
+                        let to = view.pixel_to_cell(position);
+                        let samples = (from.distance(to) / (brush_radius as f32 + 0.5)).ceil().max(1.0) as u32;
+                        for i in 1..=samples {
+                            strokes.extend(cell(size, from.lerp(to, i as f32 / samples as f32)));
+                        }
+                    }
+                    cursor = position;
+                }
+                WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                    painting = state == ElementState::Pressed;
+                    if painting {
+                        let view = life.view(swapchain.image_extent(), center.into(), zoom);
+                        strokes.extend(cell(size, view.pixel_to_cell(cursor)));
+                    }
+                }
+                WindowEvent::MouseInput { state, button: MouseButton::Right, .. } => {
+                    panning = state == ElementState::Pressed;
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let steps = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 100.0,
+                    };
+                    // Zoom around the cell under the cursor.
+                    let image_size = swapchain.image_extent();
+                    let before = life.view(image_size, center.into(), zoom).pixel_to_cell(cursor);
+                    zoom *= ZOOM_STEP.powf(steps);
+                    let after = life.view(image_size, center.into(), zoom).pixel_to_cell(cursor);
+                    center += before - after;
+                }
+                WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                    ..
+                } => {
+                    match key {
+                        VirtualKeyCode::Space => paused = !paused,
+                        VirtualKeyCode::N => {
+                            paused = true;
+                            single_step = true;
+                        }
+                        VirtualKeyCode::Up => steps_per_frame = (steps_per_frame * 2).min(MAX_STEPS_PER_FRAME),
+                        VirtualKeyCode::Down => steps_per_frame = (steps_per_frame / 2).max(1),
+                        VirtualKeyCode::LBracket => brush_radius = brush_radius.saturating_sub(1),
+                        VirtualKeyCode::RBracket => brush_radius = (brush_radius + 1).min(MAX_BRUSH_RADIUS),
+                        VirtualKeyCode::C => life.clear(),
+                        VirtualKeyCode::R => life.randomize(),
+                        VirtualKeyCode::Key1 => life.set_rule(preset(0)),
+                        VirtualKeyCode::Key2 => life.set_rule(preset(1)),
+                        VirtualKeyCode::Key3 => life.set_rule(preset(2)),
+                        VirtualKeyCode::Key4 => life.set_rule(preset(3)),
+                        VirtualKeyCode::Key5 => life.set_rule(preset(4)),
+                        _ => return,
+                    }
+                    // Single steps print the generation they get to once they ran.
+                    if !single_step {
+                        print_state(&life, paused, steps_per_frame, brush_radius);
+                    }
+                }
+                _ => (),
+            },
+            Event::RedrawEventsCleared => {
+                if window_resized || recreate_swapchain {
+                    recreate_swapchain = false;
+                    let new_dimensions = surface.window().inner_size();
+
+                    let (new_swapchain, new_images) = match swapchain.recreate(SwapchainCreateInfo {
+                        image_extent: new_dimensions.into(),
+                        ..swapchain.create_info()
+                    }) {
+                        Ok(r) => r,
+                        // This error tends to happen when the user is manually resizing the window.
+                        // Simply restarting the loop is the easiest way to fix this issue.
+                        Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                        Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
+                    };
+                    swapchain = new_swapchain;
+                    images = new_images;
+                    window_resized = false;
+
+                    // The new swapchain can have another extent even when the window was not
+                    // resized. The view keeps its centre and zoom, only the image changes size.
+                    image = get_storage_image(device.clone(), queue.clone(), swapchain.image_extent());
+                }
+
+                let (image_i, suboptimal, acquire_future) =
+                    match swapchain::acquire_next_image(swapchain.clone(), None) {
+                        Ok(r) => r,
+                        Err(AcquireError::OutOfDate) => {
+                            recreate_swapchain = true;
+                            return;
+                        }
+                        Err(e) => panic!("Failed to acquire next image: {:?}", e),
+                    };
+                if suboptimal {
+                    recreate_swapchain = true;
+                }
+
+                let image_size = swapchain.image_extent();
+                let [width, height] = image_size;
+                let mut builder = AutoCommandBufferBuilder::primary(
+                    device.clone(),
+                    queue.family(),
+                    CommandBufferUsage::OneTimeSubmit,
+                )
+                    .unwrap();
+                // Each dispatch reads the image the one before wrote, the command buffer puts
+                // the barriers in between.
+                for cell in strokes.drain(..) {
+                    life.record_paint(&mut builder, cell, brush_radius);
+                }
+                let steps = if !paused {
+                    steps_per_frame
+                } else if single_step {
+                    1
+                } else {
+                    0
+                };
+                for _ in 0..steps {
+                    life.record_step(&mut builder);
+                }
+                life.record_draw(&mut builder, image.clone(), life.view(image_size, center.into(), zoom));
+                builder
+                    .blit_image(
+                        image.clone(),
+                        [0, 0, 0],
+                        [width as i32, height as i32, 1],
+                        0,
+                        0,
+                        images[image_i].clone(),
+                        [0, 0, 0],
+                        [width as i32, height as i32, 1],
+                        0,
+                        0,
+                        1,
+                        Filter::Nearest,
+                    )
+                    .unwrap();
+                let command_buffer = builder.build().unwrap();
+
+                let execution = sync::now(device.clone())
+                    .join(acquire_future)
+                    .then_execute(queue.clone(), command_buffer)
+                    .unwrap()
+                    .then_swapchain_present(queue.clone(), swapchain.clone(), image_i)
+                    .then_signal_fence_and_flush();
+
+                match execution {
+                    Ok(future) => {
+                        future.wait(None).unwrap(); //wait for the gpu to finish
+                    }
+                    Err(FlushError::OutOfDate) => {
+                        recreate_swapchain = true;
+                    }
+                    Err(e) => {
+                        println!("Failed to flush future: {:?}", e);
+                    }
+                }
+
+                if single_step {
+                    single_step = false;
+                    print_state(&life, paused, steps_per_frame, brush_radius);
+                }
+            }
+            _ => ()
+        }
+    });
+}
+
+/// The cell at the point `p` of the grid, if it is on the grid.
+fn cell([width, height]: [u32; 2], p: Vec2) -> Option<[u32; 2]> {
+    if p.x < 0.0 || p.y < 0.0 || p.x >= width as f32 || p.y >= height as f32 {
+        return None;
+    }
+    Some([p.x as u32, p.y as u32])
+}
+
+/// The bit masks of the rule of `life::PRESETS[i]`.
+fn preset(i: usize) -> (u32, u32) {
+    life::parse_rule(life::PRESETS[i].1).unwrap()
+}
+
+fn print_state(life: &Life, paused: bool, steps_per_frame: u32, brush_radius: u32) {
+    let (birth, survival) = life.rule();
+    println!(
+        "rule {}, generation {}, {}, {} steps per frame, brush radius {}",
+        life::rule_string(birth, survival),
+        life.generation(),
+        if paused { "paused" } else { "running" },
+        steps_per_frame,
+        brush_radius,
+    );
+}
+
+/// Image the grid is drawn to before being copied to the window.
+fn get_storage_image(device: Arc<Device>, queue: Arc<Queue>, [width, height]: [u32; 2]) -> Arc<StorageImage> {
+    StorageImage::new(
+        device,
+        ImageDimensions::Dim2d {
+            width,
+            height,
+            array_layers: 1,
+        },
+        Format::R8G8B8A8_UNORM,
+        Some(queue.family()),
+    )
+        .unwrap()
+}
\ No newline at end of file
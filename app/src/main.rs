@@ -1,6 +1,6 @@
 extern crate core;
 
-use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, DeviceExtensions};
+use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, DeviceExtensions, Features};
 use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
 use vulkano::image::ImageUsage;
 use vulkano::instance::{Instance, InstanceCreateInfo};
@@ -10,13 +10,32 @@ use winit::event_loop::EventLoop;
 use winit::window::CursorIcon::Default;
 use winit::window::WindowBuilder;
 
+mod commands;
+mod deep_zoom;
 mod fractal;
+mod fractal3d_window;
+mod fractal_window;
+mod fractal_zoom;
+mod gemm;
+mod imaging;
+mod life;
+mod life_window;
+mod nbody;
+mod raytracer;
 mod simple_compute;
 mod simple_graphics;
 mod simple_window;
+mod wavefront;
 pub mod engine;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // Running the kernels on the CPU needs no GPU, so it comes before setting up Vulkan.
+    if args.first().map(String::as_str) == Some("cpu") {
+        commands::cpu(&args[1..]);
+        return;
+    }
+
     let required_extensions = vulkano_win::required_extensions();
 
     let instance = Instance::new(InstanceCreateInfo {
@@ -49,12 +68,24 @@ fn main() {
             enabled_extensions: physical_device
                 .required_extensions()
                 .union(&device_extensions),
+            // Points larger than a pixel, for the N-body simulation.
+            enabled_features: Features {
+                large_points: physical_device.supported_features().large_points,
+                ..Features::none()
+            },
             ..DeviceCreateInfo::default()
         })
         .expect("failed to create vulkan device");
 
     let queue = queues.next().unwrap();
 
+    // command line tools --------------------------------------------------------
+    let window_demo = args.first().map(String::as_str).filter(|&command| matches!(command, "explore" | "explore3d" | "nbody" | "life"));
+    if !args.is_empty() && window_demo.is_none() {
+        commands::run(device.clone(), queue.clone(), &args);
+        return;
+    }
+
     let caps = physical_device
         .surface_capabilities(&surface, SurfaceInfo::default())
         .expect("failed to get surface capabilities");
@@ -75,7 +106,11 @@ fn main() {
             min_image_count: caps.min_image_count + 1,
             image_format,
             image_extent: dimensions.into(),
-            image_usage: ImageUsage::color_attachment(),
+            // Blitted to by the fractal explorer.
+            image_usage: ImageUsage {
+                transfer_destination: true,
+                ..ImageUsage::color_attachment()
+            },
             composite_alpha,
             ..SwapchainCreateInfo::default()
         }
@@ -91,6 +126,40 @@ fn main() {
     // render a triangle ---------------------------------------------------------
     //simple_graphics::simple_graphics(device.clone(), queue.clone());
 
-    // render a triangle into a window -------------------------------------------
-    simple_window::simple_window(event_loop, device.clone(), queue.clone(), surface, swapchain.clone(), images);
+    match window_demo {
+        Some("explore") => {
+            // explore the fractal in the window -------------------------------------
+            fractal_window::fractal_window(event_loop, device.clone(), queue.clone(), surface, swapchain.clone(), images);
+        }
+        Some("nbody") => {
+            // simulate a galaxy and draw it in the window ---------------------------
+            let count = args.get(1)
+                .map(|count| count.parse().expect("count must be a number"))
+                .unwrap_or(nbody::DEFAULT_COUNT);
+            simple_window::nbody_window(event_loop, device.clone(), queue.clone(), surface, swapchain.clone(), images, count);
+        }
+        Some("life") => {
+            // run the Game of Life or another cellular automaton in the window ------
+            let rule = args.get(1).map_or(life::DEFAULT_RULE, String::as_str);
+            let rule = life::parse_rule(rule)
+                .unwrap_or_else(|| panic!("unknown rule `{}`, expected e.g. B3/S23 or one of {:?}", rule, life::PRESETS.map(|(name, _)| name)));
+            let mut size = life::DEFAULT_SIZE;
+            for (i, length) in args.iter().skip(2).take(2).enumerate() {
+                size[i] = length.parse().expect("size must be a number");
+            }
+            if args.len() == 3 {
+                // A square grid when only the width is given.
+                size[1] = size[0];
+            }
+            life_window::life_window(event_loop, device.clone(), queue.clone(), surface, swapchain.clone(), images, size, rule);
+        }
+        Some(_) => {
+            // fly around the 3D fractals in the window ------------------------------
+            fractal3d_window::fractal3d_window(event_loop, device.clone(), queue.clone(), surface, swapchain.clone(), images);
+        }
+        None => {
+            // render a triangle into a window ---------------------------------------
+            simple_window::simple_window(event_loop, device.clone(), queue.clone(), surface, swapchain.clone(), images);
+        }
+    }
 }
@@ -0,0 +1,166 @@
+//! N-body gravitational simulation, integrated on the GPU and drawn by
+//! `simple_window::nbody_window` straight from the buffers it integrates.
+
+use std::sync::Arc;
+use bytemuck::{Pod, Zeroable};
+use shared::nbody::{NbodyConstants, WORKGROUP_SIZE};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, DeviceLocalBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::{Device, Queue};
+use vulkano::DeviceSize;
+use crate::engine::kernel::ComputeKernel;
+use crate::engine::Xorshift;
+
+const SHADER_INTEGRATE: &[u8] = include_bytes!(env!("nbody.integrate.spv"));
+
+/// Bodies simulated when no number is given.
+pub const DEFAULT_COUNT: u32 = 16384;
+
+/// Mass of the black hole at the centre of the galaxy, and of all the stars around it.
+const CENTRAL_MASS: f32 = 1.0;
+const DISK_MASS: f32 = 1.0;
+
+/// Inner and outer radius of the disk of stars, and half its thickness.
+const INNER_RADIUS: f32 = 0.1;
+const OUTER_RADIUS: f32 = 1.0;
+const HALF_THICKNESS: f32 = 0.02;
+
+/// A body as drawn, `(x, y, z, mass)`.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable, Pod)]
+pub struct Body {
+    pub position: [f32; 4],
+}
+
+vulkano::impl_vertex!(Body, position);
+
+/// The velocity of a body as drawn, `(x, y, z, 0)`.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable, Pod)]
+pub struct Velocity {
+    pub velocity: [f32; 4],
+}
+
+vulkano::impl_vertex!(Velocity, velocity);
+
+/// Bodies in device local buffers, moved one time step at a time by the `integrate` kernel.
+///
+/// The positions alternate between two buffers, which are also vertex buffers, so that they
+/// can be drawn without ever going through the CPU.
+pub struct Simulation {
+    constants: NbodyConstants,
+    bodies: [Arc<DeviceLocalBuffer<[Body]>>; 2],
+    velocities: Arc<DeviceLocalBuffer<[Velocity]>>,
+    /// The kernel moving the bodies from either buffer to the other.
+    steps: [ComputeKernel; 2],
+    /// Which of `bodies` holds the current positions.
+    current: usize,
+}
+
+impl Simulation {
+    /// A disk galaxy of `count` bodies, one of them its central black hole, on circular
+    /// orbits.
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, count: u32) -> Self {
+        assert!(count > 0, "there must be at least one body");
+        let kernel = ComputeKernel::new(device.clone(), queue.clone(), SHADER_INTEGRATE, "integrate");
+        assert_eq!(kernel.local_size(), [WORKGROUP_SIZE, 1, 1]);
+
+        let usage = BufferUsage {
+            storage_buffer: true,
+            vertex_buffer: true,
+            transfer_destination: true,
+            ..BufferUsage::none()
+        };
+        let bodies = [0, 1].map(|_| {
+            DeviceLocalBuffer::<[Body]>::array(device.clone(), count as DeviceSize, usage, [queue.family()])
+                .expect("failed to create buffer")
+        });
+        let velocities = DeviceLocalBuffer::<[Velocity]>::array(device.clone(), count as DeviceSize, usage, [queue.family()])
+            .expect("failed to create buffer");
+
+        // The only copy from the CPU.
+        let constants = NbodyConstants {
+            count,
+            dt: 0.002,
+            gravity: 1.0,
+            softening: 0.01,
+        };
+        let (initial_bodies, initial_velocities) = galaxy(count, constants.gravity);
+        let staging_bodies = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_source(),
+            false,
+            initial_bodies.into_iter(),
+        )
+            .expect("failed to create buffer");
+        let staging_velocities = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_source(),
+            false,
+            initial_velocities.into_iter(),
+        )
+            .expect("failed to create buffer");
+        let mut builder = kernel.builder();
+        builder
+            .copy_buffer(staging_bodies, bodies[0].clone())
+            .unwrap()
+            .copy_buffer(staging_velocities, velocities.clone())
+            .unwrap();
+        kernel.submit(builder).wait(None).unwrap();
+
+        let steps = [0, 1].map(|from| {
+            kernel.clone()
+                .with_buffer(0, bodies[from].clone())
+                .with_buffer(1, velocities.clone())
+                .with_buffer(2, bodies[1 - from].clone())
+        });
+        Self {
+            constants,
+            bodies,
+            velocities,
+            steps,
+            current: 0,
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.constants.count
+    }
+
+    /// Record a time step into `builder`. Commands recorded after it see the moved bodies.
+    pub fn record_step(&mut self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        self.steps[self.current].record(builder, self.constants, [self.constants.count, 1, 1]);
+        self.current = 1 - self.current;
+    }
+
+    /// The current positions.
+    pub fn bodies(&self) -> Arc<DeviceLocalBuffer<[Body]>> {
+        self.bodies[self.current].clone()
+    }
+
+    pub fn velocities(&self) -> Arc<DeviceLocalBuffer<[Velocity]>> {
+        self.velocities.clone()
+    }
+}
+
+/// A black hole at the origin with `count - 1` stars in a thin disk around it, spread evenly
+/// over its area and orbiting it in circles under the mass within their orbit.
+fn galaxy(count: u32, gravity: f32) -> (Vec<Body>, Vec<Velocity>) {
+    let mut random = Xorshift::default();
+
+    let star_mass = if count > 1 { DISK_MASS / (count - 1) as f32 } else { 0.0 };
+    let mut bodies = vec![Body { position: [0.0, 0.0, 0.0, CENTRAL_MASS] }];
+    let mut velocities = vec![Velocity::default()];
+    for _ in 1..count {
+        let (inner, outer) = (INNER_RADIUS * INNER_RADIUS, OUTER_RADIUS * OUTER_RADIUS);
+        let area = random.unit();
+        let radius = (inner + area * (outer - inner)).sqrt();
+        let angle = random.unit() * std::f32::consts::TAU;
+        let height = (2.0 * random.unit() - 1.0) * HALF_THICKNESS;
+        let speed = (gravity * (CENTRAL_MASS + area * DISK_MASS) / radius).sqrt();
+        let (sin, cos) = angle.sin_cos();
+        bodies.push(Body { position: [radius * cos, height, radius * sin, star_mass] });
+        velocities.push(Velocity { velocity: [-speed * sin, 0.0, speed * cos, 0.0] });
+    }
+    (bodies, velocities)
+}
\ No newline at end of file
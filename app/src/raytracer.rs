@@ -0,0 +1,326 @@
+use std::path::Path;
+use std::sync::Arc;
+use image::{ImageBuffer, Rgba};
+use shared::adaptive::PixelStats;
+use shared::photon::{Photon, CELL_CAPACITY};
+use shared::ShaderConstants;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::shader::ShaderModule;
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+use crate::engine;
+use crate::engine::tiles::TiledRenderer;
+
+const SHADER_RAYTRACER: &[u8] = include_bytes!(env!("raytracer.main_cs.spv"));
+const SHADER_PHOTON: &[u8] = include_bytes!(env!("raytracer.photon_cs.spv"));
+
+const WIDTH: u32 = 1024;
+const HEIGHT: u32 = 768;
+
+/// Passes dispatched between two checks of how many pixels converged.
+const PASSES_PER_CHECK: u32 = 8;
+
+const TILE_SIZE: u32 = 512;
+
+/// Cells in the hash grid of the photon map.
+const PHOTON_CELLS: u32 = 1 << 16;
+
+pub fn default_constants() -> ShaderConstants {
+    ShaderConstants {
+        view_size_pixels: [WIDTH, HEIGHT],
+        rays_per_pixel: 4,
+        ray_bounce_limit: 16,
+        vfov: 0.6,
+        aperture: 0.0,
+        noise_threshold: 0.02,
+        min_samples_per_pixel: 32,
+        max_samples_per_pixel: 8192,
+        tile_size_pixels: [WIDTH, HEIGHT],
+        photon_count: 1 << 18,
+        photon_radius: 0.05,
+        ..ShaderConstants::default()
+    }
+}
+
+/// Path trace the scene, spending samples only on the pixels that are still noisy, until every
+/// pixel converged, and save it to `raytracer.png`.
+pub fn raytracer(device: Arc<Device>, queue: Arc<Queue>) {
+    let constants = default_constants();
+    let [width, height] = constants.view_size_pixels;
+
+    let compute_pipeline = get_pipeline(device.clone());
+    let pixels = render(device, queue, compute_pipeline, constants);
+
+    let samples: f64 = pixels.iter().map(|p| p.count as f64).sum();
+    println!("{:.1} samples per pixel on average", samples / pixels.len() as f64);
+
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, to_rgba8(&pixels)).unwrap();
+    image.save("raytracer.png").unwrap();
+
+    println!("Raytracing succeded!");
+}
+
+/// Path trace the scene lit by a lamp as well, with its caustics taken from a photon map.
+pub fn raytracer_photons(device: Arc<Device>, queue: Arc<Queue>) {
+    let constants = ShaderConstants {
+        photon_mapping: 1,
+        ..default_constants()
+    };
+    raytracer_to(device, queue, constants, Path::new("raytracer_photons.png"));
+}
+
+/// Path trace the scene as described by `constants` until every pixel converged and save it to
+/// `output`.
+pub fn raytracer_to(device: Arc<Device>, queue: Arc<Queue>, constants: ShaderConstants, output: &Path) {
+    let [width, height] = constants.view_size_pixels;
+
+    let compute_pipeline = get_pipeline(device.clone());
+    let pixels = render(device, queue, compute_pipeline, constants);
+
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, to_rgba8(&pixels)).unwrap();
+    image.save(output).unwrap();
+    println!("Saved {}", output.display());
+}
+
+/// Path trace the scene at any resolution, one tile at a time.
+pub fn raytracer_tiled(device: Arc<Device>, queue: Arc<Queue>, width: u32, height: u32, output: &Path) {
+    let compute_pipeline = get_pipeline(device.clone());
+    let constants = ShaderConstants {
+        view_size_pixels: [width, height],
+        tile_size_pixels: [TILE_SIZE, TILE_SIZE],
+        ..default_constants()
+    };
+    TiledRenderer::new(width, height, TILE_SIZE)
+        .render(output, "raytracer", bytemuck::bytes_of(&constants), |tile| {
+            let constants = ShaderConstants {
+                tile_offset_pixels: [tile.x, tile.y],
+                ..constants
+            };
+            to_rgba8(&render(device.clone(), queue.clone(), compute_pipeline.clone(), constants))
+        })
+        .expect("failed to render tiles");
+}
+
+/// Render frames `first` to `last` of the animation at `fps` frames per second.
+///
+/// The first run of `#` in `output` is replaced by the zero padded frame number, which is otherwise
+/// appended to the file name. Files ending in `.exr` keep the linear colours, anything else is saved as
+/// gamma corrected 8 bit. Frames already on disk are skipped, so an interrupted batch resumes.
+pub fn raytracer_frames(device: Arc<Device>, queue: Arc<Queue>, first: u32, last: u32, fps: f32, output: &str) {
+    let compute_pipeline = get_pipeline(device.clone());
+    for frame in first..=last {
+        let path = engine::frame_path(output, frame);
+        if path.exists() {
+            println!("Frame {} already rendered", frame);
+            continue;
+        }
+
+        let constants = ShaderConstants {
+            animated: 1,
+            time: frame as f32 / fps,
+            ..default_constants()
+        };
+        let [width, height] = constants.view_size_pixels;
+        let pixels = render(device.clone(), queue.clone(), compute_pipeline.clone(), constants);
+
+        if path.extension().map_or(false, |extension| extension == "exr") {
+            let image = ImageBuffer::<Rgba<f32>, _>::from_raw(width, height, to_rgba32f(&pixels)).unwrap();
+            image.save(&path).unwrap();
+        } else {
+            let image = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, to_rgba8(&pixels)).unwrap();
+            image.save(&path).unwrap();
+        }
+        println!("Frame {} saved to {}", frame, path.display());
+    }
+}
+
+pub fn get_pipeline(device: Arc<Device>) -> Arc<ComputePipeline> {
+    compute_pipeline(device, SHADER_RAYTRACER, "main_cs")
+}
+
+fn compute_pipeline(device: Arc<Device>, bytes: &[u8], entry_point: &str) -> Arc<ComputePipeline> {
+    assert_eq!(bytes.len() % 4, 0);
+    let shader = unsafe {
+        ShaderModule::from_bytes(device.clone(), bytes)
+            .unwrap()
+    };
+
+    ComputePipeline::new(
+        device.clone(),
+        shader.entry_point(entry_point).unwrap(),
+        &(),
+        None,
+        |_| {})
+        .expect("failed to create compute pipeline")
+}
+
+/// Hash grid the photon pass fills and the camera pass gathers from.
+///
+/// Without photon mapping it is a single cell, as the camera pass still needs buffers to bind.
+pub struct PhotonMap {
+    pub cells: Arc<CpuAccessibleBuffer<[u32]>>,
+    pub photons: Arc<CpuAccessibleBuffer<[Photon]>>,
+}
+
+impl PhotonMap {
+    pub fn new(device: Arc<Device>, constants: &ShaderConstants) -> Self {
+        let cell_count = if constants.photon_mapping != 0 { PHOTON_CELLS } else { 1 };
+        let cells = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::all(),
+            false,
+            (0..cell_count).map(|_| 0u32),
+        )
+            .expect("failed to create photon cell buffer");
+        let photons = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::all(),
+            false,
+            (0..cell_count * CELL_CAPACITY).map(|_| Photon::default()),
+        )
+            .expect("failed to create photon buffer");
+        Self { cells, photons }
+    }
+
+    /// Descriptor writes binding the grid where the raytracer kernels expect it.
+    pub fn writes(&self) -> [WriteDescriptorSet; 2] {
+        [
+            WriteDescriptorSet::buffer(1, self.cells.clone()),
+            WriteDescriptorSet::buffer(2, self.photons.clone()),
+        ]
+    }
+
+    fn clear(&self) {
+        for cell in self.cells.write().unwrap().iter_mut() {
+            *cell = 0;
+        }
+    }
+}
+
+/// Dispatch passes over the tile described by `constants` until all of its pixels converged.
+fn render(
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    compute_pipeline: Arc<ComputePipeline>,
+    mut constants: ShaderConstants,
+) -> Vec<PixelStats> {
+    let [width, height] = constants.tile_size_pixels;
+
+    let pixels = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::all(),
+        false,
+        (0..width * height).map(|_| PixelStats::default()),
+    )
+        .expect("failed to create pixel buffer");
+
+    let layout = compute_pipeline.layout().set_layouts()
+        .get(0)
+        .unwrap();
+    let photon_map = PhotonMap::new(device.clone(), &constants);
+    let [cells, photons] = photon_map.writes();
+    let set = PersistentDescriptorSet::new(
+        layout.clone(),
+        [WriteDescriptorSet::buffer(0, pixels.clone()), cells, photons],
+    )
+        .unwrap();
+
+    // A new photon map is traced before every pass, so that its noise averages out too.
+    let photon_pass = (constants.photon_mapping != 0).then(|| {
+        let pipeline = compute_pipeline(device.clone(), SHADER_PHOTON, "photon_cs");
+        let layout = pipeline.layout().set_layouts()
+            .get(0)
+            .unwrap();
+        let set = PersistentDescriptorSet::new(layout.clone(), photon_map.writes()).unwrap();
+        (pipeline, set)
+    });
+
+    let mut pass = 0;
+    loop {
+        constants.rng_seed_offset = pass as f32;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            device.clone(),
+            queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        if let Some((photon_pipeline, photon_set)) = &photon_pass {
+            photon_map.clear();
+            builder
+                .bind_pipeline_compute(photon_pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    photon_pipeline.layout().clone(),
+                    0,
+                    photon_set.clone(),
+                )
+                .push_constants(photon_pipeline.layout().clone(), 0, constants)
+                .dispatch([(constants.photon_count + 63) / 64, 1, 1])
+                .unwrap();
+        }
+
+        builder
+            .bind_pipeline_compute(compute_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                compute_pipeline.layout().clone(),
+                0,
+                set.clone(),
+            )
+            .push_constants(compute_pipeline.layout().clone(), 0, constants)
+            .dispatch(engine::workgroups([width, height]))
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+
+        future.wait(None).unwrap();
+        pass += 1;
+
+        if pass % PASSES_PER_CHECK == 0 {
+            let content = pixels.read().unwrap();
+            let converged = content.iter().filter(|p| p.is_converged()).count();
+            println!("pass {}: {}/{} pixels converged", pass, converged, content.len());
+            if converged == content.len() {
+                break;
+            }
+        }
+    }
+
+    let content = pixels.read().unwrap();
+    content.to_vec()
+}
+
+/// Gamma corrected 8 bit colours of the mean of every pixel.
+pub fn to_rgba8(pixels: &[PixelStats]) -> Vec<u8> {
+    pixels
+        .iter()
+        .flat_map(|p| {
+            let [r, g, b] = p.mean;
+            [r, g, b, 1.0].map(|c| (c.max(0.0).sqrt().min(1.0) * 255.0) as u8)
+        })
+        .collect()
+}
+
+
+/// Linear colours of the mean of every pixel.
+fn to_rgba32f(pixels: &[PixelStats]) -> Vec<f32> {
+    pixels
+        .iter()
+        .flat_map(|p| {
+            let [r, g, b] = p.mean;
+            [r, g, b, 1.0]
+        })
+        .collect()
+}
\ No newline at end of file
@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::sync::Arc;
 use image::{ImageBuffer, Rgba};
 use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
@@ -16,11 +17,14 @@ use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
 use vulkano::pipeline::GraphicsPipeline;
 use vulkano::sync;
 use vulkano::sync::GpuFuture;
+use crate::engine::tiles::TiledRenderer;
 use crate::engine::vec::Vertex;
 
 const SHADER_SIMPLE_GRAPHICS_VS: &[u8] = include_bytes!(env!("simple_graphics.main_vs.spv"));
 const SHADER_SIMPLE_GRAPHICS_FS: &[u8] = include_bytes!(env!("simple_graphics.main_fs.spv"));
 
+const TILE_SIZE: u32 = 1024;
+
 mod glsl_vs {
     vulkano_shaders::shader!{
         ty: "vertex",
@@ -180,4 +184,135 @@ pub fn simple_graphics(device: Arc<Device>, queue: Arc<Queue>) {
     image.save("image.png").unwrap();
 
     println!("Rendering image succeded!");
-}
\ No newline at end of file
+}
+
+/// Render the triangle at any resolution, one tile at a time.
+///
+/// Each tile moves the viewport so that only its part of the whole image lands in the
+/// framebuffer, which limits the size to what the device accepts for a viewport.
+pub fn simple_graphics_tiled(device: Arc<Device>, queue: Arc<Queue>, width: u32, height: u32, output: &Path) {
+    assert_eq!(SHADER_SIMPLE_GRAPHICS_VS.len() % 4, 0);
+    assert_eq!(SHADER_SIMPLE_GRAPHICS_FS.len() % 4, 0);
+    let r_shader_vs = unsafe {
+        ShaderModule::from_bytes(device.clone(), SHADER_SIMPLE_GRAPHICS_VS)
+            .unwrap()
+    };
+    let r_shader_fs = unsafe {
+        ShaderModule::from_bytes(device.clone(), SHADER_SIMPLE_GRAPHICS_FS)
+            .unwrap()
+    };
+
+    let vertex1 = Vertex { position: [-0.5, -0.5] };
+    let vertex2 = Vertex { position: [ 0.0,  0.5] };
+    let vertex3 = Vertex { position: [ 0.5, -0.25] };
+
+    let vertex_buffer = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::vertex_buffer(),
+        false,
+        vec![vertex1, vertex2, vertex3].into_iter(),
+    )
+        .unwrap();
+
+    let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: Format::R8G8B8A8_UNORM,
+                samples: 1,
+            }
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {}
+        }
+    )
+        .unwrap();
+
+    let image = StorageImage::new(
+        device.clone(),
+        ImageDimensions::Dim2d {
+            width: TILE_SIZE,
+            height: TILE_SIZE,
+            array_layers: 1,
+        },
+        Format::R8G8B8A8_UNORM,
+        Some(queue.family()),
+    )
+        .unwrap();
+
+    let view = ImageView::new_default(image.clone()).unwrap();
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![view],
+            ..Default::default()
+        },
+    )
+        .unwrap();
+
+    let buf = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::all(),
+        false,
+        (0..TILE_SIZE * TILE_SIZE * 4).map(|_| 0u8),
+    )
+        .expect("failed to create buffer");
+
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state(BuffersDefinition::new().vertex::<Vertex>())
+        .vertex_shader(r_shader_vs.entry_point("main_vs").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+        .fragment_shader(r_shader_fs.entry_point("main_fs").unwrap(), ())
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        .build(device.clone())
+        .unwrap();
+
+    // The triangle is always the same, only its size changes.
+    TiledRenderer::new(width, height, TILE_SIZE)
+        .render(output, "triangle", &[], |tile| {
+            let viewport = Viewport {
+                origin: [-(tile.x as f32), -(tile.y as f32)],
+                dimensions: [width as f32, height as f32],
+                depth_range: 0.0..1.0,
+            };
+
+            let mut builder = AutoCommandBufferBuilder::primary(
+                device.clone(),
+                queue.family(),
+                CommandBufferUsage::OneTimeSubmit,
+            )
+                .unwrap();
+            builder
+                .begin_render_pass(
+                    framebuffer.clone(),
+                    SubpassContents::Inline,
+                    vec![[0.0, 0.0, 1.0, 1.0].into()],
+                )
+                .unwrap()
+                .set_viewport(0, [viewport])
+                .bind_pipeline_graphics(pipeline.clone())
+                .bind_vertex_buffers(0, vertex_buffer.clone())
+                .draw(3, 1, 0, 0)
+                .unwrap()
+                .end_render_pass()
+                .unwrap()
+                .copy_image_to_buffer(image.clone(), buf.clone())
+                .unwrap();
+
+            let command_buffer = builder.build().unwrap();
+
+            let future = sync::now(device.clone())
+                .then_execute(queue.clone(), command_buffer)
+                .unwrap()
+                .then_signal_fence_and_flush()
+                .unwrap();
+            future.wait(None).unwrap();
+
+            let buffer_content = buf.read().unwrap();
+            buffer_content.to_vec()
+        })
+        .expect("failed to render tiles");
+}
@@ -11,21 +11,36 @@ use std::convert::TryFrom;
 use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, SubpassContents};
 use vulkano::image::{ImageAccess, ImageDimensions, StorageImage, SwapchainImage};
 use vulkano::instance::Instance;
-use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
 use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
 use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
-use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
 use vulkano::swapchain::{AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError};
 use vulkano::{swapchain, sync};
 use vulkano::sync::{FlushError, GpuFuture};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::{Window, WindowBuilder};
 use vulkano_win::VkSurfaceBuild;
-use winit::event::{Event, WindowEvent};
+use winit::event::{ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use shared::nbody::NbodyView;
 use crate::engine::vec::Vertex;
+use crate::nbody::{Body, Simulation, Velocity};
 
 const SHADER_SIMPLE_GRAPHICS_VS: &[u8] = include_bytes!(env!("simple_graphics.main_vs.spv"));
 const SHADER_SIMPLE_GRAPHICS_FS: &[u8] = include_bytes!(env!("simple_graphics.main_fs.spv"));
+const SHADER_BODY_VS: &[u8] = include_bytes!(env!("nbody.body_vs.spv"));
+const SHADER_BODY_FS: &[u8] = include_bytes!(env!("nbody.body_fs.spv"));
+
+/// Diameter of the bodies in pixels, when the device supports points larger than one pixel.
+const POINT_SIZE: f32 = 4.0;
+
+/// Radians the camera turns per pixel dragged, and by itself per frame.
+const ORBIT_SPEED: f32 = 0.01;
+const TURN_SPEED: f32 = 0.001;
+
+/// Factor the camera distance changes by per step of the mouse wheel.
+const DOLLY_STEP: f32 = 0.9;
 
 pub fn simple_window(event_loop: EventLoop<()>,
                      device: Arc<Device>,
@@ -187,6 +202,192 @@ pub fn simple_window(event_loop: EventLoop<()>,
     });
 }
 
+/// Simulate the gravity of `count` bodies and draw them in the window, one time step a frame.
+///
+/// Every frame dispatches the integration kernel and draws the bodies it moved as additive
+/// points, in the same command buffer and from the same buffers, which never leave the GPU.
+/// The camera slowly circles the galaxy: dragging with the left button turns it and the mouse
+/// wheel moves it closer or further. Space pauses the simulation.
+pub fn nbody_window(event_loop: EventLoop<()>,
+                    device: Arc<Device>,
+                    queue: Arc<Queue>,
+                    surface: Arc<Surface<Window>>,
+                    mut swapchain: Arc<Swapchain<Window>>,
+                    images: Vec<Arc<SwapchainImage<Window>>>,
+                    count: u32)
+{
+    let vs = unsafe {
+        ShaderModule::from_bytes(device.clone(), SHADER_BODY_VS)
+            .unwrap()
+    };
+    let fs = unsafe {
+        ShaderModule::from_bytes(device.clone(), SHADER_BODY_FS)
+            .unwrap()
+    };
+
+    let mut simulation = Simulation::new(device.clone(), queue.clone(), count);
+    println!("Simulating {} bodies", simulation.count());
+
+    let render_pass = get_render_pass(device.clone(), swapchain.clone())
+        .unwrap();
+    let mut framebuffers = get_framebuffers(&images, render_pass.clone());
+    let mut viewport = Viewport {
+        origin: [0.0, 0.0],
+        dimensions: surface.window().inner_size().into(),
+        depth_range: 0.0..1.0,
+    };
+    let mut pipeline = get_points_pipeline(device.clone(), vs.clone(), fs.clone(), render_pass.clone(), viewport.clone());
+
+    let mut view = NbodyView {
+        aspect: viewport.dimensions[0] / viewport.dimensions[1],
+        yaw: 0.0,
+        pitch: 0.5,
+        distance: 3.0,
+        point_size: if device.enabled_features().large_points { POINT_SIZE } else { 1.0 },
+        max_speed: 2.0,
+    };
+    let mut paused = false;
+    let mut dragging = false;
+    let mut cursor = [0.0, 0.0];
+    let mut window_resized = false;
+    let mut recreate_swapchain = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                WindowEvent::Resized(_) => {
+                    window_resized = true;
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    let position = [position.x as f32, position.y as f32];
+                    if dragging {
+                        view.yaw -= (position[0] - cursor[0]) * ORBIT_SPEED;
+                        view.pitch = (view.pitch + (position[1] - cursor[1]) * ORBIT_SPEED).clamp(-1.5, 1.5);
+                    }
+                    cursor = position;
+                }
+                WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                    dragging = state == ElementState::Pressed;
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let steps = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 100.0,
+                    };
+                    view.distance *= DOLLY_STEP.powf(steps);
+                }
+                WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::Space),
+                        ..
+                    },
+                    ..
+                } => {
+                    paused = !paused;
+                }
+                _ => (),
+            },
+            Event::RedrawEventsCleared => {
+                if window_resized || recreate_swapchain {
+                    recreate_swapchain = false;
+                    let new_dimensions = surface.window().inner_size();
+
+                    let (new_swapchain, new_images) = match swapchain.recreate(SwapchainCreateInfo {
+                        image_extent: new_dimensions.into(),
+                        ..swapchain.create_info()
+                    }) {
+                        Ok(r) => r,
+                        // This error tends to happen when the user is manually resizing the window.
+                        // Simply restarting the loop is the easiest way to fix this issue.
+                        Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                        Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
+                    };
+                    swapchain = new_swapchain;
+                    framebuffers = get_framebuffers(&new_images, render_pass.clone());
+
+                    if window_resized {
+                        window_resized = false;
+
+                        viewport.dimensions = new_dimensions.into();
+                        view.aspect = viewport.dimensions[0] / viewport.dimensions[1];
+                        pipeline = get_points_pipeline(
+                            device.clone(),
+                            vs.clone(),
+                            fs.clone(),
+                            render_pass.clone(),
+                            viewport.clone(),
+                        );
+                    }
+                }
+
+                let (image_i, suboptimal, acquire_future) =
+                    match swapchain::acquire_next_image(swapchain.clone(), None) {
+                        Ok(r) => r,
+                        Err(AcquireError::OutOfDate) => {
+                            recreate_swapchain = true;
+                            return;
+                        }
+                        Err(e) => panic!("Failed to acquire next image: {:?}", e),
+                    };
+                if suboptimal {
+                    recreate_swapchain = true;
+                }
+
+                let mut builder = AutoCommandBufferBuilder::primary(
+                    device.clone(),
+                    queue.family(),
+                    CommandBufferUsage::OneTimeSubmit,
+                )
+                    .unwrap();
+                // The command buffer makes the draw wait for the dispatch writing the positions.
+                if !paused {
+                    simulation.record_step(&mut builder);
+                    view.yaw += TURN_SPEED;
+                }
+                builder
+                    .begin_render_pass(
+                        framebuffers[image_i].clone(),
+                        SubpassContents::Inline,
+                        vec![[0.0, 0.0, 0.0, 1.0].into()],
+                    )
+                    .unwrap()
+                    .bind_pipeline_graphics(pipeline.clone())
+                    .push_constants(pipeline.layout().clone(), 0, view)
+                    .bind_vertex_buffers(0, (simulation.bodies(), simulation.velocities()))
+                    .draw(simulation.count(), 1, 0, 0)
+                    .unwrap()
+                    .end_render_pass()
+                    .unwrap();
+                let command_buffer = builder.build().unwrap();
+
+                let execution = sync::now(device.clone())
+                    .join(acquire_future)
+                    .then_execute(queue.clone(), command_buffer)
+                    .unwrap()
+                    .then_swapchain_present(queue.clone(), swapchain.clone(), image_i)
+                    .then_signal_fence_and_flush();
+
+                match execution {
+                    Ok(future) => {
+                        future.wait(None).unwrap(); //wait for the gpu to finish
+                    }
+                    Err(FlushError::OutOfDate) => {
+                        recreate_swapchain = true;
+                    }
+                    Err(e) => {
+                        println!("Failed to flush future: {:?}", e);
+                    }
+                }
+            }
+            _ => ()
+        }
+    });
+}
+
 fn get_render_pass(device: Arc<Device>, swapchain: Arc<Swapchain<Window>>) -> Result<Arc<RenderPass>, RenderPassCreationError> {
     vulkano::single_pass_renderpass!(
         device.clone(),
@@ -242,6 +443,27 @@ fn get_pipeline(
         .unwrap()
 }
 
+/// Pipeline drawing bodies as points, added onto what is already drawn so that dense regions
+/// glow.
+fn get_points_pipeline(
+    device: Arc<Device>,
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    render_pass: Arc<RenderPass>,
+    viewport: Viewport,
+) -> Arc<GraphicsPipeline> {
+    GraphicsPipeline::start()
+        .vertex_input_state(BuffersDefinition::new().vertex::<Body>().vertex::<Velocity>())
+        .vertex_shader(vs.entry_point("body_vs").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new().topology(PrimitiveTopology::PointList))
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
+        .fragment_shader(fs.entry_point("body_fs").unwrap(), ())
+        .color_blend_state(ColorBlendState::new(1).blend_additive())
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        .build(device.clone())
+        .unwrap()
+}
+
 fn get_command_buffers(
     device: Arc<Device>,
     queue: Arc<Queue>,
@@ -0,0 +1,312 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use image::{ImageBuffer, Rgba};
+use shared::adaptive::PixelStats;
+use shared::wavefront::{PathState, ShadowRay, COUNTERS_LEN};
+use shared::ShaderConstants;
+use vulkano::buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::shader::ShaderModule;
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+use crate::engine;
+use crate::raytracer;
+
+const SHADER_GENERATE: &[u8] = include_bytes!(env!("raytracer.wavefront_generate.spv"));
+const SHADER_INTERSECT: &[u8] = include_bytes!(env!("raytracer.wavefront_intersect.spv"));
+const SHADER_SHADE: &[u8] = include_bytes!(env!("raytracer.wavefront_shade.spv"));
+const SHADER_SHADOW: &[u8] = include_bytes!(env!("raytracer.wavefront_shadow.spv"));
+const SHADER_ADVANCE: &[u8] = include_bytes!(env!("raytracer.wavefront_advance.spv"));
+
+/// Samples per pixel taken by the benchmark with both renderers.
+const BENCHMARK_SAMPLES: u32 = 64;
+/// Timed runs of each renderer, after one untimed warm up run.
+const BENCHMARK_RUNS: u32 = 5;
+
+/// The kernels of the wavefront path tracer along with the buffers they share.
+pub struct Wavefront {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    constants: ShaderConstants,
+    generate: Kernel,
+    intersect: Kernel,
+    shade: Kernel,
+    shadow: Kernel,
+    advance: Kernel,
+    accumulation: Arc<CpuAccessibleBuffer<[[f32; 4]]>>,
+}
+
+/// A pipeline and a descriptor set holding the buffers its entry point uses.
+struct Kernel {
+    pipeline: Arc<ComputePipeline>,
+    set: Arc<PersistentDescriptorSet>,
+}
+
+impl Wavefront {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, constants: ShaderConstants) -> Self {
+        let [width, height] = constants.view_size_pixels;
+        let len = (width * height) as usize;
+
+        let paths = buffer(device.clone(), (0..len).map(|_| PathState::default()));
+        let shadow_rays = buffer(device.clone(), (0..len).map(|_| ShadowRay::default()));
+        let counters = buffer(device.clone(), (0..COUNTERS_LEN).map(|_| 0u32));
+        let ray_queue = buffer(device.clone(), (0..len).map(|_| 0u32));
+        let material_queue = buffer(device.clone(), (0..len).map(|_| 0u32));
+        let shadow_queue = buffer(device.clone(), (0..len).map(|_| 0u32));
+        let accumulation = buffer(device.clone(), (0..len).map(|_| [0.0f32; 4]));
+
+        let buffers: [Arc<dyn BufferAccess>; 7] = [
+            paths,
+            shadow_rays,
+            counters,
+            ray_queue,
+            material_queue,
+            shadow_queue,
+            accumulation.clone(),
+        ];
+        let kernel = |bytes: &[u8], entry_point: &str| Kernel::new(device.clone(), bytes, entry_point, &buffers);
+
+        Self {
+            generate: kernel(SHADER_GENERATE, "wavefront_generate"),
+            intersect: kernel(SHADER_INTERSECT, "wavefront_intersect"),
+            shade: kernel(SHADER_SHADE, "wavefront_shade"),
+            shadow: kernel(SHADER_SHADOW, "wavefront_shadow"),
+            advance: kernel(SHADER_ADVANCE, "wavefront_advance"),
+            device,
+            queue,
+            constants,
+            accumulation,
+        }
+    }
+
+    /// Trace `samples` paths per pixel and return the mean colour of every pixel.
+    pub fn render(&self, samples: u32) -> Vec<[f32; 3]> {
+        let [width, height] = self.constants.view_size_pixels;
+        let path_groups = (width * height + 63) / 64;
+
+        for pixel in self.accumulation.write().unwrap().iter_mut() {
+            *pixel = [0.0; 4];
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            self.device.clone(),
+            self.queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        for sample in 0..samples {
+            let constants = ShaderConstants {
+                rng_seed_offset: sample as f32,
+                ..self.constants
+            };
+            self.generate.dispatch(&mut builder, constants, engine::workgroups([width, height]));
+            self.advance.dispatch(&mut builder, constants, [1, 1, 1]);
+            // One more pass than bounces, so that the last bounce is intersected too.
+            for _ in 0..=constants.ray_bounce_limit {
+                self.intersect.dispatch(&mut builder, constants, [path_groups, 1, 1]);
+                self.shade.dispatch(&mut builder, constants, [path_groups, 1, 1]);
+                self.shadow.dispatch(&mut builder, constants, [path_groups, 1, 1]);
+                self.advance.dispatch(&mut builder, constants, [1, 1, 1]);
+            }
+        }
+
+        let command_buffer = builder.build().unwrap();
+
+        let future = sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+
+        future.wait(None).unwrap();
+
+        let content = self.accumulation.read().unwrap();
+        content
+            .iter()
+            .map(|&[r, g, b, _]| [r, g, b].map(|c| c / samples as f32))
+            .collect()
+    }
+}
+
+impl Kernel {
+    fn new(device: Arc<Device>, bytes: &[u8], entry_point: &str, buffers: &[Arc<dyn BufferAccess>]) -> Self {
+        assert_eq!(bytes.len() % 4, 0);
+        let shader = unsafe {
+            ShaderModule::from_bytes(device.clone(), bytes)
+                .unwrap()
+        };
+
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point(entry_point).unwrap(),
+            &(),
+            None,
+            |_| {})
+            .expect("failed to create compute pipeline");
+
+        // Every kernel only declares the bindings it uses.
+        let layout = pipeline.layout().set_layouts()
+            .get(0)
+            .unwrap();
+        let writes = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| (binding as u32, buffer))
+            .filter(|(binding, _)| layout.bindings().contains_key(binding))
+            .map(|(binding, buffer)| WriteDescriptorSet::buffer(binding, buffer.clone()));
+        let set = PersistentDescriptorSet::new(layout.clone(), writes).unwrap();
+
+        Self { pipeline, set }
+    }
+
+    fn dispatch(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        constants: ShaderConstants,
+        group_counts: [u32; 3],
+    ) {
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                self.set.clone(),
+            );
+        if !self.pipeline.layout().push_constant_ranges().is_empty() {
+            builder.push_constants(self.pipeline.layout().clone(), 0, constants);
+        }
+        builder
+            .dispatch(group_counts)
+            .unwrap();
+    }
+}
+
+/// Path trace the scene with the wavefront kernels.
+pub fn wavefront(device: Arc<Device>, queue: Arc<Queue>) {
+    let constants = raytracer::default_constants();
+    let [width, height] = constants.view_size_pixels;
+
+    let pixels = Wavefront::new(device, queue, constants).render(BENCHMARK_SAMPLES);
+
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, to_rgba8(&pixels)).unwrap();
+    image.save("wavefront.png").unwrap();
+
+    println!("Wavefront raytracing succeded!");
+}
+
+/// Time the megakernel and the wavefront kernels tracing the same number of paths through the
+/// same scene.
+pub fn benchmark(device: Arc<Device>, queue: Arc<Queue>) {
+    let constants = ShaderConstants {
+        rays_per_pixel: BENCHMARK_SAMPLES,
+        noise_threshold: 0.0,
+        max_samples_per_pixel: 0,
+        ..raytracer::default_constants()
+    };
+    let [width, height] = constants.view_size_pixels;
+    println!(
+        "Tracing {}x{} pixels with {} samples per pixel and up to {} bounces",
+        width, height, BENCHMARK_SAMPLES, constants.ray_bounce_limit,
+    );
+
+    let megakernel = raytracer::get_pipeline(device.clone());
+    let pixels = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::all(),
+        false,
+        (0..width * height).map(|_| PixelStats::default()),
+    )
+        .expect("failed to create pixel buffer");
+    let layout = megakernel.layout().set_layouts()
+        .get(0)
+        .unwrap();
+    let photon_map = raytracer::PhotonMap::new(device.clone(), &constants);
+    let [cells, photons] = photon_map.writes();
+    let set = PersistentDescriptorSet::new(
+        layout.clone(),
+        [WriteDescriptorSet::buffer(0, pixels.clone()), cells, photons],
+    )
+        .unwrap();
+
+    let megakernel_time = time(|| {
+        for pixel in pixels.write().unwrap().iter_mut() {
+            *pixel = PixelStats::default();
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            device.clone(),
+            queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        builder
+            .bind_pipeline_compute(megakernel.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                megakernel.layout().clone(),
+                0,
+                set.clone(),
+            )
+            .push_constants(megakernel.layout().clone(), 0, constants)
+            .dispatch(engine::workgroups([width, height]))
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+
+        future.wait(None).unwrap();
+    });
+
+    let wavefront = Wavefront::new(device.clone(), queue.clone(), constants);
+    let wavefront_time = time(|| {
+        wavefront.render(BENCHMARK_SAMPLES);
+    });
+
+    let paths = (width * height * BENCHMARK_SAMPLES) as f64;
+    for (name, duration) in [("megakernel", megakernel_time), ("wavefront", wavefront_time)] {
+        println!(
+            "{:>10}: {:8.1} ms, {:6.1} M paths/s",
+            name,
+            duration.as_secs_f64() * 1000.0,
+            paths / duration.as_secs_f64() / 1e6,
+        );
+    }
+    println!("wavefront / megakernel: {:.2}", wavefront_time.as_secs_f64() / megakernel_time.as_secs_f64());
+}
+
+/// Average duration of `BENCHMARK_RUNS` runs of `run`, not counting a first warm up run.
+fn time(mut run: impl FnMut()) -> Duration {
+    run();
+    let start = Instant::now();
+    for _ in 0..BENCHMARK_RUNS {
+        run();
+    }
+    start.elapsed() / BENCHMARK_RUNS
+}
+
+fn buffer<T: bytemuck::Pod + Send + Sync>(
+    device: Arc<Device>,
+    data: impl ExactSizeIterator<Item = T>,
+) -> Arc<CpuAccessibleBuffer<[T]>> {
+    CpuAccessibleBuffer::from_iter(device, BufferUsage::all(), false, data)
+        .expect("failed to create buffer")
+}
+
+/// Gamma corrected 8 bit colours.
+fn to_rgba8(pixels: &[[f32; 3]]) -> Vec<u8> {
+    pixels
+        .iter()
+        .flat_map(|&[r, g, b]| [r, g, b, 1.0].map(|c| (c.max(0.0).sqrt().min(1.0) * 255.0) as u8))
+        .collect()
+}
@@ -0,0 +1,69 @@
+#![cfg_attr(
+    target_arch = "spirv",
+    no_std,
+    feature(register_attr),
+    register_attr(spirv)
+)]
+// HACK(eddyb) can't easily see warnings otherwise from `spirv-builder` builds.
+#![deny(warnings)]
+
+#[cfg(not(target_arch = "spirv"))]
+use spirv_std::macros::spirv;
+use shared::fft::FftConstants;
+use shared::fractal::complex_mul;
+use shared::glam::{vec2, UVec3, Vec2};
+
+/// One pass of the Stockham FFT over complex numbers stored as `(re, im)`.
+///
+/// The input holds `n / span` transforms of length `span`, each spread `n / span` elements
+/// apart. Every invocation takes the elements at the same position in `radix` of them, rotated
+/// by their twiddle factors, and combines them into `radix` elements of a transform `radix`
+/// times longer, which the output holds the same way.
+///
+/// The twiddle factors come from `twiddles`, holding `exp(2 pi i m / n)` for every `m` below
+/// `n`, as the `sin` and `cos` of the GPU are only accurate to about three decimals.
+#[spirv(compute(threads(64)))]
+pub fn fft(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &FftConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] input: &[Vec2],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] output: &mut [Vec2],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] twiddles: &[Vec2],
+) {
+    let (j, batch) = (id.x, id.y);
+    let radix = constants.radix;
+    let stride = constants.n / radix;
+    if j >= stride || batch >= constants.batches {
+        return;
+    }
+
+    let span = constants.span;
+    let k = j % span;
+    // The twiddle of `r` is `exp(sign 2 pi i k r / (span radix))`, the power `k r` of the root
+    // of unity a step of `n / (span radix)` along the table.
+    let step = constants.n / (span * radix);
+    let mut v = [Vec2::ZERO; 4];
+    let mut r = 0;
+    while r < radix {
+        let twiddle = twiddles[(k * r * step) as usize];
+        let x = input[constants.index(batch, j + r * stride)];
+        v[r as usize] = complex_mul(x, vec2(twiddle.x, constants.sign() * twiddle.y));
+        r += 1;
+    }
+
+    if radix == 4 {
+        let (t0, t1, t2, t3) = (v[0] + v[2], v[0] - v[2], v[1] + v[3], v[1] - v[3]);
+        // `t3` times `-i` for the forward transform and `i` for the inverse one.
+        let t3 = vec2(-t3.y, t3.x) * constants.sign();
+        v = [t0 + t2, t1 + t3, t0 - t2, t1 - t3];
+    } else {
+        v = [v[0] + v[1], v[0] - v[1], Vec2::ZERO, Vec2::ZERO];
+    }
+
+    let first = (j / span) * span * radix + k;
+    r = 0;
+    while r < radix {
+        output[constants.index(batch, first + r * span)] = v[r as usize] * constants.scale;
+        r += 1;
+    }
+}
\ No newline at end of file
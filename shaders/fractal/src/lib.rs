@@ -9,41 +9,203 @@
 
 #[cfg(not(target_arch = "spirv"))]
 use spirv_std::macros::spirv;
+use shared::fractal::{
+    complex_mul, gradient, ldexp, FractalConstants, GradientStop, PerturbationConstants, DISTANCE,
+    JULIA, NEWTON, ORBIT_TRAP,
+};
+use shared::Rng;
+#[cfg(target_arch = "spirv")]
 use spirv_std::Image;
-use spirv_std::glam::{uvec2, UVec2, UVec3};
-use spirv_std::glam::{vec2, vec4, Vec2, Vec4};
+use spirv_std::glam::{UVec2, UVec3};
+use spirv_std::glam::{vec2, Vec2, Vec4};
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
 use spirv_std::glam::Vec3Swizzles;
 
+#[cfg(target_arch = "spirv")]
 type Image2d = Image!(2D, format=rgba8, sampled=false);
+/// Stand-in for running the kernels on the CPU.
+#[cfg(not(target_arch = "spirv"))]
+type Image2d = shared::host::Image2d;
 
+/// Any of the fractals selected by `FractalConstants::formula`.
 #[spirv(compute(threads(8,8)))]
 pub fn fractal(
     #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &FractalConstants,
     #[spirv(descriptor_set = 0, binding = 0)] image: &mut Image2d,
-    // #[spirv(flat, descriptor_set = 0, binding = 1)] dimensions: [u32;2],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] palette: &[GradientStop],
 ) {
-    // let dimensions = uvec2(dimensions[0], dimensions[1]);
-    let dimensions: UVec2 = uvec2(1024, 1024);//image.query_size();
-    let norm_coordinates: Vec2 = (id.xy().as_vec2() + vec2(0.5, 0.5)) / dimensions.as_vec2();
-    let c: Vec2 = (norm_coordinates - vec2(0.5, 0.5)) * 2.0 - vec2(1.0, 0.0);
-
-    let mut z: Vec2 = vec2(0.0, 0.0);
-    let mut i: f32 = 0.0f32;
-    while i<1.0 {
-        z = vec2(
-            z.x*z.x - z.y*z.y + c.x,
-            z.y*z.x + z.x*z.y + c.y,
-        );
-
-        if z.length() > 4.0 {
+    let pixel = id.xy() + UVec2::from(constants.tile_offset);
+    if pixel.x >= constants.image_size[0] || pixel.y >= constants.image_size[1] {
+        return;
+    }
+
+    // The average of a grid of samples, each jittered within its cell of the pixel.
+    let samples_per_side = constants.samples_per_side.max(1);
+    let mut rng = Rng::for_pixel(pixel, 0);
+    let mut to_write = Vec4::ZERO;
+    let mut y = 0;
+    while y < samples_per_side {
+        let mut x = 0;
+        while x < samples_per_side {
+            let jitter = if samples_per_side == 1 {
+                vec2(0.5, 0.5)
+            } else {
+                (vec2(x as f32, y as f32) + vec2(rng.gen(), rng.gen())) / samples_per_side as f32
+            };
+            let p = constants.pixel_to_complex(pixel.as_vec2() + jitter);
+            to_write += if constants.formula == NEWTON {
+                newton(constants, palette, p)
+            } else {
+                escape_time(constants, palette, p)
+            };
+            x += 1;
+        }
+        y += 1;
+    }
+    to_write /= (samples_per_side * samples_per_side) as f32;
+    unsafe {
+        image.write(id.xy(), to_write);
+    }
+}
+
+fn escape_time(constants: &FractalConstants, palette: &[GradientStop], p: Vec2) -> Vec4 {
+    let escape_radius_squared = constants.escape_radius * constants.escape_radius;
+    let (mut z, c) = constants.start(p);
+    // Derivative of `z` with respect to the pixel, for the distance estimate.
+    let mut dz = if constants.formula == JULIA { vec2(1.0, 0.0) } else { Vec2::ZERO };
+    let mut trap = f32::MAX;
+    let mut iteration = 0;
+    while iteration < constants.max_iterations {
+        dz = constants.step_derivative(z, dz);
+        z = constants.step(z, c);
+        trap = trap.min(z.abs().min_element());
+
+        if z.length_squared() > escape_radius_squared {
             break;
         }
 
-        i+= 0.005;
+        iteration += 1;
     }
 
-    let to_write: Vec4 = vec4(i, i, i, 1.0);
+    if constants.shading == ORBIT_TRAP {
+        return color(palette, trap.sqrt() * constants.palette_cycles);
+    }
+    if iteration == constants.max_iterations {
+        return Vec4::new(0.0, 0.0, 0.0, 1.0);
+    }
+    if constants.shading == DISTANCE {
+        // Distances over a pixel fade to the end of the palette.
+        let distance = z.length() * z.length().ln() / dz.length();
+        let t = (distance / constants.pixel_size()).min(1.0).sqrt();
+        return gradient(palette, t).extend(1.0);
+    }
+    let smooth = constants.smooth_iteration(iteration, z) / constants.max_iterations as f32;
+    color(palette, smooth * constants.palette_cycles)
+}
+
+/// Basin of the root `p` converges to, one colour of the palette per root, darker the longer
+/// it takes.
+fn newton(constants: &FractalConstants, palette: &[GradientStop], p: Vec2) -> Vec4 {
+    let mut z = p;
+    let mut root = constants.nearest_root(z);
+    let mut iteration = 0;
+    while root == constants.root_count && iteration < constants.max_iterations {
+        z = constants.newton_step(z);
+        root = constants.nearest_root(z);
+        iteration += 1;
+    }
+
+    if root == constants.root_count {
+        return Vec4::new(0.0, 0.0, 0.0, 1.0);
+    }
+    let color = gradient(palette, (root as f32 + 0.5) / constants.root_count as f32);
+    let brightness = 1.0 / (1.0 + 0.1 * iteration as f32);
+    (color * brightness).extend(1.0)
+}
+
+/// Mandelbrot set iterated as offsets from a high precision reference orbit, for zooms far
+/// beyond the precision of `f32`.
+///
+/// Pixels whose offset loses its precision are marked in `glitches`, to be rendered again from
+/// a reference orbit closer to them.
+#[spirv(compute(threads(8,8)))]
+pub fn fractal_deep(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &PerturbationConstants,
+    #[spirv(descriptor_set = 0, binding = 0)] image: &mut Image2d,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] reference: &[Vec2],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] glitches: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] palette: &[GradientStop],
+) {
+    let size = UVec2::from(constants.fractal.image_size);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+    let index = (id.y * size.x + id.x) as usize;
+    if constants.glitched_only != 0 && glitches[index] == 0 {
+        return;
+    }
+
+    // The offset from the reference orbit is `delta * 2^exponent`, so that it neither
+    // underflows while tiny nor overflows once it grew.
+    let offset = (id.xy().as_vec2() + vec2(0.5, 0.5) - Vec2::from(constants.reference_pixel)) * vec2(1.0, -1.0);
+    let start = offset * constants.pixel_size_mantissa;
+    let start_exponent = constants.pixel_size_exponent;
+    let mut delta = Vec2::ZERO;
+    let mut exponent = start_exponent;
+
+    let escape_radius_squared = constants.fractal.escape_radius * constants.fractal.escape_radius;
+    let mut glitched = false;
+    let mut z = Vec2::ZERO;
+    let mut iteration = 0;
+    while iteration < constants.fractal.max_iterations {
+        let n = iteration as usize;
+        if n + 1 >= constants.reference_len as usize {
+            // The reference escaped before this pixel did.
+            glitched = true;
+            break;
+        }
+
+        // delta' = 2 Z delta + delta^2 + delta_0
+        let square = complex_mul(delta, delta);
+        delta = 2.0 * complex_mul(reference[n], delta)
+            + vec2(ldexp(square.x, exponent), ldexp(square.y, exponent))
+            + vec2(ldexp(start.x, start_exponent - exponent), ldexp(start.y, start_exponent - exponent));
+        if delta.abs().max_element() > 4294967296.0 {
+            delta /= 4294967296.0;
+            exponent += 32;
+        }
+
+        let z_reference = reference[n + 1];
+        z = z_reference + vec2(ldexp(delta.x, exponent), ldexp(delta.y, exponent));
+        if z.length_squared() > escape_radius_squared {
+            break;
+        }
+        // The pixel came so close to zero that the offset no longer carries enough precision.
+        if z.length_squared() < 1e-6 * z_reference.length_squared() {
+            glitched = true;
+            break;
+        }
+
+        iteration += 1;
+    }
+
+    glitches[index] = if glitched { 1 } else { 0 };
+    let to_write = if iteration == constants.fractal.max_iterations {
+        Vec4::new(0.0, 0.0, 0.0, 1.0)
+    } else {
+        let fractal = &constants.fractal;
+        let smooth = fractal.smooth_iteration(iteration, z) / fractal.max_iterations as f32;
+        color(palette, smooth * fractal.palette_cycles)
+    };
     unsafe {
         image.write(id.xy(), to_write);
     }
+}
+
+/// Colour of the palette repeated every unit of `t`.
+fn color(palette: &[GradientStop], t: f32) -> Vec4 {
+    gradient(palette, t.fract()).extend(1.0)
 }
\ No newline at end of file
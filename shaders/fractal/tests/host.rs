@@ -0,0 +1,60 @@
+//! The `fractal` kernel run on the CPU through `shared::host`.
+
+use shared::fractal::{FractalConstants, GradientStop, MANDELBROT, SMOOTH};
+use shared::glam::{uvec2, Vec4};
+use shared::host::{dispatch, Image2d};
+
+const PALETTE: [GradientStop; 2] = [
+    GradientStop { position: 0.0, color: [1.0, 0.0, 0.0] },
+    GradientStop { position: 1.0, color: [0.0, 0.0, 1.0] },
+];
+
+/// The whole Mandelbrot set in an image of `size`, as the fractal window first shows it.
+fn mandelbrot(size: [u32; 2]) -> FractalConstants {
+    FractalConstants {
+        image_size: size,
+        center: [-0.5, 0.0],
+        zoom: 0.8,
+        max_iterations: 200,
+        escape_radius: 4.0,
+        formula: MANDELBROT,
+        shading: SMOOTH,
+        palette_cycles: 4.0,
+        samples_per_side: 1,
+        ..FractalConstants::default()
+    }
+}
+
+/// Render `constants` with workgroups of 8 by 8 invocations, like the GPU does.
+fn render(constants: &FractalConstants) -> Image2d {
+    let [width, height] = constants.image_size;
+    let mut image = Image2d::new(constants.image_size);
+    dispatch([8, 8, 1], [(width + 7) / 8, (height + 7) / 8, 1], |id| {
+        fractal::fractal(id, constants, &mut image, &PALETTE)
+    });
+    image
+}
+
+#[test]
+fn pixels_inside_the_set_are_black() {
+    // The centre of the image is -0.5, well inside the main cardioid.
+    let image = render(&mandelbrot([33, 21]));
+    assert_eq!(image.read(uvec2(16, 10)), Vec4::new(0.0, 0.0, 0.0, 1.0));
+}
+
+#[test]
+fn pixels_outside_the_set_are_coloured() {
+    // The top left corner is around -2.5 + 1.25i, which escapes at once.
+    let image = render(&mandelbrot([33, 21]));
+    let corner = image.read(uvec2(0, 0));
+    assert_eq!(corner.w, 1.0);
+    assert!(corner.truncate().max_element() > 0.0, "the corner is {}", corner);
+}
+
+#[test]
+fn invocations_past_the_edges_are_skipped() {
+    // Neither side is a multiple of the workgroup size, and `Image2d` panics on writes past
+    // its edges.
+    let image = render(&mandelbrot([13, 5]));
+    assert_eq!(image.size(), [13, 5]);
+}
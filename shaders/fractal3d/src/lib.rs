@@ -0,0 +1,154 @@
+#![cfg_attr(
+    target_arch = "spirv",
+    no_std,
+    feature(register_attr),
+    register_attr(spirv)
+)]
+// HACK(eddyb) can't easily see warnings otherwise from `spirv-builder` builds.
+#![deny(warnings)]
+
+#[cfg(not(target_arch = "spirv"))]
+use spirv_std::macros::spirv;
+use shared::fractal3d::{Fractal3dConstants, Mandelbox, Mandelbulb, MengerSponge, MANDELBOX, MENGER_SPONGE};
+use shared::glam::{vec2, vec3, UVec2, UVec3, Vec3, Vec3Swizzles};
+use shared::sdf::{gradient, Sdf};
+use shared::{color_sky, unit_vector, Camera, Ray, Rng};
+#[cfg(target_arch = "spirv")]
+use spirv_std::Image;
+
+#[cfg(target_arch = "spirv")]
+type Image2d = Image!(2D, format=rgba8, sampled=false);
+/// Stand-in for running the kernels on the CPU.
+#[cfg(not(target_arch = "spirv"))]
+type Image2d = shared::host::Image2d;
+
+/// Distance past which a ray is considered to miss, all the fractals fit well within it.
+const MAX_DISTANCE: f32 = 20.0;
+
+/// Sphere trace the fractal selected by `Fractal3dConstants::fractal`.
+#[spirv(compute(threads(8, 8)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &Fractal3dConstants,
+    #[spirv(descriptor_set = 0, binding = 0)] image: &mut Image2d,
+) {
+    let size = UVec2::from(constants.image_size);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    let color = match constants.fractal {
+        MANDELBOX => render(
+            constants,
+            Mandelbox {
+                scale: constants.scale,
+                iterations: constants.iterations,
+            },
+            id.xy(),
+        ),
+        MENGER_SPONGE => render(
+            constants,
+            MengerSponge {
+                iterations: constants.iterations,
+            },
+            id.xy(),
+        ),
+        _ => render(
+            constants,
+            Mandelbulb {
+                power: constants.power,
+                iterations: constants.iterations,
+            },
+            id.xy(),
+        ),
+    };
+    unsafe {
+        image.write(id.xy(), color.extend(1.0));
+    }
+}
+
+/// The average of a grid of samples of the pixel, each jittered within its cell.
+fn render(constants: &Fractal3dConstants, sdf: impl Copy + Sdf, pixel: UVec2) -> Vec3 {
+    let size = UVec2::from(constants.image_size).as_vec2();
+    let camera = Camera::new(
+        Vec3::from(constants.eye),
+        Vec3::from(constants.target),
+        vec3(0.0, 1.0, 0.0),
+        constants.vfov,
+        size.x / size.y,
+        0.0,
+        1.0,
+    );
+    let samples_per_side = constants.samples_per_side.max(1);
+    let mut rng = Rng::for_pixel(pixel, 0);
+    let mut color = Vec3::ZERO;
+    let mut y = 0;
+    while y < samples_per_side {
+        let mut x = 0;
+        while x < samples_per_side {
+            let jitter = if samples_per_side == 1 {
+                vec2(0.5, 0.5)
+            } else {
+                (vec2(x as f32, y as f32) + vec2(rng.gen(), rng.gen())) / samples_per_side as f32
+            };
+            let uv = (pixel.as_vec2() + jitter) / size;
+            // Without an aperture the camera never draws from `rng`.
+            let ray = camera.ray(&mut rng, vec2(uv.x, 1.0 - uv.y));
+            color += trace(constants, sdf, Ray::new(ray.origin(), unit_vector(ray.direction())));
+            x += 1;
+        }
+        y += 1;
+    }
+    color / (samples_per_side * samples_per_side) as f32
+}
+
+/// Colour seen along `ray`, whose direction is normalised.
+fn trace(constants: &Fractal3dConstants, sdf: impl Copy + Sdf, ray: Ray) -> Vec3 {
+    let mut t = 0.0;
+    let mut step = 0;
+    let mut hit = false;
+    while step < constants.max_steps && t < MAX_DISTANCE {
+        let d = sdf.distance(ray.point_at_parameter(t));
+        if d < constants.epsilon * t {
+            hit = true;
+            break;
+        }
+        t += d;
+        step += 1;
+    }
+    if !hit {
+        return color_sky(&ray);
+    }
+
+    let p = ray.point_at_parameter(t);
+    let normal = gradient(sdf, p);
+    // Creases take many small steps to reach, and are also where little ambient light gets in.
+    let occlusion = 1.0 - step as f32 / constants.max_steps as f32;
+    let light = unit_vector(Vec3::from(constants.light_direction));
+    let shadow = soft_shadow(constants, sdf, p + normal * (2.0 * constants.epsilon * t), light);
+    let diffuse = normal.dot(light).max(0.0) * shadow;
+
+    let albedo = vec3(0.8, 0.65, 0.5);
+    albedo * (0.25 * occlusion * color_sky(&Ray::new(p, normal)) + 0.9 * diffuse)
+}
+
+/// Fraction of the light reaching `p`, falling off smoothly at the edges of shadows according
+/// to how close the ray towards the light passes by the surface.
+fn soft_shadow(constants: &Fractal3dConstants, sdf: impl Copy + Sdf, p: Vec3, light: Vec3) -> f32 {
+    let ray = Ray::new(p, light);
+    let mut shade: f32 = 1.0;
+    let mut t = 0.0;
+    let mut step = 0;
+    while step < constants.max_steps && t < MAX_DISTANCE {
+        let d = sdf.distance(ray.point_at_parameter(t));
+        if d < constants.epsilon * t {
+            return 0.0;
+        }
+        if t > 0.0 {
+            shade = shade.min(constants.shadow_sharpness * d / t);
+        }
+        t += d;
+        step += 1;
+    }
+    shade
+}
\ No newline at end of file
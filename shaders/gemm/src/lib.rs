@@ -0,0 +1,105 @@
+#![cfg_attr(
+    target_arch = "spirv",
+    no_std,
+    feature(register_attr),
+    register_attr(spirv)
+)]
+// HACK(eddyb) can't easily see warnings otherwise from `spirv-builder` builds.
+#![deny(warnings)]
+
+#[cfg(not(target_arch = "spirv"))]
+use spirv_std::macros::spirv;
+use shared::gemm::{GemmConstants, BLOCK_M, BLOCK_N, THREADS_M, THREADS_N, TILE_K, TILE_M, TILE_N};
+use shared::glam::UVec3;
+use spirv_std::arch::workgroup_memory_barrier_with_group_sync;
+
+/// `C = A B`, a `TILE_M` by `TILE_N` tile of `C` per workgroup.
+///
+/// The workgroup steps along `k` through tiles of `A` and `B` copied to workgroup memory, so
+/// that every element is read from the buffers once per workgroup instead of once per
+/// invocation. Every invocation then keeps a `BLOCK_M` by `BLOCK_N` block of `C` in registers,
+/// so that every element read from workgroup memory is used several times.
+#[spirv(compute(threads(16, 16)))]
+pub fn gemm(
+    #[spirv(local_invocation_id)] local_id: UVec3,
+    #[spirv(workgroup_id)] group_id: UVec3,
+    #[spirv(push_constant)] constants: &GemmConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] a: &[f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] b: &[f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] c: &mut [f32],
+    #[spirv(workgroup)] a_tile: &mut [f32; (TILE_M * TILE_K) as usize],
+    #[spirv(workgroup)] b_tile: &mut [f32; (TILE_K * TILE_N) as usize],
+) {
+    let (m, n, k) = (constants.m, constants.n, constants.k);
+    let row0 = group_id.y * TILE_M;
+    let col0 = group_id.x * TILE_N;
+    let invocation = local_id.y * THREADS_N + local_id.x;
+    let invocations = THREADS_M * THREADS_N;
+
+    let mut acc = [[0.0f32; BLOCK_N as usize]; BLOCK_M as usize];
+    let mut a_reg = [0.0f32; BLOCK_M as usize];
+    let mut b_reg = [0.0f32; BLOCK_N as usize];
+
+    let mut k0 = 0;
+    while k0 < k {
+        // Copy the tiles, with zeros past the edges of the matrices.
+        let mut e = invocation;
+        while e < TILE_M * TILE_K {
+            let (row, kk) = (row0 + e / TILE_K, k0 + e % TILE_K);
+            a_tile[e as usize] = if row < m && kk < k { a[(row * k + kk) as usize] } else { 0.0 };
+            e += invocations;
+        }
+        e = invocation;
+        while e < TILE_K * TILE_N {
+            let (kk, col) = (k0 + e / TILE_N, col0 + e % TILE_N);
+            b_tile[e as usize] = if kk < k && col < n { b[(kk * n + col) as usize] } else { 0.0 };
+            e += invocations;
+        }
+        barrier();
+
+        let mut kk = 0;
+        while kk < TILE_K {
+            let mut i = 0;
+            while i < BLOCK_M {
+                a_reg[i as usize] = a_tile[((local_id.y + i * THREADS_M) * TILE_K + kk) as usize];
+                i += 1;
+            }
+            let mut j = 0;
+            while j < BLOCK_N {
+                b_reg[j as usize] = b_tile[(kk * TILE_N + local_id.x + j * THREADS_N) as usize];
+                j += 1;
+            }
+            i = 0;
+            while i < BLOCK_M {
+                j = 0;
+                while j < BLOCK_N {
+                    acc[i as usize][j as usize] += a_reg[i as usize] * b_reg[j as usize];
+                    j += 1;
+                }
+                i += 1;
+            }
+            kk += 1;
+        }
+        // Everyone is done with the tiles before they are overwritten.
+        barrier();
+        k0 += TILE_K;
+    }
+
+    let mut i = 0;
+    while i < BLOCK_M {
+        let row = row0 + local_id.y + i * THREADS_M;
+        let mut j = 0;
+        while j < BLOCK_N {
+            let col = col0 + local_id.x + j * THREADS_N;
+            if row < m && col < n {
+                c[(row * n + col) as usize] = acc[i as usize][j as usize];
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+fn barrier() {
+    unsafe { workgroup_memory_barrier_with_group_sync() }
+}
\ No newline at end of file
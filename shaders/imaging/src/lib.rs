@@ -0,0 +1,209 @@
+#![cfg_attr(
+    target_arch = "spirv",
+    no_std,
+    feature(register_attr),
+    register_attr(spirv)
+)]
+// HACK(eddyb) can't easily see warnings otherwise from `spirv-builder` builds.
+#![deny(warnings)]
+
+//! Image processing, from one storage image into another.
+//!
+//! Reads past the edges of the input repeat its edge texels, and every kernel skips the
+//! invocations past the edges of its output.
+
+#[cfg(not(target_arch = "spirv"))]
+use spirv_std::macros::spirv;
+use shared::glam::{ivec2, IVec2, UVec2, UVec3, Vec2, Vec3Swizzles, Vec4};
+use shared::imaging::{gaussian, lanczos, luminance, ImagingConstants, BILINEAR, LANCZOS_LOBES};
+#[cfg(target_arch = "spirv")]
+use spirv_std::Image;
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
+
+#[cfg(target_arch = "spirv")]
+type Image2d = Image!(2D, format=rgba32f, sampled=false);
+/// Stand-in for running the kernels on the CPU.
+#[cfg(not(target_arch = "spirv"))]
+type Image2d = shared::host::Image2d;
+
+/// One pass of a separable Gaussian blur, along `constants.direction`.
+#[spirv(compute(threads(8, 8)))]
+pub fn blur(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &ImagingConstants,
+    #[spirv(descriptor_set = 0, binding = 0)] input: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 1)] output: &mut Image2d,
+) {
+    let pixel = id.xy();
+    if !inside(pixel, constants.output_size) {
+        return;
+    }
+
+    let direction = IVec2::from(constants.direction);
+    let radius = constants.blur_radius();
+    let mut sum = Vec4::ZERO;
+    let mut weights = 0.0;
+    let mut i = -radius;
+    while i <= radius {
+        let weight = gaussian(i as f32, constants.sigma);
+        sum += weight * texel(input, constants.input_size, pixel.as_ivec2() + i * direction);
+        weights += weight;
+        i += 1;
+    }
+    unsafe {
+        output.write(pixel, sum / weights);
+    }
+}
+
+/// Magnitude of the gradient of the luminance, with the Sobel operator.
+#[spirv(compute(threads(8, 8)))]
+pub fn sobel(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &ImagingConstants,
+    #[spirv(descriptor_set = 0, binding = 0)] input: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 1)] output: &mut Image2d,
+) {
+    let pixel = id.xy();
+    if !inside(pixel, constants.output_size) {
+        return;
+    }
+
+    // Luminance of the 3 by 3 texels around the pixel, row by row.
+    let mut l = [[0.0f32; 3]; 3];
+    let mut y = 0;
+    while y < 3 {
+        let mut x = 0;
+        while x < 3 {
+            let p = pixel.as_ivec2() + ivec2(x as i32 - 1, y as i32 - 1);
+            l[y][x] = luminance(texel(input, constants.input_size, p).truncate());
+            x += 1;
+        }
+        y += 1;
+    }
+    let gx = l[0][2] + 2.0 * l[1][2] + l[2][2] - l[0][0] - 2.0 * l[1][0] - l[2][0];
+    let gy = l[2][0] + 2.0 * l[2][1] + l[2][2] - l[0][0] - 2.0 * l[0][1] - l[0][2];
+    let magnitude = (gx * gx + gy * gy).sqrt();
+    let alpha = texel(input, constants.input_size, pixel.as_ivec2()).w;
+    unsafe {
+        output.write(pixel, Vec4::new(magnitude, magnitude, magnitude, alpha));
+    }
+}
+
+/// Weighted sum of the texels around every texel, with the row major `weights` of
+/// `constants.kernel_size`. Alpha is left alone.
+#[spirv(compute(threads(8, 8)))]
+pub fn convolve(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &ImagingConstants,
+    #[spirv(descriptor_set = 0, binding = 0)] input: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 1)] output: &mut Image2d,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] weights: &[f32],
+) {
+    let pixel = id.xy();
+    if !inside(pixel, constants.output_size) {
+        return;
+    }
+
+    let [columns, rows] = constants.kernel_size;
+    let corner = pixel.as_ivec2() - ivec2((columns / 2) as i32, (rows / 2) as i32);
+    let mut sum = Vec4::ZERO;
+    let mut y = 0;
+    while y < rows {
+        let mut x = 0;
+        while x < columns {
+            let weight = weights[(y * columns + x) as usize];
+            sum += weight * texel(input, constants.input_size, corner + ivec2(x as i32, y as i32));
+            x += 1;
+        }
+        y += 1;
+    }
+    let alpha = texel(input, constants.input_size, pixel.as_ivec2()).w;
+    unsafe {
+        output.write(pixel, sum.truncate().extend(alpha));
+    }
+}
+
+/// Resample the input to `constants.output_size` with `constants.filter`.
+///
+/// The Lanczos filter is stretched by the ratio of the sizes when shrinking, so that it
+/// averages every input texel instead of skipping some. Bilinear filtering only ever looks at
+/// the four nearest texels.
+#[spirv(compute(threads(8, 8)))]
+pub fn resize(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &ImagingConstants,
+    #[spirv(descriptor_set = 0, binding = 0)] input: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 1)] output: &mut Image2d,
+) {
+    let pixel = id.xy();
+    if !inside(pixel, constants.output_size) {
+        return;
+    }
+
+    let input_size = constants.input_size;
+    let scale = UVec2::from(input_size).as_vec2() / UVec2::from(constants.output_size).as_vec2();
+    // Position of the centre of the pixel in the input, in texels.
+    let center = (pixel.as_vec2() + 0.5) * scale - 0.5;
+
+    let color = if constants.filter == BILINEAR {
+        let corner = center.floor();
+        let t = center - corner;
+        let corner = corner.as_ivec2();
+        let top = texel(input, input_size, corner).lerp(texel(input, input_size, corner + ivec2(1, 0)), t.x);
+        let bottom = texel(input, input_size, corner + ivec2(0, 1))
+            .lerp(texel(input, input_size, corner + ivec2(1, 1)), t.x);
+        top.lerp(bottom, t.y)
+    } else {
+        let stretch = scale.max(Vec2::ONE);
+        let first = (center - LANCZOS_LOBES * stretch).ceil().as_ivec2();
+        let last = (center + LANCZOS_LOBES * stretch).floor().as_ivec2();
+        let mut sum = Vec4::ZERO;
+        let mut weights = 0.0;
+        let mut y = first.y;
+        while y <= last.y {
+            let weight_y = lanczos((y as f32 - center.y) / stretch.y);
+            let mut x = first.x;
+            while x <= last.x {
+                let weight = weight_y * lanczos((x as f32 - center.x) / stretch.x);
+                sum += weight * texel(input, input_size, ivec2(x, y));
+                weights += weight;
+                x += 1;
+            }
+            y += 1;
+        }
+        sum / weights
+    };
+    unsafe {
+        output.write(pixel, color);
+    }
+}
+
+/// Brightness, contrast, saturation and gamma, as in `ImagingConstants::adjust`.
+#[spirv(compute(threads(8, 8)))]
+pub fn adjust(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &ImagingConstants,
+    #[spirv(descriptor_set = 0, binding = 0)] input: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 1)] output: &mut Image2d,
+) {
+    let pixel = id.xy();
+    if !inside(pixel, constants.output_size) {
+        return;
+    }
+
+    let color = constants.adjust(texel(input, constants.input_size, pixel.as_ivec2()));
+    unsafe {
+        output.write(pixel, color);
+    }
+}
+
+fn inside(pixel: UVec2, size: [u32; 2]) -> bool {
+    pixel.x < size[0] && pixel.y < size[1]
+}
+
+/// The texel of `image` at `p`, or the nearest one on its edge for `p` outside of it.
+fn texel(image: &Image2d, size: [u32; 2], p: IVec2) -> Vec4 {
+    let p = p.clamp(IVec2::ZERO, ivec2(size[0] as i32 - 1, size[1] as i32 - 1));
+    image.read(p.as_uvec2())
+}
\ No newline at end of file
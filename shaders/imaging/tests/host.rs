@@ -0,0 +1,119 @@
+//! The image processing kernels run on the CPU through `shared::host`.
+
+use shared::glam::{uvec2, vec4, UVec3, Vec4};
+use shared::host::{dispatch, Image2d};
+use shared::imaging::{ImagingConstants, BILINEAR};
+
+type Kernel = fn(UVec3, &ImagingConstants, &Image2d, &mut Image2d);
+
+/// Constants for an input and output of `size`, leaving the colours as they are.
+fn constants(size: [u32; 2]) -> ImagingConstants {
+    ImagingConstants {
+        input_size: size,
+        output_size: size,
+        contrast: 1.0,
+        saturation: 1.0,
+        gamma: 1.0,
+        ..ImagingConstants::default()
+    }
+}
+
+/// Image of `size` with `texel(x, y)` at every texel.
+fn image(size: [u32; 2], texel: impl Fn(u32, u32) -> Vec4) -> Image2d {
+    let mut image = Image2d::new(size);
+    for y in 0..size[1] {
+        for x in 0..size[0] {
+            unsafe {
+                image.write(uvec2(x, y), texel(x, y));
+            }
+        }
+    }
+    image
+}
+
+/// Run `kernel` on `input` with workgroups of 8 by 8 invocations, like the GPU does.
+fn run(kernel: Kernel, constants: &ImagingConstants, input: &Image2d) -> Image2d {
+    let [width, height] = constants.output_size;
+    let mut output = Image2d::new(constants.output_size);
+    dispatch([8, 8, 1], [(width + 7) / 8, (height + 7) / 8, 1], |id| {
+        kernel(id, constants, input, &mut output)
+    });
+    output
+}
+
+fn assert_close(actual: Vec4, expected: Vec4, x: u32, y: u32) {
+    assert!(
+        (actual - expected).abs().max_element() < 1e-5,
+        "texel ({}, {}) is {} instead of {}",
+        x,
+        y,
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn sobel_finds_a_step_edge() {
+    // Black on the left half and white on the right half.
+    let size = [8, 4];
+    let input = image(size, |x, _| if x < 4 { vec4(0.0, 0.0, 0.0, 1.0) } else { Vec4::ONE });
+    let output = run(imaging::sobel, &constants(size), &input);
+    for y in 0..4 {
+        for x in 0..8 {
+            // The columns on either side of the edge see a gradient of 1 + 2 + 1.
+            let magnitude = if x == 3 || x == 4 { 4.0 } else { 0.0 };
+            let expected = vec4(magnitude, magnitude, magnitude, 1.0);
+            assert_close(output.read(uvec2(x, y)), expected, x, y);
+        }
+    }
+}
+
+#[test]
+fn blur_keeps_a_constant_image() {
+    let size = [9, 7];
+    let color = vec4(0.2, 0.4, 0.6, 1.0);
+    let input = image(size, |_, _| color);
+    let pass = |direction| ImagingConstants {
+        sigma: 1.5,
+        direction,
+        ..constants(size)
+    };
+    let output = run(imaging::blur, &pass([0, 1]), &run(imaging::blur, &pass([1, 0]), &input));
+    for y in 0..size[1] {
+        for x in 0..size[0] {
+            assert_close(output.read(uvec2(x, y)), color, x, y);
+        }
+    }
+}
+
+#[test]
+fn bilinear_resize_interpolates_between_texels() {
+    // Red along x and green along y, doubled in size.
+    let input = image([2, 2], |x, y| vec4(x as f32, y as f32, 0.0, 1.0));
+    let constants = ImagingConstants {
+        output_size: [4, 4],
+        filter: BILINEAR,
+        ..constants([2, 2])
+    };
+    let output = run(imaging::resize, &constants, &input);
+    // The outer texels repeat the edge, the inner ones are a quarter of the way in.
+    let ramp = [0.0, 0.25, 0.75, 1.0];
+    for y in 0..4 {
+        for x in 0..4 {
+            let expected = vec4(ramp[x as usize], ramp[y as usize], 0.0, 1.0);
+            assert_close(output.read(uvec2(x, y)), expected, x, y);
+        }
+    }
+}
+
+#[test]
+fn neutral_adjustments_keep_the_colours() {
+    let size = [5, 3];
+    let input = image(size, |x, y| vec4(x as f32 / 4.0, y as f32 / 2.0, 0.3, 0.5));
+    let output = run(imaging::adjust, &constants(size), &input);
+    for y in 0..size[1] {
+        for x in 0..size[0] {
+            assert_close(output.read(uvec2(x, y)), input.read(uvec2(x, y)), x, y);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,125 @@
+#![cfg_attr(
+    target_arch = "spirv",
+    no_std,
+    feature(register_attr),
+    register_attr(spirv)
+)]
+// HACK(eddyb) can't easily see warnings otherwise from `spirv-builder` builds.
+#![deny(warnings)]
+
+//! Outer totalistic cellular automata on a grid that wraps around, one storage image per
+//! generation with a texel per cell, white for alive and black for dead.
+
+#[cfg(not(target_arch = "spirv"))]
+use spirv_std::macros::spirv;
+use shared::glam::{uvec2, UVec2, UVec3, Vec3Swizzles, Vec4};
+use shared::life::{LifeConstants, GRID_ZOOM};
+#[cfg(target_arch = "spirv")]
+use spirv_std::Image;
+
+#[cfg(target_arch = "spirv")]
+type Image2d = Image!(2D, format=rgba8, sampled=false);
+/// Stand-in for running the kernels on the CPU.
+#[cfg(not(target_arch = "spirv"))]
+type Image2d = shared::host::Image2d;
+
+const ALIVE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const DEAD: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+/// Edges of the dead cells, when zoomed in.
+const EDGE: [f32; 4] = [0.15, 0.15, 0.15, 1.0];
+/// Around the grid.
+const OUTSIDE: [f32; 4] = [0.05, 0.05, 0.1, 1.0];
+
+/// The generation after the one in `cells`, written to `next`.
+///
+/// The two images must differ, as every cell reads its neighbours while the others are written.
+#[spirv(compute(threads(8, 8)))]
+pub fn step(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &LifeConstants,
+    #[spirv(descriptor_set = 0, binding = 0)] cells: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 1)] next: &mut Image2d,
+) {
+    let cell = id.xy();
+    if cell.x >= constants.size[0] || cell.y >= constants.size[1] {
+        return;
+    }
+
+    let mut neighbours = 0;
+    let mut y = 0;
+    while y < 3 {
+        let mut x = 0;
+        while x < 3 {
+            if x != 1 || y != 1 {
+                let neighbour = constants.neighbour(cell.into(), [x, y]);
+                neighbours += alive(cells, UVec2::from(neighbour)) as u32;
+            }
+            x += 1;
+        }
+        y += 1;
+    }
+    let state = if constants.next(alive(cells, cell), neighbours) { ALIVE } else { DEAD };
+    unsafe {
+        next.write(cell, Vec4::from(state));
+    }
+}
+
+/// Bring the cells within `constants.brush_radius` of `constants.brush_center` alive, one
+/// invocation per cell of the square around the brush.
+#[spirv(compute(threads(8, 8)))]
+pub fn paint(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &LifeConstants,
+    #[spirv(descriptor_set = 0, binding = 0)] cells: &mut Image2d,
+) {
+    let radius = constants.brush_radius;
+    if id.x > 2 * radius || id.y > 2 * radius {
+        return;
+    }
+    let (dx, dy) = (id.x as i32 - radius as i32, id.y as i32 - radius as i32);
+    if dx * dx + dy * dy > (radius * radius) as i32 {
+        return;
+    }
+
+    let [width, height] = constants.size;
+    let [x, y] = constants.brush_center;
+    // Wrap around the edges like the neighbours do.
+    let cell = uvec2((x + width + id.x - radius) % width, (y + height + id.y - radius) % height);
+    unsafe {
+        cells.write(cell, Vec4::from(ALIVE));
+    }
+}
+
+/// The grid as seen in the window, `constants.zoom` pixels per cell around
+/// `constants.view_center`, with the edges of the cells once they are `GRID_ZOOM` pixels wide.
+#[spirv(compute(threads(8, 8)))]
+pub fn draw(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &LifeConstants,
+    #[spirv(descriptor_set = 0, binding = 0)] cells: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 1)] output: &mut Image2d,
+) {
+    let pixel = id.xy();
+    if pixel.x >= constants.image_size[0] || pixel.y >= constants.image_size[1] {
+        return;
+    }
+
+    let p = constants.pixel_to_cell(pixel.as_vec2() + 0.5);
+    let size = UVec2::from(constants.size).as_vec2();
+    let color = if p.x < 0.0 || p.y < 0.0 || p.x >= size.x || p.y >= size.y {
+        OUTSIDE
+    } else if alive(cells, p.as_uvec2()) {
+        ALIVE
+    } else if constants.zoom >= GRID_ZOOM && ((p - p.floor()) * constants.zoom).min_element() < 1.0 {
+        EDGE
+    } else {
+        DEAD
+    };
+    unsafe {
+        output.write(pixel, Vec4::from(color));
+    }
+}
+
+fn alive(cells: &Image2d, cell: UVec2) -> bool {
+    cells.read(cell).x > 0.5
+}
\ No newline at end of file
@@ -0,0 +1,91 @@
+#![cfg_attr(
+    target_arch = "spirv",
+    no_std,
+    feature(register_attr),
+    register_attr(spirv)
+)]
+// HACK(eddyb) can't easily see warnings otherwise from `spirv-builder` builds.
+#![deny(warnings)]
+
+#[cfg(not(target_arch = "spirv"))]
+use spirv_std::macros::spirv;
+use shared::glam::{vec3, UVec3, Vec2, Vec3, Vec4};
+use shared::nbody::{NbodyConstants, NbodyView, WORKGROUP_SIZE};
+use spirv_std::arch::workgroup_memory_barrier_with_group_sync;
+
+/// Move every body by one time step, under the gravity of all the others.
+///
+/// The workgroup goes through the bodies a tile of `WORKGROUP_SIZE` at a time, every invocation
+/// copying one of them to workgroup memory, so that every body is read from the buffer once per
+/// workgroup instead of once per invocation. The positions are read from one buffer and written
+/// to another, as the other workgroups are still reading them.
+#[spirv(compute(threads(256)))]
+pub fn integrate(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(local_invocation_id)] local_id: UVec3,
+    #[spirv(push_constant)] constants: &NbodyConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] bodies: &[Vec4],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] velocities: &mut [Vec4],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] moved_bodies: &mut [Vec4],
+    #[spirv(workgroup)] tile: &mut [Vec4; WORKGROUP_SIZE as usize],
+) {
+    let i = id.x as usize;
+    let local = local_id.x as usize;
+    let count = constants.count as usize;
+    let body = if i < count { bodies[i] } else { Vec4::ZERO };
+
+    let mut acceleration = Vec3::ZERO;
+    let mut start = 0;
+    while start < count {
+        // Bodies past the end have no mass, so they pull on nothing.
+        tile[local] = if start + local < count { bodies[start + local] } else { Vec4::ZERO };
+        barrier();
+        let mut j = 0;
+        while j < WORKGROUP_SIZE as usize {
+            acceleration += constants.acceleration(body.truncate(), tile[j]);
+            j += 1;
+        }
+        // Everyone is done with the tile before it is overwritten.
+        barrier();
+        start += WORKGROUP_SIZE as usize;
+    }
+
+    if i < count {
+        // Semi-implicit Euler, which keeps orbits from spiralling out.
+        let velocity = velocities[i].truncate() + acceleration * constants.dt;
+        velocities[i] = velocity.extend(0.0);
+        moved_bodies[i] = (body.truncate() + velocity * constants.dt).extend(body.w);
+    }
+}
+
+/// Bodies as points, blue when slow and white when fast.
+#[spirv(vertex)]
+pub fn body_vs(
+    position: Vec4,
+    velocity: Vec4,
+    #[spirv(push_constant)] view: &NbodyView,
+    #[spirv(position, invariant)] out_pos: &mut Vec4,
+    #[spirv(point_size)] point_size: &mut f32,
+    color: &mut Vec3,
+) {
+    *out_pos = view.project(position.truncate());
+    *point_size = view.point_size;
+    let t = (velocity.truncate().length() / view.max_speed).min(1.0);
+    *color = vec3(0.2, 0.3, 0.8).lerp(Vec3::ONE, t);
+}
+
+/// Round sprites fading towards their edges, to be added onto what is already drawn.
+#[spirv(fragment)]
+pub fn body_fs(
+    #[spirv(point_coord)] point_coord: Vec2,
+    color: Vec3,
+    output: &mut Vec4,
+) {
+    let r2 = ((point_coord - Vec2::splat(0.5)) * 2.0).length_squared();
+    let falloff = (1.0 - r2).max(0.0);
+    *output = (color * (0.1 * falloff * falloff)).extend(1.0);
+}
+
+fn barrier() {
+    unsafe { workgroup_memory_barrier_with_group_sync() }
+}
\ No newline at end of file
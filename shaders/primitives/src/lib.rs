@@ -0,0 +1,284 @@
+#![cfg_attr(
+    target_arch = "spirv",
+    no_std,
+    feature(register_attr),
+    register_attr(spirv)
+)]
+// HACK(eddyb) can't easily see warnings otherwise from `spirv-builder` builds.
+#![deny(warnings)]
+
+//! Parallel primitives over `u32` buffers, for the other kernels to build on.
+//!
+//! Every kernel runs in workgroups of `WORKGROUP_SIZE` invocations, which must match their
+//! `threads(...)`, and works on its own block of the input. `engine::primitives` chains them
+//! into operations over buffers of any length.
+
+#[cfg(not(target_arch = "spirv"))]
+use spirv_std::macros::spirv;
+use shared::glam::UVec3;
+use shared::primitives::{PrimitiveConstants, MAX_BINS, RADIX, RADIX_BITS, SCAN_BLOCK, WORKGROUP_SIZE};
+use spirv_std::arch::{atomic_i_add, workgroup_memory_barrier_with_group_sync};
+use spirv_std::memory::{Scope, Semantics};
+
+/// Combine the elements of every block of `WORKGROUP_SIZE` elements with `constants.op`, into
+/// one element of `output` per block.
+#[spirv(compute(threads(256)))]
+pub fn reduce(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(local_invocation_id)] local_id: UVec3,
+    #[spirv(workgroup_id)] group_id: UVec3,
+    #[spirv(push_constant)] constants: &PrimitiveConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] input: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] output: &mut [u32],
+    #[spirv(workgroup)] partial: &mut [u32; WORKGROUP_SIZE as usize],
+) {
+    let local = local_id.x as usize;
+    partial[local] = if id.x < constants.len {
+        input[id.x as usize]
+    } else {
+        constants.identity()
+    };
+    barrier();
+
+    // Fold the upper half of what is left onto the lower half.
+    let mut stride = WORKGROUP_SIZE as usize / 2;
+    while stride > 0 {
+        if local < stride {
+            partial[local] = constants.combine(partial[local], partial[local + stride]);
+        }
+        barrier();
+        stride /= 2;
+    }
+
+    if local == 0 {
+        output[group_id.x as usize] = partial[0];
+    }
+}
+
+/// Exclusive prefix sum of every block of `SCAN_BLOCK` elements, with the Blelloch scan. The
+/// sum of the whole block goes to `block_sums`, for `scan_add` to offset the blocks by.
+#[spirv(compute(threads(256)))]
+pub fn scan_blocks(
+    #[spirv(local_invocation_id)] local_id: UVec3,
+    #[spirv(workgroup_id)] group_id: UVec3,
+    #[spirv(push_constant)] constants: &PrimitiveConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] input: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] output: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] block_sums: &mut [u32],
+    #[spirv(workgroup)] tree: &mut [u32; SCAN_BLOCK as usize],
+) {
+    let local = local_id.x as usize;
+    let block = SCAN_BLOCK as usize;
+    let base = group_id.x as usize * block;
+    let len = constants.len as usize;
+    tree[2 * local] = if base + 2 * local < len { input[base + 2 * local] } else { 0 };
+    tree[2 * local + 1] = if base + 2 * local + 1 < len { input[base + 2 * local + 1] } else { 0 };
+
+    // Up sweep: every node of the tree gets the sum of its leaves.
+    let mut offset = 1;
+    let mut nodes = block / 2;
+    while nodes > 0 {
+        barrier();
+        if local < nodes {
+            let left = offset * (2 * local + 1) - 1;
+            let right = offset * (2 * local + 2) - 1;
+            tree[right] += tree[left];
+        }
+        offset *= 2;
+        nodes /= 2;
+    }
+
+    barrier();
+    if local == 0 {
+        block_sums[group_id.x as usize] = tree[block - 1];
+        tree[block - 1] = 0;
+    }
+
+    // Down sweep: every node passes what comes before it to its left child, and that plus the
+    // sum of the left child to its right child.
+    nodes = 1;
+    while nodes < block {
+        offset /= 2;
+        barrier();
+        if local < nodes {
+            let left = offset * (2 * local + 1) - 1;
+            let right = offset * (2 * local + 2) - 1;
+            let sum = tree[left];
+            tree[left] = tree[right];
+            tree[right] += sum;
+        }
+        nodes *= 2;
+    }
+    barrier();
+
+    if base + 2 * local < len {
+        output[base + 2 * local] = tree[2 * local];
+    }
+    if base + 2 * local + 1 < len {
+        output[base + 2 * local + 1] = tree[2 * local + 1];
+    }
+}
+
+/// Add to every element of `data` the offset of its block of `SCAN_BLOCK` elements.
+#[spirv(compute(threads(256)))]
+pub fn scan_add(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &PrimitiveConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] data: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] block_offsets: &[u32],
+) {
+    if id.x >= constants.len {
+        return;
+    }
+    data[id.x as usize] += block_offsets[(id.x / SCAN_BLOCK) as usize];
+}
+
+/// Count the digits of the keys of every block of `WORKGROUP_SIZE` keys, digit by digit so that
+/// the exclusive scan of `counts` is where the keys of every digit and block go.
+#[spirv(compute(threads(256)))]
+pub fn radix_count(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(local_invocation_id)] local_id: UVec3,
+    #[spirv(workgroup_id)] group_id: UVec3,
+    #[spirv(num_workgroups)] group_count: UVec3,
+    #[spirv(push_constant)] constants: &PrimitiveConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] keys: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] counts: &mut [u32],
+    #[spirv(workgroup)] histogram: &mut [u32; RADIX as usize],
+) {
+    let local = local_id.x as usize;
+    if local < RADIX as usize {
+        histogram[local] = 0;
+    }
+    barrier();
+    if id.x < constants.len {
+        let digit = constants.digit(keys[id.x as usize]) as usize;
+        unsafe {
+            atomic_i_add::<u32, { Scope::Workgroup as u32 }, { Semantics::NONE.bits() }>(&mut histogram[digit], 1);
+        }
+    }
+    barrier();
+    if local < RADIX as usize {
+        counts[local * group_count.x as usize + group_id.x as usize] = histogram[local];
+    }
+}
+
+/// Move the keys and values of every block of `WORKGROUP_SIZE` to where `offsets`, the
+/// exclusive scan of the counts of `radix_count`, puts them for their digit.
+///
+/// The block is first sorted by digit in workgroup memory, one bit at a time so that keys of
+/// the same digit keep their order, which makes the rank of a key within its digit its
+/// distance to the first key of the digit.
+#[spirv(compute(threads(256)))]
+pub fn radix_scatter(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(local_invocation_id)] local_id: UVec3,
+    #[spirv(workgroup_id)] group_id: UVec3,
+    #[spirv(num_workgroups)] group_count: UVec3,
+    #[spirv(push_constant)] constants: &PrimitiveConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] keys: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] values: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] offsets: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] sorted_keys: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] sorted_values: &mut [u32],
+    #[spirv(workgroup)] block_keys: &mut [u32; WORKGROUP_SIZE as usize],
+    #[spirv(workgroup)] block_values: &mut [u32; WORKGROUP_SIZE as usize],
+    #[spirv(workgroup)] scratch: &mut [u32; WORKGROUP_SIZE as usize],
+    #[spirv(workgroup)] digit_starts: &mut [u32; RADIX as usize],
+) {
+    let local = local_id.x as usize;
+    // Past the end the keys have the last digit, so they end up after every other key.
+    let (mut key, mut value) = if id.x < constants.len {
+        (keys[id.x as usize], values[id.x as usize])
+    } else {
+        (u32::MAX, 0)
+    };
+
+    let mut bit = 0;
+    while bit < RADIX_BITS {
+        let one = (key >> (constants.shift + bit)) & 1;
+        let (zeros_before, zeros) = exclusive_scan(local, 1 - one, scratch);
+        let position = if one == 0 {
+            zeros_before
+        } else {
+            zeros + local as u32 - zeros_before
+        } as usize;
+        block_keys[position] = key;
+        block_values[position] = value;
+        barrier();
+        key = block_keys[local];
+        value = block_values[local];
+        barrier();
+        bit += 1;
+    }
+
+    // The first key of every digit is where the digit before it changes.
+    let digit = constants.digit(key);
+    if local < RADIX as usize {
+        digit_starts[local] = 0;
+    }
+    barrier();
+    if local > 0 && constants.digit(block_keys[local - 1]) != digit {
+        digit_starts[digit as usize] = local as u32;
+    }
+    barrier();
+
+    // The keys past the end are sorted last, so the keys still in the input are the first ones.
+    if id.x < constants.len {
+        let offset = offsets[digit as usize * group_count.x as usize + group_id.x as usize];
+        let index = (offset + local as u32 - digit_starts[digit as usize]) as usize;
+        sorted_keys[index] = key;
+        sorted_values[index] = value;
+    }
+}
+
+/// Count the keys of the input in `constants.bins` bins, adding to the counts in `bins`.
+#[spirv(compute(threads(256)))]
+pub fn histogram(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(local_invocation_id)] local_id: UVec3,
+    #[spirv(push_constant)] constants: &PrimitiveConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] keys: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] bins: &mut [u32],
+    #[spirv(workgroup)] block_bins: &mut [u32; MAX_BINS as usize],
+) {
+    // Count in workgroup memory first, so that few atomics reach the buffer.
+    let local = local_id.x as usize;
+    block_bins[local] = 0;
+    barrier();
+    if id.x < constants.len {
+        let bin = constants.bin(keys[id.x as usize]) as usize;
+        unsafe {
+            atomic_i_add::<u32, { Scope::Workgroup as u32 }, { Semantics::NONE.bits() }>(&mut block_bins[bin], 1);
+        }
+    }
+    barrier();
+    if local < constants.bins as usize && block_bins[local] != 0 {
+        unsafe {
+            atomic_i_add::<u32, { Scope::Device as u32 }, { Semantics::NONE.bits() }>(&mut bins[local], block_bins[local]);
+        }
+    }
+}
+
+/// Exclusive prefix sum of `value` over the invocations of the workgroup, along with the sum
+/// of all of them, with the Hillis-Steele scan.
+fn exclusive_scan(local: usize, value: u32, scratch: &mut [u32; WORKGROUP_SIZE as usize]) -> (u32, u32) {
+    scratch[local] = value;
+    barrier();
+    let mut offset = 1;
+    while offset < WORKGROUP_SIZE as usize {
+        let before = if local >= offset { scratch[local - offset] } else { 0 };
+        barrier();
+        scratch[local] += before;
+        barrier();
+        offset *= 2;
+    }
+    let result = (scratch[local] - value, scratch[WORKGROUP_SIZE as usize - 1]);
+    // Everyone has read the sums before `scratch` is used again.
+    barrier();
+    result
+}
+
+fn barrier() {
+    unsafe { workgroup_memory_barrier_with_group_sync() }
+}
\ No newline at end of file
@@ -0,0 +1,128 @@
+//! An eight second turntable of the scene, with the balls and their materials changing as the
+//! camera goes around them.
+
+use shared::animation::{Interpolation, Keyframe, Track, Transform};
+use shared::glam::{vec3, Vec3};
+use shared::{Camera, Lambertian, Materials, Metal, Sphere};
+
+/// Camera orbiting the scene once.
+pub fn camera(time: f32, aspect: f32) -> Camera {
+    // Every 45 degrees on a circle of radius 8, rising and sinking along the way.
+    let from = Track::new(
+        Interpolation::CatmullRom,
+        [
+            Keyframe::new(0.0, vec3(0.0, 2.0, 8.0)),
+            Keyframe::new(1.0, vec3(5.657, 2.5, 5.657)),
+            Keyframe::new(2.0, vec3(8.0, 3.0, 0.0)),
+            Keyframe::new(3.0, vec3(5.657, 2.5, -5.657)),
+            Keyframe::new(4.0, vec3(0.0, 2.0, -8.0)),
+            Keyframe::new(5.0, vec3(-5.657, 1.5, -5.657)),
+            Keyframe::new(6.0, vec3(-8.0, 1.0, 0.0)),
+            Keyframe::new(7.0, vec3(-5.657, 1.5, 5.657)),
+            Keyframe::new(8.0, vec3(0.0, 2.0, 8.0)),
+        ],
+    );
+    let to = Track::new(
+        Interpolation::Linear,
+        [
+            Keyframe::new(0.0, vec3(0.0, 1.0, 0.0)),
+            Keyframe::new(4.0, vec3(2.2, 1.0, 0.0)),
+            Keyframe::new(8.0, vec3(0.0, 1.0, 0.0)),
+        ],
+    );
+    let vfov = Track::new(
+        Interpolation::Bezier,
+        [
+            Keyframe::bezier(0.0, 0.6, 0.6, 0.6),
+            Keyframe::bezier(4.0, 0.4, 0.35, 0.35),
+            Keyframe::bezier(8.0, 0.6, 0.6, 0.6),
+        ],
+    );
+    let aperture = Track::new(
+        Interpolation::Linear,
+        [
+            Keyframe::new(0.0, 0.0),
+            Keyframe::new(4.0, 0.1),
+            Keyframe::new(8.0, 0.0),
+        ],
+    );
+
+    let from = from.sample(time);
+    let to = to.sample(time);
+    Camera::new(
+        from,
+        to,
+        vec3(0.0, 1.0, 0.0),
+        vfov.sample(time),
+        aspect,
+        aperture.sample(time),
+        (from - to).length(),
+    )
+}
+
+/// The metal ball bounces twice while the diffuse one shrinks and grows back.
+pub fn world(time: f32, world: [Sphere; 4]) -> [Sphere; 4] {
+    let bounce = Track::new(
+        Interpolation::Bezier,
+        [
+            Keyframe::new(0.0, Vec3::ZERO),
+            Keyframe::bezier(2.0, vec3(0.0, 1.5, 0.0), vec3(0.0, 1.5, 0.0), vec3(0.0, 1.5, 0.0)),
+            Keyframe::bezier(4.0, Vec3::ZERO, vec3(0.0, 0.8, 0.0), vec3(0.0, 0.8, 0.0)),
+            Keyframe::bezier(6.0, vec3(0.0, 1.5, 0.0), vec3(0.0, 1.5, 0.0), vec3(0.0, 1.5, 0.0)),
+            Keyframe::bezier(8.0, Vec3::ZERO, vec3(0.0, 0.8, 0.0), Vec3::ZERO),
+        ],
+    );
+    let shrink = Track::new(
+        Interpolation::CatmullRom,
+        [
+            Keyframe::new(0.0, 1.0),
+            Keyframe::new(4.0, 0.6),
+            Keyframe::new(8.0, 1.0),
+        ],
+    );
+
+    let [ground, diffuse, glass, metal] = world;
+    // Scale the diffuse ball around the point it rests on.
+    let scale = shrink.sample(time);
+    let diffuse = Transform {
+        translation: (1.0 - scale) * (diffuse.center - vec3(0.0, diffuse.radius, 0.0)),
+        scale,
+    }
+        .apply_to_sphere(diffuse);
+    let metal = Transform {
+        translation: bounce.sample(time),
+        scale: 1.0,
+    }
+        .apply_to_sphere(metal);
+    [ground, diffuse, glass, metal]
+}
+
+/// The diffuse ball turns from brown to blue and the metal ball gets rough halfway through.
+pub fn materials(time: f32, materials: Materials<2, 1, 1>) -> Materials<2, 1, 1> {
+    let albedo = Track::new(
+        Interpolation::Linear,
+        [
+            Keyframe::new(0.0, vec3(0.4, 0.2, 0.1)),
+            Keyframe::new(4.0, vec3(0.1, 0.2, 0.4)),
+            Keyframe::new(8.0, vec3(0.4, 0.2, 0.1)),
+        ],
+    );
+    let fuzz = Track::new(
+        Interpolation::CatmullRom,
+        [
+            Keyframe::new(0.0, 0.0),
+            Keyframe::new(4.0, 0.3),
+            Keyframe::new(8.0, 0.0),
+        ],
+    );
+
+    let metal = materials.metal[0];
+    Materials {
+        lambertian: [
+            materials.lambertian[0],
+            Lambertian::new(albedo.sample(time)),
+        ],
+        metal: [Metal::new(metal.albedo, fuzz.sample(time))],
+        dielectric: materials.dielectric,
+    }
+}
@@ -0,0 +1,204 @@
+#![cfg_attr(
+    target_arch = "spirv",
+    no_std,
+    feature(register_attr),
+    register_attr(spirv)
+)]
+// HACK(eddyb) can't easily see warnings otherwise from `spirv-builder` builds.
+#![deny(warnings)]
+
+#[cfg(not(target_arch = "spirv"))]
+use spirv_std::macros::spirv;
+use shared::adaptive::PixelStats;
+use shared::glam::{uvec2, vec2, vec3, UVec2, UVec3, Vec2, Vec3, Vec3Swizzles};
+use shared::photon::{cell_index, color_photons, grid_cell, trace_photon, Photon, PointLight, CELL_CAPACITY};
+use shared::spectral::color_spectral_rgb;
+use shared::{
+    color, Camera, Dielectric, Lambertian, Lens, MaterialInfo, MaterialKind, Materials, Metal, Ray,
+    Rng, ShaderConstants, Sphere,
+};
+use spirv_std::arch::atomic_i_add;
+use spirv_std::memory::{Scope, Semantics};
+
+pub mod animation;
+pub mod wavefront;
+
+#[spirv(compute(threads(8, 8)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] pixels: &mut [PixelStats],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] photon_cells: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] photons: &[Photon],
+) {
+    let size = UVec2::from(constants.view_size_pixels);
+    let tile_size = UVec2::from(constants.tile_size_pixels);
+    if id.x >= tile_size.x || id.y >= tile_size.y {
+        return;
+    }
+    let index = (id.y * tile_size.x + id.x) as usize;
+    let pixel = id.xy() + UVec2::from(constants.tile_offset_pixels);
+    let mut stats = pixels[index];
+    if stats.is_converged() {
+        return;
+    }
+
+    let camera = camera(constants);
+    let materials = materials(constants);
+    let mut rng = Rng::for_pixel(pixel, constants.rng_seed_offset as u32);
+    let mut sample = 0;
+    while sample < constants.rays_per_pixel {
+        let jitter = vec2(rng.gen(), rng.gen());
+        let uv = (pixel.as_vec2() + jitter) / size.as_vec2();
+        let uv = vec2(uv.x, 1.0 - uv.y);
+        let c = if constants.chromatic_aberration != 0.0 {
+            // Every channel is seen through its own ray.
+            let red = camera.ray_channel(&mut rng, uv, 0);
+            let green = camera.ray_channel(&mut rng, uv, 1);
+            let blue = camera.ray_channel(&mut rng, uv, 2);
+            vec3(
+                radiance(constants, &mut rng, red, &materials, photon_cells, photons).x * camera.vignetting(&red),
+                radiance(constants, &mut rng, green, &materials, photon_cells, photons).y * camera.vignetting(&green),
+                radiance(constants, &mut rng, blue, &materials, photon_cells, photons).z * camera.vignetting(&blue),
+            )
+        } else {
+            let ray = camera.ray(&mut rng, uv);
+            radiance(constants, &mut rng, ray, &materials, photon_cells, photons) * camera.vignetting(&ray)
+        };
+        stats.add(c);
+        sample += 1;
+    }
+    stats.update_converged(constants);
+    pixels[index] = stats;
+}
+
+/// Light arriving along `ray`, with the integrator selected by `constants`.
+fn radiance(
+    constants: &ShaderConstants,
+    rng: &mut Rng,
+    ray: Ray,
+    materials: &Materials<2, 1, 1>,
+    photon_cells: &[u32],
+    photons: &[Photon],
+) -> Vec3 {
+    let world = world(constants);
+    if constants.spectral != 0 {
+        color_spectral_rgb(constants.ray_bounce_limit, rng, ray, world, materials)
+    } else if constants.photon_mapping != 0 {
+        color_photons(
+            constants.ray_bounce_limit,
+            rng,
+            ray,
+            world,
+            materials,
+            &light(),
+            photon_cells,
+            photons,
+            constants.photon_radius,
+        )
+    } else {
+        color(constants.ray_bounce_limit, rng, ray, world, materials)
+    }
+}
+
+/// Trace photons from the light and store the caustic ones in the photon grid, which must have
+/// been cleared beforehand.
+#[spirv(compute(threads(64)))]
+pub fn photon_cs(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] photon_cells: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] photons: &mut [Photon],
+) {
+    if id.x >= constants.photon_count {
+        return;
+    }
+    // A fresh hash of the photon and the pass, so that every pass traces different photons.
+    let mut rng = Rng::for_pixel(uvec2(id.x, 0), constants.rng_seed_offset as u32);
+    let mut photon = Photon::default();
+    if !trace_photon(
+        constants.ray_bounce_limit,
+        constants.photon_count,
+        &mut rng,
+        &light(),
+        world(constants),
+        &materials(constants),
+        &mut photon,
+    ) {
+        return;
+    }
+
+    let cell = cell_index(grid_cell(Vec3::from(photon.position), constants.photon_radius), photon_cells.len());
+    let slot = unsafe {
+        atomic_i_add::<u32, { Scope::Device as u32 }, { Semantics::NONE.bits() }>(&mut photon_cells[cell], 1)
+    };
+    if slot < CELL_CAPACITY {
+        photons[cell * CELL_CAPACITY as usize + slot as usize] = photon;
+    }
+}
+
+fn camera(constants: &ShaderConstants) -> Camera {
+    let size = UVec2::from(constants.view_size_pixels).as_vec2();
+    let lens = Lens {
+        distortion: Vec2::from(constants.lens_distortion),
+        vignetting: constants.vignetting != 0,
+        chromatic_aberration: constants.chromatic_aberration,
+    };
+    if constants.animated != 0 {
+        return animation::camera(constants.time, size.x / size.y).with_lens(lens);
+    }
+    let from = vec3(0.0, 2.0, 8.0);
+    let to = vec3(0.0, 1.0, 0.0);
+    Camera::new(
+        from,
+        to,
+        vec3(0.0, 1.0, 0.0),
+        constants.vfov,
+        size.x / size.y,
+        constants.aperture,
+        (from - to).length(),
+    )
+        .with_lens(lens)
+}
+
+/// A glass, a diffuse and a metal ball on a huge diffuse one.
+fn world(constants: &ShaderConstants) -> [Sphere; 4] {
+    let world = [
+        sphere(vec3(0.0, -1000.0, 0.0), 1000.0, MaterialKind::Lambertian, 0),
+        sphere(vec3(-2.2, 1.0, 0.0), 1.0, MaterialKind::Lambertian, 1),
+        sphere(vec3(0.0, 1.0, 0.0), 1.0, MaterialKind::Dielectric, 0),
+        sphere(vec3(2.2, 1.0, 0.0), 1.0, MaterialKind::Metal, 0),
+    ];
+    if constants.animated != 0 {
+        return animation::world(constants.time, world);
+    }
+    world
+}
+
+fn materials(constants: &ShaderConstants) -> Materials<2, 1, 1> {
+    let materials = Materials {
+        lambertian: [
+            Lambertian::new(vec3(0.5, 0.5, 0.5)),
+            Lambertian::new(vec3(0.4, 0.2, 0.1)),
+        ],
+        metal: [Metal::new(vec3(0.7, 0.6, 0.5), 0.0)],
+        dielectric: [Dielectric::bk7()],
+    };
+    if constants.animated != 0 {
+        return animation::materials(constants.time, materials);
+    }
+    materials
+}
+
+/// Lamp above the balls, between the glass and the metal one.
+fn light() -> PointLight {
+    PointLight::new(vec3(1.1, 4.0, 1.5), Vec3::splat(150.0))
+}
+
+fn sphere(center: Vec3, radius: f32, kind: MaterialKind, index: usize) -> Sphere {
+    Sphere {
+        center,
+        radius,
+        material: MaterialInfo { kind, index },
+    }
+}
@@ -0,0 +1,190 @@
+//! Kernels of the wavefront path tracer.
+//!
+//! Each kernel handles one stage of every live path and hands the paths over to the next stage
+//! through queues of path indices, whose lengths are kept in `counters`. The host runs `advance`
+//! between bounces to turn the paths pushed during a bounce into the queue of the next one.
+
+#[cfg(not(target_arch = "spirv"))]
+use spirv_std::macros::spirv;
+use shared::glam::{vec2, UVec2, UVec3, Vec3, Vec3Swizzles, Vec4};
+use shared::wavefront::{
+    PathState, ShadowRay, MATERIAL_COUNT, NEXT_RAY_COUNT, RAY_COUNT, SHADOW_COUNT,
+};
+use shared::{color_sky, Hit, HitData, Material, MaterialKind, Ray, Rng, ShaderConstants};
+use spirv_std::arch::atomic_i_add;
+use spirv_std::memory::{Scope, Semantics};
+
+use crate::{camera, materials, world};
+
+const T_MIN: f32 = 0.001;
+
+/// Start one path per pixel from the camera.
+#[spirv(compute(threads(8, 8)))]
+pub fn wavefront_generate(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] paths: &mut [PathState],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] counters: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] ray_queue: &mut [u32],
+) {
+    let size = UVec2::from(constants.view_size_pixels);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+    let index = id.y * size.x + id.x;
+    let pixel = id.xy();
+
+    let mut rng = Rng::for_pixel(pixel, constants.rng_seed_offset as u32);
+    let jitter = vec2(rng.gen(), rng.gen());
+    let uv = (pixel.as_vec2() + jitter) / size.as_vec2();
+    let ray = camera(constants).ray(&mut rng, vec2(uv.x, 1.0 - uv.y));
+
+    let mut path = PathState {
+        pixel: index,
+        throughput: [1.0, 1.0, 1.0],
+        ..PathState::default()
+    };
+    path.set_ray(&ray);
+    path.set_rng(&rng);
+    paths[index as usize] = path;
+
+    let slot = push(&mut counters[NEXT_RAY_COUNT]);
+    ray_queue[slot] = index;
+}
+
+/// Find the closest hit of every path in the ray queue, queueing the ones that hit something for
+/// shading and gathering the sky for the ones that escape.
+#[spirv(compute(threads(64)))]
+pub fn wavefront_intersect(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] paths: &mut [PathState],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] counters: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] ray_queue: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] material_queue: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 6)] accumulation: &mut [Vec4],
+) {
+    if id.x >= counters[RAY_COUNT] {
+        return;
+    }
+    let index = ray_queue[id.x as usize];
+    let mut path = paths[index as usize];
+    let ray = path.ray();
+
+    let mut hit = HitData::default();
+    if world(constants).hit(&ray, T_MIN, core::f32::MAX, &mut hit) {
+        path.set_hit(&hit);
+        paths[index as usize] = path;
+        let slot = push(&mut counters[MATERIAL_COUNT]);
+        material_queue[slot] = index;
+    } else if path.after_diffuse == 0 {
+        let light = path.throughput() * color_sky(&ray);
+        accumulation[path.pixel as usize] += light.extend(0.0);
+    }
+}
+
+/// Scatter every path in the material queue.
+///
+/// Diffuse surfaces gather the sky directly through a shadow ray, so the path that continues from
+/// them only carries the light bounced off other surfaces.
+#[spirv(compute(threads(64)))]
+pub fn wavefront_shade(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] paths: &mut [PathState],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] shadow_rays: &mut [ShadowRay],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] counters: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] ray_queue: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] material_queue: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 5)] shadow_queue: &mut [u32],
+) {
+    if id.x >= counters[MATERIAL_COUNT] {
+        return;
+    }
+    let index = material_queue[id.x as usize];
+    let mut path = paths[index as usize];
+    if path.bounces >= constants.ray_bounce_limit {
+        return;
+    }
+
+    let materials = materials(constants);
+    let ray = path.ray();
+    let hit = path.hit();
+    let mut rng = path.rng();
+    let mut attenuation = Vec3::ZERO;
+    let mut scattered = Ray::new(Vec3::ZERO, Vec3::ONE); // placeholder to initialise.
+
+    match hit.material.kind {
+        MaterialKind::Lambertian => {
+            let lambertian = materials.lambertian[hit.material.index];
+            lambertian.scatter_ray(&hit, &mut rng, &mut attenuation, &mut scattered);
+            let contribution = path.throughput() * attenuation * color_sky(&scattered);
+            let slot = push(&mut counters[SHADOW_COUNT]);
+            shadow_rays[index as usize] = ShadowRay {
+                origin: scattered.origin().into(),
+                pixel: path.pixel,
+                direction: scattered.direction().into(),
+                contribution: contribution.into(),
+            };
+            shadow_queue[slot] = index;
+
+            lambertian.scatter_ray(&hit, &mut rng, &mut attenuation, &mut scattered);
+            path.after_diffuse = 1;
+        }
+        _ => {
+            if !materials.scatter(&ray, &hit, &mut rng, &mut attenuation, &mut scattered) {
+                return;
+            }
+            path.after_diffuse = 0;
+        }
+    }
+
+    path.throughput = (path.throughput() * attenuation).into();
+    path.bounces += 1;
+    path.set_ray(&scattered);
+    path.set_rng(&rng);
+    paths[index as usize] = path;
+
+    let slot = push(&mut counters[NEXT_RAY_COUNT]);
+    ray_queue[slot] = index;
+}
+
+/// Add the light of every shadow ray that reaches the sky.
+#[spirv(compute(threads(64)))]
+pub fn wavefront_shadow(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] shadow_rays: &mut [ShadowRay],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] counters: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 5)] shadow_queue: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 6)] accumulation: &mut [Vec4],
+) {
+    if id.x >= counters[SHADOW_COUNT] {
+        return;
+    }
+    let shadow_ray = shadow_rays[shadow_queue[id.x as usize] as usize];
+
+    let mut hit = HitData::default();
+    if !world(constants).hit(&shadow_ray.ray(), T_MIN, core::f32::MAX, &mut hit) {
+        accumulation[shadow_ray.pixel as usize] += Vec3::from(shadow_ray.contribution).extend(0.0);
+    }
+}
+
+/// Make the paths pushed during the last bounce the ray queue of the next one.
+#[spirv(compute(threads(1)))]
+pub fn wavefront_advance(
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] counters: &mut [u32],
+) {
+    counters[RAY_COUNT] = counters[NEXT_RAY_COUNT];
+    counters[NEXT_RAY_COUNT] = 0;
+    counters[MATERIAL_COUNT] = 0;
+    counters[SHADOW_COUNT] = 0;
+}
+
+/// Reserve a slot at the end of the queue whose length is `counter`.
+fn push(counter: &mut u32) -> usize {
+    let slot = unsafe {
+        atomic_i_add::<u32, { Scope::Device as u32 }, { Semantics::NONE.bits() }>(counter, 1)
+    };
+    slot as usize
+}
@@ -0,0 +1,17 @@
+//! `main_cs` run on the CPU through `shared::host`.
+
+use shared::host::dispatch;
+
+#[test]
+fn main_cs_multiplies_every_element_by_12() {
+    let mut data = Box::new([0u32; 65536]);
+    for (n, value) in data.iter_mut().enumerate() {
+        *value = n as u32;
+    }
+
+    dispatch([64, 1, 1], [65536 / 64, 1, 1], |id| simple_compute::main_cs(id, &mut data));
+
+    for (n, value) in data.iter().enumerate() {
+        assert_eq!(*value, n as u32 * 12);
+    }
+}
@@ -0,0 +1,82 @@
+//! Per pixel statistics used to stop sampling pixels once they are free of noise.
+
+use crate::ShaderConstants;
+use spirv_std::{glam::Vec3, num_traits::Float};
+
+/// Weights used to reduce a colour to its luminance.
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Running mean and variance of the samples taken for a single pixel, kept with Welford's
+/// algorithm so that they can be updated one sample at a time.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct PixelStats {
+    /// Mean colour of the samples so far.
+    pub mean: [f32; 3],
+    /// Number of samples taken.
+    pub count: f32,
+    /// Sum of the squared differences of the luminance of the samples from its mean.
+    pub luminance_m2: f32,
+    /// Non-zero once the pixel no longer needs samples.
+    pub converged: u32,
+}
+
+impl PixelStats {
+    pub fn mean(&self) -> Vec3 {
+        Vec3::from(self.mean)
+    }
+
+    /// Mean luminance of the samples, which is the luminance of their mean colour.
+    pub fn mean_luminance(&self) -> f32 {
+        luminance(self.mean())
+    }
+
+    /// Unbiased variance of the luminance of the samples.
+    pub fn luminance_variance(&self) -> f32 {
+        if self.count < 2.0 {
+            0.0
+        } else {
+            self.luminance_m2 / (self.count - 1.0)
+        }
+    }
+
+    /// Standard error of the mean luminance, relative to that luminance.
+    ///
+    /// The luminance is floored so that black pixels don't need infinitely many samples.
+    pub fn relative_error(&self) -> f32 {
+        let standard_error = (self.luminance_variance() / self.count).sqrt();
+        standard_error / self.mean_luminance().max(0.01)
+    }
+
+    pub fn is_converged(&self) -> bool {
+        self.converged != 0
+    }
+
+    /// Add a sample to the running statistics.
+    pub fn add(&mut self, sample: Vec3) {
+        let mean_luminance = self.mean_luminance();
+        self.count += 1.0;
+        let mean = self.mean() + (sample - self.mean()) / self.count;
+        self.mean = [mean.x, mean.y, mean.z];
+        // The luminance is linear in the colour, so its mean is kept along with the colour.
+        let sample_luminance = luminance(sample);
+        self.luminance_m2 += (sample_luminance - mean_luminance) * (sample_luminance - luminance(mean));
+    }
+
+    /// Mark the pixel as converged once it has enough samples according to `constants`.
+    pub fn update_converged(&mut self, constants: &ShaderConstants) {
+        let max_samples = constants.max_samples_per_pixel;
+        let reached_max = max_samples != 0 && self.count >= max_samples as f32;
+        let below_noise = constants.noise_threshold > 0.0
+            && self.count >= constants.min_samples_per_pixel as f32
+            && self.relative_error() < constants.noise_threshold;
+        if reached_max || below_noise {
+            self.converged = 1;
+        }
+    }
+}
+
+fn luminance(color: Vec3) -> f32 {
+    color.dot(Vec3::from(LUMINANCE))
+}
\ No newline at end of file
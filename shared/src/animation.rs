@@ -0,0 +1,136 @@
+//! Keyframe tracks used to animate scenes over `ShaderConstants::time`.
+
+use core::ops::{Add, Mul, Sub};
+use crate::Sphere;
+use spirv_std::glam::Vec3;
+
+/// How a track moves from one keyframe to the next.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub enum Interpolation {
+    /// Straight line between the two keyframes.
+    Linear,
+    /// Cubic Bézier curve through the handles of the two keyframes.
+    Bezier,
+    /// Smooth curve through all keyframes, with tangents taken from the neighbouring keyframes.
+    CatmullRom,
+}
+
+/// Values that can be animated.
+pub trait Animatable: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self> {}
+
+impl<T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>> Animatable for T {}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Keyframe<T> {
+    /// Time of the keyframe, in seconds.
+    pub time: f32,
+    pub value: T,
+    /// Bézier control point before the keyframe.
+    pub in_handle: T,
+    /// Bézier control point after the keyframe.
+    pub out_handle: T,
+}
+
+/// Keyframes sorted by time. Before the first and after the last keyframe the track holds its
+/// first and last value.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Track<T, const N: usize> {
+    pub keys: [Keyframe<T>; N],
+    pub interpolation: Interpolation,
+}
+
+impl<T: Animatable> Keyframe<T> {
+    /// A keyframe whose handles sit on its value, so Bézier segments ease in and out of it.
+    pub fn new(time: f32, value: T) -> Self {
+        Self::bezier(time, value, value, value)
+    }
+
+    pub fn bezier(time: f32, value: T, in_handle: T, out_handle: T) -> Self {
+        Self {
+            time,
+            value,
+            in_handle,
+            out_handle,
+        }
+    }
+}
+
+impl<T: Animatable, const N: usize> Track<T, N> {
+    pub fn new(interpolation: Interpolation, keys: [Keyframe<T>; N]) -> Self {
+        Self { keys, interpolation }
+    }
+
+    /// Value of the track at `time`.
+    pub fn sample(&self, time: f32) -> T {
+        if N == 1 || time <= self.keys[0].time {
+            return self.keys[0].value;
+        }
+        if time >= self.keys[N - 1].time {
+            return self.keys[N - 1].value;
+        }
+
+        let mut i = 0;
+        while i + 2 < N && time >= self.keys[i + 1].time {
+            i += 1;
+        }
+        let k1 = self.keys[i];
+        let k2 = self.keys[i + 1];
+        let t = (time - k1.time) / (k2.time - k1.time);
+
+        match self.interpolation {
+            Interpolation::Linear => lerp(k1.value, k2.value, t),
+            Interpolation::Bezier => bezier(k1.value, k1.out_handle, k2.in_handle, k2.value, t),
+            Interpolation::CatmullRom => {
+                let k0 = if i > 0 { self.keys[i - 1] } else { k1 };
+                let k3 = if i + 2 < N { self.keys[i + 2] } else { k2 };
+                catmull_rom(k0.value, k1.value, k2.value, k3.value, t)
+            }
+        }
+    }
+}
+
+/// Position and size of an object at some time.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub scale: f32,
+}
+
+impl Transform {
+    pub fn apply(&self, point: Vec3) -> Vec3 {
+        point * self.scale + self.translation
+    }
+
+    pub fn apply_to_sphere(&self, sphere: Sphere) -> Sphere {
+        Sphere {
+            center: self.apply(sphere.center),
+            radius: sphere.radius * self.scale,
+            material: sphere.material,
+        }
+    }
+}
+
+pub fn lerp<T: Animatable>(a: T, b: T, t: f32) -> T {
+    a + (b - a) * t
+}
+
+/// Cubic Bézier curve from `p0` to `p3` with control points `p1` and `p2`.
+pub fn bezier<T: Animatable>(p0: T, p1: T, p2: T, p3: T, t: f32) -> T {
+    let s = 1.0 - t;
+    p0 * (s * s * s) + p1 * (3.0 * s * s * t) + p2 * (3.0 * s * t * t) + p3 * (t * t * t)
+}
+
+/// Uniform Catmull-Rom spline from `p1` to `p2`.
+pub fn catmull_rom<T: Animatable>(p0: T, p1: T, p2: T, p3: T, t: f32) -> T {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
@@ -0,0 +1,283 @@
+//! Constructive solid geometry over closed analytic shapes.
+//!
+//! Unlike [`Hit`], which only reports the closest surface, CSG needs to know where a ray enters and
+//! leaves every shape. [`HitInterval`] provides exactly that, one interval at a time.
+
+use crate::{Hit, HitData, MaterialInfo, Ray, Sphere};
+use spirv_std::{glam::Vec3, num_traits::Float};
+
+/// Upper bound on the number of intervals visited while combining two solids.
+const MAX_CSG_STEPS: u32 = 16;
+
+/// Closed shapes that may be crossed by a ray.
+pub trait HitInterval {
+    /// The first interval along the ray during which it is inside the shape and that ends after
+    /// `t_min`.
+    ///
+    /// The interval may start before `t_min` when the ray is already inside the shape there. Walk
+    /// all intervals by calling this again with `t_min` set to the previous `exit.t`.
+    fn hit_interval(self, r: &Ray, t_min: f32, interval: &mut Interval) -> bool;
+}
+
+/// The part of a ray that lies inside a solid.
+///
+/// Normals of both ends point out of the solid.
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct Interval {
+    pub enter: HitData,
+    pub exit: HitData,
+}
+
+/// Axis aligned box.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Cuboid {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub material: MaterialInfo,
+}
+
+/// Space inside either `a` or `b`.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Union<A, B> {
+    pub a: A,
+    pub b: B,
+}
+
+/// Space inside both `a` and `b`.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Intersection<A, B> {
+    pub a: A,
+    pub b: B,
+}
+
+/// Space inside `a` but not inside `b`.
+///
+/// Surfaces carved by `b` keep its material.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Difference<A, B> {
+    pub a: A,
+    pub b: B,
+}
+
+/// Wraps a solid so that it can be placed in a world next to other objects.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Csg<S> {
+    pub solid: S,
+}
+
+impl Cuboid {
+    pub fn new(min: Vec3, max: Vec3, material: MaterialInfo) -> Self {
+        Self { min, max, material }
+    }
+}
+
+impl Interval {
+    /// Which of the two ends of the interval is the closest one inside `[t_min, t_max]`.
+    pub fn closest(&self, t_min: f32, t_max: f32, hit: &mut HitData) -> bool {
+        if self.enter.t > t_min && self.enter.t < t_max {
+            *hit = self.enter;
+            true
+        } else if self.exit.t > t_min && self.exit.t < t_max {
+            *hit = self.exit;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl HitInterval for Sphere {
+    fn hit_interval(self, r: &Ray, t_min: f32, interval: &mut Interval) -> bool {
+        let Sphere { center, radius, material } = self;
+        let oc = r.origin() - center;
+        let direction = r.direction();
+        let a = direction.dot(direction);
+        let b = oc.dot(direction);
+        let c = oc.dot(oc) - radius * radius;
+        let discriminant = b * b - a * c;
+        if discriminant <= 0.0 {
+            return false;
+        }
+        let exit_t = (-b + discriminant.sqrt()) / a;
+        if exit_t <= t_min {
+            return false;
+        }
+        let enter_t = (-b - discriminant.sqrt()) / a;
+        interval.enter = sphere_hit_data(r, enter_t, center, radius, material);
+        interval.exit = sphere_hit_data(r, exit_t, center, radius, material);
+        true
+    }
+}
+
+impl HitInterval for Cuboid {
+    fn hit_interval(self, r: &Ray, t_min: f32, interval: &mut Interval) -> bool {
+        let origin = r.origin();
+        let direction = r.direction();
+        let inv_direction = Vec3::ONE / direction;
+        let t0 = (self.min - origin) * inv_direction;
+        let t1 = (self.max - origin) * inv_direction;
+        let near = t0.min(t1);
+        let far = t0.max(t1);
+        let enter_t = near.max_element();
+        let exit_t = far.min_element();
+        if enter_t >= exit_t || exit_t <= t_min {
+            return false;
+        }
+        interval.enter = HitData {
+            t: enter_t,
+            p: r.point_at_parameter(enter_t),
+            normal: slab_normal(near, enter_t, direction, -1.0),
+            material: self.material,
+        };
+        interval.exit = HitData {
+            t: exit_t,
+            p: r.point_at_parameter(exit_t),
+            normal: slab_normal(far, exit_t, direction, 1.0),
+            material: self.material,
+        };
+        true
+    }
+}
+
+impl<A: Copy + HitInterval, B: Copy + HitInterval> HitInterval for Union<A, B> {
+    fn hit_interval(self, r: &Ray, t_min: f32, interval: &mut Interval) -> bool {
+        let mut a = Interval::default();
+        let mut b = Interval::default();
+        let hit_a = self.a.hit_interval(r, t_min, &mut a);
+        let hit_b = self.b.hit_interval(r, t_min, &mut b);
+        *interval = match (hit_a, hit_b) {
+            (false, false) => return false,
+            (true, false) => a,
+            (false, true) => b,
+            (true, true) if a.enter.t <= b.enter.t => a,
+            (true, true) => b,
+        };
+        // Keep growing the interval while either operand has one overlapping its end.
+        let mut step = 0;
+        while step < MAX_CSG_STEPS {
+            let mut grown = false;
+            let end = interval.exit.t;
+            if self.a.hit_interval(r, end, &mut a) && a.enter.t <= end {
+                interval.exit = a.exit;
+                grown = true;
+            }
+            let end = interval.exit.t;
+            if self.b.hit_interval(r, end, &mut b) && b.enter.t <= end {
+                interval.exit = b.exit;
+                grown = true;
+            }
+            if !grown {
+                break;
+            }
+            step += 1;
+        }
+        true
+    }
+}
+
+impl<A: Copy + HitInterval, B: Copy + HitInterval> HitInterval for Intersection<A, B> {
+    fn hit_interval(self, r: &Ray, t_min: f32, interval: &mut Interval) -> bool {
+        let mut a = Interval::default();
+        let mut b = Interval::default();
+        let mut t = t_min;
+        let mut step = 0;
+        while step < MAX_CSG_STEPS {
+            if !self.a.hit_interval(r, t, &mut a) || !self.b.hit_interval(r, t, &mut b) {
+                return false;
+            }
+            interval.enter = if a.enter.t > b.enter.t { a.enter } else { b.enter };
+            interval.exit = if a.exit.t < b.exit.t { a.exit } else { b.exit };
+            if interval.enter.t < interval.exit.t {
+                return true;
+            }
+            // No overlap, so the interval ending first can't overlap anything further along.
+            t = interval.exit.t;
+            step += 1;
+        }
+        false
+    }
+}
+
+impl<A: Copy + HitInterval, B: Copy + HitInterval> HitInterval for Difference<A, B> {
+    fn hit_interval(self, r: &Ray, t_min: f32, interval: &mut Interval) -> bool {
+        let mut b = Interval::default();
+        let mut t = t_min;
+        let mut have_a = false;
+        let mut step = 0;
+        while step < MAX_CSG_STEPS {
+            if !have_a {
+                if !self.a.hit_interval(r, t, interval) {
+                    return false;
+                }
+                have_a = true;
+            }
+            let start = interval.enter.t.max(t);
+            if !self.b.hit_interval(r, start, &mut b) || b.enter.t >= interval.exit.t {
+                return true;
+            }
+            if b.enter.t > start {
+                // `b` cuts the interval short.
+                interval.exit = flipped(b.enter);
+                return true;
+            }
+            if b.exit.t >= interval.exit.t {
+                // `b` swallows the whole interval, move on to the next one of `a`.
+                t = interval.exit.t;
+                have_a = false;
+            } else {
+                interval.enter = flipped(b.exit);
+            }
+            step += 1;
+        }
+        false
+    }
+}
+
+impl<S: Copy + HitInterval> Hit for Csg<S> {
+    fn hit(self, r: &Ray, t_min: f32, t_max: f32, hit: &mut HitData) -> bool {
+        let mut interval = Interval::default();
+        self.solid.hit_interval(r, t_min, &mut interval) && interval.closest(t_min, t_max, hit)
+    }
+}
+
+impl Hit for Cuboid {
+    fn hit(self, r: &Ray, t_min: f32, t_max: f32, hit: &mut HitData) -> bool {
+        let mut interval = Interval::default();
+        self.hit_interval(r, t_min, &mut interval) && interval.closest(t_min, t_max, hit)
+    }
+}
+
+fn sphere_hit_data(r: &Ray, t: f32, center: Vec3, radius: f32, material: MaterialInfo) -> HitData {
+    let p = r.point_at_parameter(t);
+    HitData {
+        t,
+        p,
+        normal: (p - center) / radius,
+        material,
+    }
+}
+
+/// Normal of the box face crossed at `t`, `sign` being -1 when entering and 1 when leaving.
+fn slab_normal(slab_t: Vec3, t: f32, direction: Vec3, sign: f32) -> Vec3 {
+    let mut normal = Vec3::ZERO;
+    if slab_t.x == t {
+        normal.x = sign * direction.x.signum();
+    } else if slab_t.y == t {
+        normal.y = sign * direction.y.signum();
+    } else {
+        normal.z = sign * direction.z.signum();
+    }
+    normal
+}
+
+/// The surface of a carved out solid, seen from the other side.
+fn flipped(mut hit: HitData) -> HitData {
+    hit.normal = -hit.normal;
+    hit
+}
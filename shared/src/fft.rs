@@ -0,0 +1,47 @@
+//! Parameters of the passes of the Stockham FFT.
+//!
+//! A transform of length `n` takes one pass per factor of `n`, radix 4 while it divides what is
+//! left and radix 2 for the last factor otherwise. Every pass reads one buffer and writes the
+//! other, the results landing in order so that no bit reversal is needed.
+
+/// Invocations per workgroup of the FFT kernel, which must match its `threads(...)`.
+pub const WORKGROUP_SIZE: u32 = 64;
+
+/// One pass over `batches` transforms of length `n`.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct FftConstants {
+    pub n: u32,
+    /// 2 or 4.
+    pub radix: u32,
+    /// Length of the transforms combined by this pass, the product of the radices of the passes
+    /// before it.
+    pub span: u32,
+    pub batches: u32,
+    /// Elements between consecutive elements of a transform, 1 along rows and the width along
+    /// columns.
+    pub element_stride: u32,
+    /// Elements between the first elements of consecutive transforms.
+    pub batch_stride: u32,
+    /// Non-zero for the inverse transform.
+    pub inverse: u32,
+    /// Every output is multiplied by it, `1 / n` in the last pass of an inverse transform.
+    pub scale: f32,
+}
+
+impl FftConstants {
+    /// Index in the buffers of element `i` of transform `batch`.
+    pub fn index(&self, batch: u32, i: u32) -> usize {
+        (batch * self.batch_stride + i * self.element_stride) as usize
+    }
+
+    /// Sign of the exponent of the twiddle factors, negative for the forward transform.
+    pub fn sign(&self) -> f32 {
+        if self.inverse != 0 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,234 @@
+//! Parameters of the fractal kernel, and the formulas it iterates.
+
+use spirv_std::glam::{vec2, Vec2, Vec3};
+use spirv_std::num_traits::Float;
+
+// Formulas selected by `FractalConstants::formula`.
+/// `z = z^2 + c` from `z = 0`, with `c` the point of the pixel.
+pub const MANDELBROT: u32 = 0;
+/// `z = z^2 + c` from the point of the pixel, with `c` the constant `julia_c`.
+pub const JULIA: u32 = 1;
+/// `z = (|re z| + i |im z|)^2 + c`.
+pub const BURNING_SHIP: u32 = 2;
+/// `z = z^d + c`, with `d` the `exponent`.
+pub const MULTIBROT: u32 = 3;
+/// `z = conj(z)^2 + c`.
+pub const TRICORN: u32 = 4;
+/// Newton's method on the polynomial with the given `roots`, coloured by the root each point
+/// converges to.
+pub const NEWTON: u32 = 5;
+
+/// Roots the polynomial of a Newton fractal has at most.
+pub const MAX_ROOTS: usize = 4;
+
+// Shadings of the escape time formulas, selected by `FractalConstants::shading`.
+/// Iterations taken to escape, interpolated between whole iterations so that it has no bands.
+pub const SMOOTH: u32 = 0;
+/// Closest the orbit came to the axes.
+pub const ORBIT_TRAP: u32 = 1;
+/// Estimated distance to the set, in pixels, which outlines its finest filaments.
+pub const DISTANCE: u32 = 2;
+
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct FractalConstants {
+    /// Size of the whole image in pixels.
+    pub image_size: [u32; 2],
+    /// Position of the rendered tile within the whole image, in pixels.
+    pub tile_offset: [u32; 2],
+    /// Point of the complex plane at the centre of the image.
+    pub center: [f32; 2],
+    /// Magnification, at 1 the image is 2 units of the complex plane tall.
+    pub zoom: f32,
+    pub max_iterations: u32,
+    /// Distance from the origin past which a point is known to escape.
+    pub escape_radius: f32,
+
+    /// One of `MANDELBROT`, `JULIA`, `BURNING_SHIP`, `MULTIBROT`, `TRICORN` or `NEWTON`.
+    pub formula: u32,
+    /// Constant added at every step of a Julia set.
+    pub julia_c: [f32; 2],
+    /// Power of a Multibrot set.
+    pub exponent: f32,
+    /// Number of `roots` in use by a Newton fractal.
+    pub root_count: u32,
+    pub roots: [[f32; 2]; MAX_ROOTS],
+
+    /// One of `SMOOTH`, `ORBIT_TRAP` or `DISTANCE`.
+    pub shading: u32,
+    /// Times the palette repeats from zero to `max_iterations`, or over the range of the orbit
+    /// trap.
+    pub palette_cycles: f32,
+    /// Every pixel averages a grid of this many samples squared, zero or one for a single
+    /// sample at its centre.
+    pub samples_per_side: u32,
+}
+
+/// Colour a gradient takes at `position`, from 0 to 1.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct GradientStop {
+    pub position: f32,
+    pub color: [f32; 3],
+}
+
+impl FractalConstants {
+    /// Point of the complex plane under `pixel`, measured from the top left of the whole image.
+    pub fn pixel_to_complex(&self, pixel: Vec2) -> Vec2 {
+        let size = vec2(self.image_size[0] as f32, self.image_size[1] as f32);
+        let offset = (pixel - size * 0.5) * (2.0 / (self.zoom * size.y));
+        // The imaginary axis points up while pixel rows go down.
+        Vec2::from(self.center) + vec2(offset.x, -offset.y)
+    }
+
+    /// Starting point and constant of the escape time formulas for the point `p` of a pixel.
+    pub fn start(&self, p: Vec2) -> (Vec2, Vec2) {
+        if self.formula == JULIA {
+            (p, Vec2::from(self.julia_c))
+        } else {
+            (Vec2::ZERO, p)
+        }
+    }
+
+    /// One step of the escape time formula.
+    pub fn step(&self, z: Vec2, c: Vec2) -> Vec2 {
+        match self.formula {
+            BURNING_SHIP => complex_mul(z.abs(), z.abs()) + c,
+            MULTIBROT => complex_pow(z, self.exponent) + c,
+            TRICORN => {
+                let conjugate = vec2(z.x, -z.y);
+                complex_mul(conjugate, conjugate) + c
+            }
+            _ => complex_mul(z, z) + c,
+        }
+    }
+
+    /// Derivative with respect to `c` of `step(z, c)`, from the derivative `dz` of `z`, for the
+    /// distance estimate. The Burning Ship and the Tricorn use that of the Mandelbrot set, which
+    /// has the same magnitude.
+    pub fn step_derivative(&self, z: Vec2, dz: Vec2) -> Vec2 {
+        let dz = if self.formula == MULTIBROT {
+            self.exponent * complex_mul(complex_pow(z, self.exponent - 1.0), dz)
+        } else {
+            2.0 * complex_mul(z, dz)
+        };
+        // The constant of a Julia set does not depend on the pixel.
+        if self.formula == JULIA {
+            dz
+        } else {
+            dz + vec2(1.0, 0.0)
+        }
+    }
+
+    /// Iteration count of a point that escaped to `z` after `iteration` iterations, continuous
+    /// across the bands of whole iterations.
+    pub fn smooth_iteration(&self, iteration: u32, z: Vec2) -> f32 {
+        let degree = if self.formula == MULTIBROT { self.exponent } else { 2.0 };
+        iteration as f32 + 1.0 - z.length().ln().ln() / degree.ln()
+    }
+
+    /// Width of a pixel in the complex plane.
+    pub fn pixel_size(&self) -> f32 {
+        2.0 / (self.zoom * self.image_size[1] as f32)
+    }
+
+    /// One step of Newton's method on the polynomial with the given roots.
+    pub fn newton_step(&self, z: Vec2) -> Vec2 {
+        // p'(z) / p(z) is the sum of 1 / (z - root) over the roots.
+        let mut sum = Vec2::ZERO;
+        let mut i = 0;
+        while i < self.root_count as usize {
+            sum += complex_inverse(z - Vec2::from(self.roots[i]));
+            i += 1;
+        }
+        z - complex_inverse(sum)
+    }
+
+    /// Index of the root `z` converged to, or `root_count` if it is close to none of them.
+    pub fn nearest_root(&self, z: Vec2) -> u32 {
+        let mut i = 0;
+        while i < self.root_count as usize {
+            if (z - Vec2::from(self.roots[i])).length_squared() < 1e-6 {
+                return i as u32;
+            }
+            i += 1;
+        }
+        self.root_count
+    }
+}
+
+/// Parameters of the deep zoom kernel, which iterates every pixel as a small offset from a
+/// reference orbit computed on the host at high precision.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct PerturbationConstants {
+    /// The view is given by the pixel size below instead of `center` and `zoom`, and the whole
+    /// image is rendered at once with one sample per pixel, ignoring `tile_offset` and
+    /// `samples_per_side`.
+    pub fractal: FractalConstants,
+    /// Position within the image of the point the reference orbit starts from, in pixels.
+    pub reference_pixel: [f32; 2],
+    /// Number of points in the reference orbit, fewer than `max_iterations + 1` if it escaped.
+    pub reference_len: u32,
+    /// Width of a pixel in the complex plane is `pixel_size_mantissa * 2^pixel_size_exponent`,
+    /// as it is far too small for `f32` past zooms of about 1e38.
+    pub pixel_size_mantissa: f32,
+    pub pixel_size_exponent: i32,
+    /// Non-zero to only render the pixels marked as glitched again.
+    pub glitched_only: u32,
+}
+
+/// Colour at `t` of the gradient through `stops`, sorted by position. Outside of the stops the
+/// gradient keeps the colour of the first or last.
+pub fn gradient(stops: &[GradientStop], t: f32) -> Vec3 {
+    let mut i = 0;
+    while i + 1 < stops.len() && t > stops[i + 1].position {
+        i += 1;
+    }
+    let a = stops[i];
+    if i + 1 == stops.len() || t <= a.position {
+        return Vec3::from(a.color);
+    }
+    let b = stops[i + 1];
+    let s = (t - a.position) / (b.position - a.position);
+    Vec3::from(a.color).lerp(Vec3::from(b.color), s)
+}
+
+/// Product of two complex numbers.
+pub fn complex_mul(a: Vec2, b: Vec2) -> Vec2 {
+    vec2(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x)
+}
+
+/// `1 / z`.
+pub fn complex_inverse(z: Vec2) -> Vec2 {
+    vec2(z.x, -z.y) / z.length_squared()
+}
+
+/// `z^d` on the principal branch.
+pub fn complex_pow(z: Vec2, d: f32) -> Vec2 {
+    let angle = z.y.atan2(z.x) * d;
+    z.length().powf(d) * vec2(angle.cos(), angle.sin())
+}
+
+/// `x * 2^exponent`, for exponents beyond the range of `f32`.
+pub fn ldexp(x: f32, exponent: i32) -> f32 {
+    let mut x = x;
+    let mut exponent = exponent;
+    while exponent < -126 {
+        x *= exp2i(-126);
+        exponent += 126;
+    }
+    while exponent > 127 {
+        x *= exp2i(127);
+        exponent -= 127;
+    }
+    x * exp2i(exponent)
+}
+
+/// `2^exponent`, for exponents from -126 to 127.
+fn exp2i(exponent: i32) -> f32 {
+    f32::from_bits(((exponent + 127) as u32) << 23)
+}
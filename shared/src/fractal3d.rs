@@ -0,0 +1,138 @@
+//! Distance estimators of 3D fractals, and the parameters of the kernel that sphere traces them.
+
+use crate::sdf::{Sdf, SdfBox};
+use spirv_std::glam::{vec3, Vec3};
+use spirv_std::num_traits::Float;
+
+// Fractals selected by `Fractal3dConstants::fractal`.
+pub const MANDELBULB: u32 = 0;
+pub const MANDELBOX: u32 = 1;
+pub const MENGER_SPONGE: u32 = 2;
+
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct Fractal3dConstants {
+    pub image_size: [u32; 2],
+    /// Every pixel averages a grid of this many samples squared, zero or one for a single
+    /// sample at its centre.
+    pub samples_per_side: u32,
+    /// One of `MANDELBULB`, `MANDELBOX` or `MENGER_SPONGE`.
+    pub fractal: u32,
+    /// Iterations of the distance estimator, more show finer details.
+    pub iterations: u32,
+    /// Power of the Mandelbulb.
+    pub power: f32,
+    /// Scale of the Mandelbox.
+    pub scale: f32,
+
+    // Camera
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+    /// Vertical field of view in radians.
+    pub vfov: f32,
+
+    // Sphere tracing
+    /// Steps a ray takes at most before it is considered to miss.
+    pub max_steps: u32,
+    /// Distance to the surface under which a ray hits it, per unit of distance travelled, so
+    /// that details shrink with the distance like pixels do.
+    pub epsilon: f32,
+
+    // Lighting
+    /// Direction towards the light, which is infinitely far away.
+    pub light_direction: [f32; 3],
+    /// How quickly shadows go from lit to dark at their edges, higher is sharper.
+    pub shadow_sharpness: f32,
+}
+
+/// `z = z^power + p` in spherical coordinates.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Mandelbulb {
+    pub power: f32,
+    pub iterations: u32,
+}
+
+/// Folds space into a box and a sphere, then scales it around `p`.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Mandelbox {
+    pub scale: f32,
+    pub iterations: u32,
+}
+
+/// Cube with a cross carved out of it at every scale.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct MengerSponge {
+    pub iterations: u32,
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(self, p: Vec3) -> f32 {
+        let mut z = p;
+        // Derivative of the orbit with respect to `p`.
+        let mut dr = 1.0;
+        let mut r = z.length();
+        let mut i = 0;
+        while i < self.iterations && r < 2.0 {
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            z = r.powf(self.power) * vec3(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()) + p;
+            r = z.length();
+            i += 1;
+        }
+        0.5 * r.ln() * r / dr
+    }
+}
+
+impl Sdf for Mandelbox {
+    fn distance(self, p: Vec3) -> f32 {
+        const MIN_RADIUS_SQUARED: f32 = 0.25;
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut i = 0;
+        while i < self.iterations {
+            // Fold the box.
+            z = z.clamp(Vec3::splat(-1.0), Vec3::ONE) * 2.0 - z;
+            // Fold the sphere.
+            let r2 = z.length_squared();
+            if r2 < MIN_RADIUS_SQUARED {
+                z /= MIN_RADIUS_SQUARED;
+                dr /= MIN_RADIUS_SQUARED;
+            } else if r2 < 1.0 {
+                z /= r2;
+                dr /= r2;
+            }
+            z = z * self.scale + p;
+            dr = dr * self.scale.abs() + 1.0;
+            i += 1;
+        }
+        z.length() / dr.abs()
+    }
+}
+
+impl Sdf for MengerSponge {
+    fn distance(self, p: Vec3) -> f32 {
+        let mut d = SdfBox {
+            center: Vec3::ZERO,
+            half_extents: Vec3::ONE,
+        }
+            .distance(p);
+        let mut s = 1.0;
+        let mut i = 0;
+        while i < self.iterations {
+            // Position within the cell of the current scale, from -1 to 1.
+            let q = p * s;
+            let a = q - 2.0 * (q * 0.5).floor() - Vec3::ONE;
+            s *= 3.0;
+            let r = (Vec3::ONE - 3.0 * a.abs()).abs();
+            let cross = r.x.max(r.y).min(r.y.max(r.z)).min(r.z.max(r.x));
+            d = d.max((cross - 1.0) / s);
+            i += 1;
+        }
+        d
+    }
+}
\ No newline at end of file
@@ -0,0 +1,25 @@
+//! Parameters of the matrix multiplication kernel.
+
+/// Rows of `C` computed by a workgroup.
+pub const TILE_M: u32 = 64;
+/// Columns of `C` computed by a workgroup.
+pub const TILE_N: u32 = 64;
+/// Columns of `A` and rows of `B` held in workgroup memory at a time.
+pub const TILE_K: u32 = 16;
+/// Invocations of a workgroup along the columns and the rows of its tile.
+pub const THREADS_N: u32 = 16;
+pub const THREADS_M: u32 = 16;
+/// Rows and columns of the tile every invocation computes in its registers, spread
+/// `THREADS_M` rows and `THREADS_N` columns apart.
+pub const BLOCK_M: u32 = TILE_M / THREADS_M;
+pub const BLOCK_N: u32 = TILE_N / THREADS_N;
+
+/// `C = A B` for the row major matrices `A` of `m` by `k` and `B` of `k` by `n`.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct GemmConstants {
+    pub m: u32,
+    pub n: u32,
+    pub k: u32,
+}
\ No newline at end of file
@@ -0,0 +1,74 @@
+//! Runs the compute kernels on the CPU, so that their logic can be tested and stepped through in
+//! a debugger on machines without a GPU.
+//!
+//! The shader crates are ordinary Rust outside of `target_arch = "spirv"`: push constants are
+//! references, storage buffers are slices, and their storage images are the `Image2d` below.
+//! Kernels using atomics, barriers, workgroup memory or other GPU only intrinsics still only run
+//! on the GPU.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use spirv_std::glam::{UVec2, UVec3, Vec4};
+use spirv_std::num_traits::Float;
+
+/// Stand-in for a 2D storage image, holding the texels as written.
+pub struct Image2d {
+    size: UVec2,
+    texels: Vec<Vec4>,
+}
+
+impl Image2d {
+    /// Image of `width` by `height` texels, all zero.
+    pub fn new([width, height]: [u32; 2]) -> Self {
+        Self {
+            size: UVec2::new(width, height),
+            texels: vec![Vec4::ZERO; (width * height) as usize],
+        }
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.size.into()
+    }
+
+    /// Same as `spirv_std::Image::write`, except that writing past the edges panics instead of
+    /// being dropped, to catch kernels missing their bounds check.
+    ///
+    /// # Safety
+    ///
+    /// Always safe on the CPU, it is only unsafe to match the GPU version.
+    pub unsafe fn write(&mut self, coordinate: UVec2, texels: Vec4) {
+        assert!(
+            coordinate.x < self.size.x && coordinate.y < self.size.y,
+            "write to {} outside of a {} image",
+            coordinate,
+            self.size,
+        );
+        self.texels[(coordinate.y * self.size.x + coordinate.x) as usize] = texels;
+    }
+
+    pub fn read(&self, coordinate: UVec2) -> Vec4 {
+        self.texels[(coordinate.y * self.size.x + coordinate.x) as usize]
+    }
+
+    /// Texels row by row, converted to RGBA8 as `format=rgba8` stores them.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.texels
+            .iter()
+            .flat_map(|texel| texel.to_array())
+            .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect()
+    }
+}
+
+/// Call `kernel` with the `global_invocation_id` of every invocation of `group_counts`
+/// workgroups of `workgroup_size` invocations, one after the other.
+pub fn dispatch(workgroup_size: [u32; 3], group_counts: [u32; 3], mut kernel: impl FnMut(UVec3)) {
+    let size = UVec3::from(workgroup_size) * UVec3::from(group_counts);
+    for z in 0..size.z {
+        for y in 0..size.y {
+            for x in 0..size.x {
+                kernel(UVec3::new(x, y, z));
+            }
+        }
+    }
+}
\ No newline at end of file
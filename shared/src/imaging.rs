@@ -0,0 +1,93 @@
+//! Parameters and filters of the image processing kernels.
+
+use core::f32::consts::PI;
+use spirv_std::glam::{vec3, Vec3, Vec4};
+use spirv_std::num_traits::Float;
+
+/// Resize filters.
+pub const BILINEAR: u32 = 0;
+pub const LANCZOS: u32 = 1;
+
+/// Lobes of the Lanczos filter on either side of its centre.
+pub const LANCZOS_LOBES: f32 = 3.0;
+
+/// Parameters of all the image processing kernels, each using the fields it needs.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct ImagingConstants {
+    pub input_size: [u32; 2],
+    pub output_size: [u32; 2],
+
+    // Gaussian blur
+    /// Step between the texels of a pass, `[1, 0]` to blur along x and `[0, 1]` along y.
+    pub direction: [i32; 2],
+    pub sigma: f32,
+
+    // Convolution
+    /// Columns and rows of the weights, centred on the texel.
+    pub kernel_size: [u32; 2],
+
+    // Resize
+    /// `BILINEAR` or `LANCZOS`.
+    pub filter: u32,
+
+    // Colour adjustments
+    /// Added to every channel.
+    pub brightness: f32,
+    /// Scale of the distance of every channel to middle grey.
+    pub contrast: f32,
+    /// 0 for greys, 1 to keep the colours as they are.
+    pub saturation: f32,
+    /// Channels are raised to `1 / gamma`.
+    pub gamma: f32,
+}
+
+impl ImagingConstants {
+    /// Texels on either side of the centre of a blur pass, where the Gaussian has fallen under
+    /// about one percent of its peak.
+    pub fn blur_radius(&self) -> i32 {
+        (3.0 * self.sigma).ceil() as i32
+    }
+
+    /// Apply the brightness, contrast, saturation and gamma to `color`, leaving its alpha alone.
+    pub fn adjust(&self, color: Vec4) -> Vec4 {
+        let rgb = (color.truncate() - Vec3::splat(0.5)) * self.contrast + Vec3::splat(0.5 + self.brightness);
+        let grey = Vec3::splat(luminance(rgb));
+        let rgb = grey + (rgb - grey) * self.saturation;
+        let exponent = 1.0 / self.gamma;
+        let rgb = vec3(
+            rgb.x.max(0.0).powf(exponent),
+            rgb.y.max(0.0).powf(exponent),
+            rgb.z.max(0.0).powf(exponent),
+        );
+        rgb.extend(color.w)
+    }
+}
+
+/// Unnormalised Gaussian of standard deviation `sigma`.
+pub fn gaussian(x: f32, sigma: f32) -> f32 {
+    (-x * x / (2.0 * sigma * sigma)).exp()
+}
+
+/// Lanczos window of `LANCZOS_LOBES` lobes, `sinc(x) sinc(x / LANCZOS_LOBES)`.
+pub fn lanczos(x: f32) -> f32 {
+    if x.abs() >= LANCZOS_LOBES {
+        0.0
+    } else {
+        sinc(x) * sinc(x / LANCZOS_LOBES)
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Rec. 709 luminance of a colour.
+pub fn luminance(rgb: Vec3) -> f32 {
+    rgb.dot(vec3(0.2126, 0.7152, 0.0722))
+}
\ No newline at end of file
@@ -2,13 +2,34 @@
 
 #![no_std]
 
+#[cfg(not(target_arch = "spirv"))]
+extern crate alloc;
+
 use spirv_std::{
-    glam::{vec2, vec3, Vec2, Vec3},
+    glam::{vec2, vec3, UVec2, Vec2, Vec3},
     num_traits::Float,
 };
 
 pub use spirv_std::glam;
 
+pub mod adaptive;
+pub mod animation;
+pub mod csg;
+pub mod fft;
+pub mod fractal;
+pub mod fractal3d;
+pub mod gemm;
+#[cfg(not(target_arch = "spirv"))]
+pub mod host;
+pub mod imaging;
+pub mod life;
+pub mod nbody;
+pub mod photon;
+pub mod primitives;
+pub mod sdf;
+pub mod spectral;
+pub mod wavefront;
+
 /// Types that may be hit by a ray.
 pub trait Hit {
     /// Whether or not the Ray hits the object along with the associated hit data.
@@ -84,6 +105,9 @@ pub struct Dielectric {
     //     |
     //     = note: from: *struct Dielectric { ref_idx: f32 }
     //     = note: to: *u32
+    //
+    // `x` is the index of refraction at the d-line, `y` and `z` the `B` and `C` terms of Cauchy's
+    // equation giving its dispersion (see `spectral`).
     pub ref_idx: Vec3,
 }
 
@@ -95,6 +119,7 @@ pub struct Metal {
 }
 
 #[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
 #[repr(C)]
 pub struct ShaderConstants {
     pub view_size_pixels: [u32; 2],
@@ -105,10 +130,41 @@ pub struct ShaderConstants {
     // Rendering
     pub rays_per_pixel: u32,
     pub ray_bounce_limit: u32,
+    /// Non-zero to trace a single wavelength per ray, showing dispersion.
+    pub spectral: u32,
+
+    // Adaptive sampling (see `adaptive`)
+    /// Relative error under which a pixel stops taking samples, zero to never stop early.
+    pub noise_threshold: f32,
+    pub min_samples_per_pixel: u32,
+    /// Zero for no limit.
+    pub max_samples_per_pixel: u32,
+
+    // Tiles
+    /// Position of the rendered tile within the view, in pixels.
+    pub tile_offset_pixels: [u32; 2],
+    pub tile_size_pixels: [u32; 2],
+
+    // Photon mapping (see `photon`)
+    /// Non-zero to light the scene with a point light too, with caustics from a photon map.
+    pub photon_mapping: u32,
+    /// Photons emitted per pass.
+    pub photon_count: u32,
+    /// Distance from a hit within which photons are gathered.
+    pub photon_radius: f32,
+
+    // Animation (see `animation`)
+    /// Non-zero to take the camera, objects and materials from keyframe tracks at `time`.
+    pub animated: u32,
 
     // Camera
     pub vfov: f32,
     pub aperture: f32,
+    /// See `Lens`.
+    pub lens_distortion: [f32; 2],
+    /// Non-zero for cos⁴ vignetting.
+    pub vignetting: u32,
+    pub chromatic_aberration: f32,
     //pub focus_dist: f32,
 
     // TODO: This would be awesome for automatically improving scene quality when the camera
@@ -147,6 +203,21 @@ pub struct Camera {
     pub v: Vec3,
     pub w: Vec3,
     pub lens_radius: f32,
+    pub lens: Lens,
+}
+
+/// Imperfections of a real lens, to match renders to camera footage. All of them are off by
+/// default.
+#[derive(Copy, Clone, Default)]
+pub struct Lens {
+    /// Radial distortion coefficients k1 and k2 applied to ray generation, for radii where the
+    /// image corners are at 1. Positive values bend the rays at the edges outwards, giving
+    /// barrel distortion, negative ones give pincushion distortion.
+    pub distortion: Vec2,
+    /// Whether to darken the image by cos⁴ of the angle of rays off the axis.
+    pub vignetting: bool,
+    /// How much further from the centre red is imaged than green, and green than blue.
+    pub chromatic_aberration: f32,
 }
 
 impl Camera {
@@ -191,10 +262,23 @@ impl Camera {
             v,
             w,
             lens_radius,
+            lens: Lens::default(),
         }
     }
 
+    pub fn with_lens(self, lens: Lens) -> Self {
+        Self { lens, ..self }
+    }
+
     pub fn ray(&self, rng: &mut Rng, uv: Vec2) -> Ray {
+        self.ray_channel(rng, uv, 1)
+    }
+
+    /// Ray seen by the colour channel `channel` (0 for red, 1 for green, 2 for blue) at `uv`.
+    ///
+    /// Only differs between channels with chromatic aberration.
+    pub fn ray_channel(&self, rng: &mut Rng, uv: Vec2, channel: u32) -> Ray {
+        let uv = self.distort(uv, 1.0 - self.lens.chromatic_aberration * (1.0 - channel as f32));
         let rd = self.lens_radius * random_in_unit_disk(rng);
         let offset = self.u * rd.x + self.v * rd.y;
         Ray {
@@ -202,6 +286,27 @@ impl Camera {
             b: self.lower_left_corner + uv.x * self.horizontal + uv.y * self.vertical - self.origin - offset,
         }
     }
+
+    /// How much of the light along `ray` reaches the film.
+    pub fn vignetting(&self, ray: &Ray) -> f32 {
+        if !self.lens.vignetting {
+            return 1.0;
+        }
+        let cos = -unit_vector(ray.direction()).dot(self.w);
+        let cos2 = cos * cos;
+        cos2 * cos2
+    }
+
+    /// Move `uv` radially from the centre of the image according to the lens distortion, then
+    /// scale it by `scale`.
+    fn distort(&self, uv: Vec2, scale: f32) -> Vec2 {
+        let aspect = self.horizontal.length() / self.vertical.length();
+        let p = (2.0 * uv - Vec2::ONE) * vec2(aspect, 1.0);
+        let r2 = p.length_squared() / (aspect * aspect + 1.0);
+        let k = self.lens.distortion;
+        let p = p * (1.0 + k.x * r2 + k.y * r2 * r2) * scale;
+        (p / vec2(aspect, 1.0) + Vec2::ONE) * 0.5
+    }
 }
 
 impl Ray {
@@ -223,6 +328,17 @@ impl Ray {
 }
 
 impl Rng {
+    /// Generator for `pixel` in the pass `pass`, seeded from a hash of both so that no two
+    /// pixels or passes start from related seeds.
+    pub fn for_pixel(pixel: UVec2, pass: u32) -> Self {
+        let a = pcg_hash(pixel.x ^ pcg_hash(pixel.y ^ pcg_hash(pass)));
+        let b = pcg_hash(a);
+        // Within the ranges the seed wraps around in.
+        Self {
+            seed: vec2(unit_float(a) * 3718.0, unit_float(b) * 1739.0),
+        }
+    }
+
     pub fn gen_signed(&mut self) -> f32 {
         let res = (self.seed.dot(vec2(12.9898, 78.233)).sin() * 43758.5453).fract();
         self.seed = vec2(
@@ -237,6 +353,18 @@ impl Rng {
     }
 }
 
+/// The PCG hash of `input`, scrambling every bit of it into every bit of the result.
+fn pcg_hash(input: u32) -> u32 {
+    let state = input.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+/// `bits` mapped to [0, 1).
+fn unit_float(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1 << 24) as f32
+}
+
 impl Lambertian {
     pub fn new(albedo: Vec3) -> Self {
         Self { albedo }
@@ -263,7 +391,38 @@ impl Metal {
 
 impl Dielectric {
     pub fn new(ref_idx: f32) -> Self {
-        Self { ref_idx: Vec3::splat(ref_idx) }
+        Self { ref_idx: vec3(ref_idx, 0.0, 0.0) }
+    }
+
+    fn scatter_with_ior(
+        &self,
+        ray_in: &Ray,
+        hit: &HitData,
+        ref_idx: f32,
+        rng: &mut Rng,
+        ray_out: &mut Ray,
+    ) {
+        let ray_in_dir = ray_in.direction();
+        let reflected = reflect(ray_in_dir, hit.normal);
+        let ray_in_dir_dot_normal = ray_in_dir.dot(hit.normal);
+        let (outward_normal, ni_over_nt, cos) = if ray_in_dir_dot_normal > 0.0 {
+            let cos = ref_idx * ray_in_dir_dot_normal / ray_in_dir.length();
+            (-hit.normal, ref_idx, cos)
+        } else {
+            let cos = -ray_in_dir_dot_normal / ray_in_dir.length();
+            (hit.normal, 1.0 / ref_idx, cos)
+        };
+        let mut refracted = Vec3::ZERO;
+        let reflect_prob = if refract(ray_in.direction(), outward_normal, ni_over_nt, &mut refracted) {
+            schlick(cos, ref_idx)
+        } else {
+            1.0
+        };
+        if rng.gen() < reflect_prob {
+            *ray_out = Ray::new(hit.p, reflected);
+        } else {
+            *ray_out = Ray::new(hit.p, refracted);
+        }
     }
 }
 
@@ -283,6 +442,16 @@ impl<T: Copy + Hit, const N: usize> Hit for [T; N] {
     }
 }
 
+impl<A: Hit, B: Hit> Hit for (A, B) {
+    fn hit(self, r: &Ray, t_min: f32, t_max: f32, hit: &mut HitData) -> bool {
+        let (a, b) = self;
+        let hit_a = a.hit(r, t_min, t_max, hit);
+        let closest_t = if hit_a { hit.t } else { t_max };
+        let hit_b = b.hit(r, t_min, closest_t, hit);
+        hit_a || hit_b
+    }
+}
+
 impl Hit for Sphere {
     fn hit(self, r: &Ray, t_min: f32, t_max: f32, hit: &mut HitData) -> bool {
         (&self).hit(r, t_min, t_max, hit)
@@ -360,28 +529,8 @@ impl Material for Dielectric {
         attenuation: &mut Vec3,
         ray_out: &mut Ray,
     ) -> bool {
-        let ray_in_dir = ray_in.direction();
-        let reflected = reflect(ray_in_dir, hit.normal);
         *attenuation = Vec3::ONE;
-        let ray_in_dir_dot_normal = ray_in_dir.dot(hit.normal);
-        let (outward_normal, ni_over_nt, cos) = if ray_in_dir_dot_normal > 0.0 {
-            let cos = self.ref_idx.x * ray_in_dir_dot_normal / ray_in_dir.length();
-            (-hit.normal, self.ref_idx.x, cos)
-        } else {
-            let cos = -ray_in_dir_dot_normal / ray_in_dir.length();
-            (hit.normal, 1.0 / self.ref_idx.x, cos)
-        };
-        let mut refracted = Vec3::ZERO;
-        let reflect_prob = if refract(ray_in.direction(), outward_normal, ni_over_nt, &mut refracted) {
-            schlick(cos, self.ref_idx.x)
-        } else {
-            1.0
-        };
-        if rng.gen() < reflect_prob {
-            *ray_out = Ray::new(hit.p, reflected);
-        } else {
-            *ray_out = Ray::new(hit.p, refracted);
-        }
+        self.scatter_with_ior(ray_in, hit, self.ref_idx.x, rng, ray_out);
         true
     }
 }
@@ -502,7 +651,7 @@ pub fn color(
     sky * color
 }
 
-fn color_sky(ray: &Ray) -> Vec3 {
+pub fn color_sky(ray: &Ray) -> Vec3 {
     let unit_direction = unit_vector(ray.direction()) * 2.0;
     let t = 0.5 * (unit_direction.y + 1.0);
     (1.0 - t) * vec3(1.0, 1.0, 1.0) + t * vec3(0.5, 0.7, 1.0)
@@ -0,0 +1,58 @@
+//! Parameters of the outer totalistic cellular automata, such as Conway's Game of Life.
+//!
+//! Every cell is alive or dead, and its next state only depends on that and on how many of
+//! its eight neighbours are alive. The grid wraps around at its edges.
+
+use spirv_std::glam::{UVec2, Vec2};
+
+/// Pixels per cell from which the edges of the cells are drawn.
+pub const GRID_ZOOM: f32 = 8.0;
+
+/// Parameters of the step, paint and draw kernels, each using the fields it needs.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct LifeConstants {
+    /// Columns and rows of the grid.
+    pub size: [u32; 2],
+    /// Bit `n` is set when a dead cell with `n` live neighbours comes alive.
+    pub birth: u32,
+    /// Bit `n` is set when a live cell with `n` live neighbours stays alive.
+    pub survival: u32,
+
+    // Painting
+    /// Cell at the centre of the brush.
+    pub brush_center: [u32; 2],
+    /// Cells painted around the centre, in every direction.
+    pub brush_radius: u32,
+
+    // Drawing
+    pub image_size: [u32; 2],
+    /// Point of the grid at the centre of the image, in cells.
+    pub view_center: [f32; 2],
+    /// Pixels per cell.
+    pub zoom: f32,
+}
+
+impl LifeConstants {
+    /// Whether a cell is alive at the next step.
+    pub fn next(&self, alive: bool, neighbours: u32) -> bool {
+        let rule = if alive { self.survival } else { self.birth };
+        (rule >> neighbours) & 1 != 0
+    }
+
+    /// Cell `offset - 1` cells away from `cell` along both axes, wrapping around the edges.
+    pub fn neighbour(&self, cell: [u32; 2], offset: [u32; 2]) -> [u32; 2] {
+        [
+            (cell[0] + self.size[0] + offset[0] - 1) % self.size[0],
+            (cell[1] + self.size[1] + offset[1] - 1) % self.size[1],
+        ]
+    }
+
+    /// Point of the grid under `pixel`, in cells, with the cell `[x, y]` covering `x..x + 1`
+    /// and `y..y + 1`.
+    pub fn pixel_to_cell(&self, pixel: Vec2) -> Vec2 {
+        let image_size = UVec2::from(self.image_size).as_vec2();
+        Vec2::from(self.view_center) + (pixel - 0.5 * image_size) / self.zoom
+    }
+}
\ No newline at end of file
@@ -0,0 +1,67 @@
+//! Parameters of the N-body simulation and of how its bodies are drawn.
+
+use spirv_std::glam::{vec4, Vec3, Vec4};
+use spirv_std::num_traits::Float;
+
+/// Invocations per workgroup of the integration kernel, and bodies per tile of workgroup
+/// memory. Must match its `threads(...)`.
+pub const WORKGROUP_SIZE: u32 = 256;
+
+/// Distance from the camera to the image plane, for a vertical field of view of 60°.
+const FOCAL_LENGTH: f32 = 1.732;
+
+/// One step of the simulation. Bodies are `(x, y, z, mass)`.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct NbodyConstants {
+    pub count: u32,
+    /// Time step.
+    pub dt: f32,
+    pub gravity: f32,
+    /// Added to the distance between bodies, so that close encounters do not fling them apart.
+    pub softening: f32,
+}
+
+impl NbodyConstants {
+    /// Acceleration of a body at `p` towards `body`, zero for `body` at `p` itself.
+    pub fn acceleration(&self, p: Vec3, body: Vec4) -> Vec3 {
+        let d = body.truncate() - p;
+        let distance_squared = d.length_squared() + self.softening * self.softening;
+        let inverse_distance = 1.0 / distance_squared.sqrt();
+        d * (self.gravity * body.w * inverse_distance * inverse_distance * inverse_distance)
+    }
+}
+
+/// Camera circling the origin, looking at it.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct NbodyView {
+    /// Width over height of the window.
+    pub aspect: f32,
+    /// Angle around the vertical axis, in radians.
+    pub yaw: f32,
+    /// Angle above the horizontal plane, in radians.
+    pub pitch: f32,
+    pub distance: f32,
+    /// Diameter of the sprites, in pixels.
+    pub point_size: f32,
+    /// Speed drawn white, slower bodies being bluer.
+    pub max_speed: f32,
+}
+
+impl NbodyView {
+    /// Clip space position of `p`, behind the near plane when behind the camera.
+    pub fn project(&self, p: Vec3) -> Vec4 {
+        let (sin_yaw, cos_yaw) = (self.yaw.sin(), self.yaw.cos());
+        let (sin_pitch, cos_pitch) = (self.pitch.sin(), self.pitch.cos());
+        let x = cos_yaw * p.x - sin_yaw * p.z;
+        let z = sin_yaw * p.x + cos_yaw * p.z;
+        let y = cos_pitch * p.y - sin_pitch * z;
+        let z = sin_pitch * p.y + cos_pitch * z;
+        let depth = self.distance - z;
+        // Vulkan's y points down, and every point in front of the camera is halfway in depth.
+        vec4(x * FOCAL_LENGTH / self.aspect, -y * FOCAL_LENGTH, 0.5 * depth, depth)
+    }
+}
\ No newline at end of file
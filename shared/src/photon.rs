@@ -0,0 +1,186 @@
+//! Photon mapping of caustics.
+//!
+//! Paths sampled from the camera almost never find a small light through glass or a mirror. So
+//! photons are traced from the light instead, and the ones that land on a diffuse surface after
+//! at least one specular bounce are stored in a hash grid. The camera pass then estimates the
+//! caustic light at each diffuse hit from the photons around it.
+
+use crate::{
+    color_sky, random_in_unit_sphere, unit_vector, Hit, HitData, Material, MaterialKind,
+    Materials, Ray, Rng,
+};
+use core::f32::consts::PI;
+use spirv_std::glam::{ivec3, IVec3, Vec3};
+
+/// Photons kept per grid cell, any more landing in a full cell are dropped.
+pub const CELL_CAPACITY: u32 = 16;
+
+/// A photon stored on a diffuse surface.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct Photon {
+    pub position: [f32; 3],
+    /// Flux carried by the photon.
+    pub power: [f32; 3],
+}
+
+/// Light emitted equally in all directions from a single point.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct PointLight {
+    pub position: Vec3,
+    /// Total flux emitted.
+    pub power: Vec3,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, power: Vec3) -> Self {
+        Self { position, power }
+    }
+
+    /// A photon path leaving the light in a uniformly random direction.
+    pub fn emit(&self, rng: &mut Rng) -> Ray {
+        Ray::new(self.position, unit_vector(random_in_unit_sphere(rng)))
+    }
+
+    /// Irradiance received directly from the light by the surface at `hit`.
+    ///
+    /// Glass and mirrors occlude the light too, the light they redirect is left to the photons.
+    pub fn irradiance(&self, world: impl Copy + Hit, hit: &HitData) -> Vec3 {
+        let to_light = self.position - hit.p;
+        let cos = hit.normal.dot(unit_vector(to_light));
+        let mut occluder = HitData::default();
+        if cos <= 0.0 || world.hit(&Ray::new(hit.p, to_light), 0.001, 1.0, &mut occluder) {
+            return Vec3::ZERO;
+        }
+        self.power * (cos / (4.0 * PI * to_light.length_squared()))
+    }
+}
+
+/// Trace one of `photon_count` photons from `light`, returning whether it ended up in `photon`.
+pub fn trace_photon<const NL: usize, const NM: usize, const ND: usize>(
+    ray_bounce_limit: u32,
+    photon_count: u32,
+    rng: &mut Rng,
+    light: &PointLight,
+    world: impl Copy + Hit,
+    materials: &Materials<NL, NM, ND>,
+    photon: &mut Photon,
+) -> bool {
+    let mut ray = light.emit(rng);
+    let mut power = light.power / photon_count as f32;
+    let mut hit = HitData::default();
+    let mut scattered = Ray::new(Vec3::ZERO, Vec3::ONE); // placeholder to initialise.
+    let mut attenuation = Vec3::default();
+    let mut specular = false;
+    let mut bounces = 0;
+    while bounces <= ray_bounce_limit && world.hit(&ray, 0.001, core::f32::MAX, &mut hit) {
+        match hit.material.kind {
+            MaterialKind::Lambertian => {
+                // Photons that reach a diffuse surface directly are direct light, not caustics.
+                if specular {
+                    photon.position = hit.p.into();
+                    photon.power = power.into();
+                }
+                return specular;
+            }
+            _ => {
+                if !materials.scatter(&ray, &hit, rng, &mut attenuation, &mut scattered) {
+                    return false;
+                }
+                power *= attenuation;
+                ray = scattered;
+                specular = true;
+            }
+        }
+        bounces += 1;
+    }
+    false
+}
+
+/// Grid cell containing `p`, for cells as wide as the gather radius.
+pub fn grid_cell(p: Vec3, radius: f32) -> IVec3 {
+    (p / radius).floor().as_ivec3()
+}
+
+/// Index in a table of `len` cells of the grid cell `cell`.
+pub fn cell_index(cell: IVec3, len: usize) -> usize {
+    let hash = (cell.x as u32).wrapping_mul(73856093)
+        ^ (cell.y as u32).wrapping_mul(19349663)
+        ^ (cell.z as u32).wrapping_mul(83492791);
+    (hash % len as u32) as usize
+}
+
+/// Irradiance at `p` estimated from the photons within `radius` of it.
+///
+/// `cells` holds the number of photons that landed in every cell, whose photons are stored
+/// `CELL_CAPACITY` apart in `photons`. Distinct cells sharing a slot of the table are rare
+/// enough that their photons are simply gathered together.
+pub fn gather(cells: &[u32], photons: &[Photon], p: Vec3, radius: f32) -> Vec3 {
+    let center = grid_cell(p, radius);
+    let mut power = Vec3::ZERO;
+    let mut z = -1;
+    while z <= 1 {
+        let mut y = -1;
+        while y <= 1 {
+            let mut x = -1;
+            while x <= 1 {
+                let cell = cell_index(center + ivec3(x, y, z), cells.len());
+                let count = cells[cell].min(CELL_CAPACITY) as usize;
+                let mut i = 0;
+                while i < count {
+                    let photon = photons[cell * CELL_CAPACITY as usize + i];
+                    if (Vec3::from(photon.position) - p).length_squared() < radius * radius {
+                        power += Vec3::from(photon.power);
+                    }
+                    i += 1;
+                }
+                x += 1;
+            }
+            y += 1;
+        }
+        z += 1;
+    }
+    power / (PI * radius * radius)
+}
+
+/// Like `color`, with every diffuse hit also lit by `light`, both directly and through the
+/// caustics gathered from the photon map.
+pub fn color_photons<const NL: usize, const NM: usize, const ND: usize>(
+    ray_bounce_limit: u32,
+    rng: &mut Rng,
+    mut ray: Ray,
+    world: impl Copy + Hit,
+    materials: &Materials<NL, NM, ND>,
+    light: &PointLight,
+    cells: &[u32],
+    photons: &[Photon],
+    radius: f32,
+) -> Vec3 {
+    let mut hit = HitData::default();
+    let mut scattered = Ray::new(Vec3::ZERO, Vec3::ONE); // placeholder to initialise.
+    let mut attenuation = Vec3::default();
+
+    let mut color = Vec3::ZERO;
+    let mut throughput = Vec3::ONE;
+    let mut bounces = 0;
+    loop {
+        if !world.hit(&ray, 0.001, core::f32::MAX, &mut hit) {
+            color += throughput * color_sky(&ray);
+            break;
+        }
+        if let MaterialKind::Lambertian = hit.material.kind {
+            let albedo = materials.lambertian[hit.material.index].albedo;
+            let irradiance = light.irradiance(world, &hit) + gather(cells, photons, hit.p, radius);
+            color += throughput * albedo * irradiance / PI;
+        }
+        if bounces >= ray_bounce_limit || !materials.scatter(&ray, &hit, rng, &mut attenuation, &mut scattered) {
+            break;
+        }
+        throughput *= attenuation;
+        ray = scattered;
+        bounces += 1;
+    }
+    color
+}
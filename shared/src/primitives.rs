@@ -0,0 +1,62 @@
+//! Parameters of the parallel primitives: reduction, prefix scan, radix sort and histogram.
+//!
+//! The kernels only handle what fits in one dispatch, it is up to the host to chain them over
+//! buffers of any length.
+
+// Operations selected by `PrimitiveConstants::op` for reductions.
+pub const SUM: u32 = 0;
+pub const MIN: u32 = 1;
+pub const MAX: u32 = 2;
+
+/// Invocations per workgroup of every primitive kernel.
+pub const WORKGROUP_SIZE: u32 = 256;
+/// Elements scanned by a workgroup, two per invocation.
+pub const SCAN_BLOCK: u32 = 2 * WORKGROUP_SIZE;
+/// Bits of the keys sorted by each pass of the radix sort.
+pub const RADIX_BITS: u32 = 4;
+/// Values a digit of the radix sort takes.
+pub const RADIX: u32 = 1 << RADIX_BITS;
+/// Bins a histogram has at most, one per invocation of a workgroup.
+pub const MAX_BINS: u32 = WORKGROUP_SIZE;
+
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct PrimitiveConstants {
+    /// Elements in the input.
+    pub len: u32,
+    /// One of `SUM`, `MIN` or `MAX`, for reductions.
+    pub op: u32,
+    /// Bits the keys are shifted right by before taking their digit or bin.
+    pub shift: u32,
+    /// Bins of the histogram.
+    pub bins: u32,
+}
+
+impl PrimitiveConstants {
+    /// Value that leaves any other unchanged when combined with it by `op`.
+    pub fn identity(&self) -> u32 {
+        match self.op {
+            MIN => u32::MAX,
+            _ => 0,
+        }
+    }
+
+    pub fn combine(&self, a: u32, b: u32) -> u32 {
+        match self.op {
+            MIN => a.min(b),
+            MAX => a.max(b),
+            _ => a.wrapping_add(b),
+        }
+    }
+
+    /// Digit of `key` sorted by the current pass of the radix sort.
+    pub fn digit(&self, key: u32) -> u32 {
+        (key >> self.shift) & (RADIX - 1)
+    }
+
+    /// Bin of the histogram counting `key`, the keys past the last bin are counted in it.
+    pub fn bin(&self, key: u32) -> u32 {
+        (key >> self.shift).min(self.bins - 1)
+    }
+}
\ No newline at end of file
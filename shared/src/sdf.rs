@@ -0,0 +1,238 @@
+//! Signed distance fields, made hittable through sphere tracing.
+
+use crate::{unit_vector, Hit, HitData, MaterialInfo, Ray};
+use spirv_std::glam::{vec2, vec3, Vec3};
+
+/// Offset used when estimating the normal from the gradient of a field.
+const GRADIENT_STEP: f32 = 0.001;
+
+/// Types that describe a shape through a signed distance field.
+pub trait Sdf {
+    /// Distance from `p` to the closest point of the surface, negative inside the shape.
+    fn distance(self, p: Vec3) -> f32;
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct SdfSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+/// Axis aligned box.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct SdfBox {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+}
+
+/// Torus lying in the XZ plane.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct SdfTorus {
+    pub center: Vec3,
+    /// Distance from the center to the middle of the tube.
+    pub major_radius: f32,
+    /// Radius of the tube.
+    pub minor_radius: f32,
+}
+
+/// Segment from `a` to `b` swept by a sphere.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct SdfCapsule {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub radius: f32,
+}
+
+/// Any shape with its edges rounded off by `radius`.
+///
+/// The shape grows by `radius` in every direction, so shrink it beforehand to keep its size.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Rounded<S> {
+    pub shape: S,
+    pub radius: f32,
+}
+
+/// Union of two fields, blended over a distance of `k`.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct SmoothUnion<A, B> {
+    pub a: A,
+    pub b: B,
+    pub k: f32,
+}
+
+/// `a` with `b` carved out of it, blended over a distance of `k`.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct SmoothSubtraction<A, B> {
+    pub a: A,
+    pub b: B,
+    pub k: f32,
+}
+
+/// Intersection of two fields, blended over a distance of `k`.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct SmoothIntersection<A, B> {
+    pub a: A,
+    pub b: B,
+    pub k: f32,
+}
+
+/// Wraps a signed distance field so that it can be placed in a world next to analytic objects.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct SdfObject<S> {
+    pub sdf: S,
+    pub material: MaterialInfo,
+    /// Maximum number of sphere tracing steps before giving up on a ray.
+    pub max_steps: u32,
+    /// Distance to the surface under which the ray is considered to hit it.
+    pub epsilon: f32,
+}
+
+impl<S> SdfObject<S> {
+    pub fn new(sdf: S, material: MaterialInfo) -> Self {
+        Self {
+            sdf,
+            material,
+            max_steps: 128,
+            epsilon: 0.0001,
+        }
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(self, p: Vec3) -> f32 {
+        (p - self.center).length() - self.radius
+    }
+}
+
+impl Sdf for SdfBox {
+    fn distance(self, p: Vec3) -> f32 {
+        let q = (p - self.center).abs() - self.half_extents;
+        q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+    }
+}
+
+impl Sdf for SdfTorus {
+    fn distance(self, p: Vec3) -> f32 {
+        let p = p - self.center;
+        let q = vec2(vec2(p.x, p.z).length() - self.major_radius, p.y);
+        q.length() - self.minor_radius
+    }
+}
+
+impl Sdf for SdfCapsule {
+    fn distance(self, p: Vec3) -> f32 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+        (pa - ba * h).length() - self.radius
+    }
+}
+
+impl<S: Sdf> Sdf for Rounded<S> {
+    fn distance(self, p: Vec3) -> f32 {
+        self.shape.distance(p) - self.radius
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(self, p: Vec3) -> f32 {
+        smooth_union(self.a.distance(p), self.b.distance(p), self.k)
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothSubtraction<A, B> {
+    fn distance(self, p: Vec3) -> f32 {
+        smooth_subtraction(self.a.distance(p), self.b.distance(p), self.k)
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothIntersection<A, B> {
+    fn distance(self, p: Vec3) -> f32 {
+        smooth_intersection(self.a.distance(p), self.b.distance(p), self.k)
+    }
+}
+
+impl<S: Copy + Sdf> Hit for SdfObject<S> {
+    fn hit(self, r: &Ray, t_min: f32, t_max: f32, hit: &mut HitData) -> bool {
+        // `t` is measured in units of the (not necessarily normalised) ray direction.
+        let direction_len = r.direction().length();
+        let mut t = t_min;
+        // Rays that start inside the shape (e.g. refracted ones) march towards its way out.
+        let side = if self.sdf.distance(r.point_at_parameter(t)) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let mut step = 0;
+        while step < self.max_steps && t < t_max {
+            let d = side * self.sdf.distance(r.point_at_parameter(t));
+            // The first step is skipped so rays leaving the surface don't hit it again.
+            if step > 0 && d < self.epsilon {
+                hit.t = t;
+                hit.p = r.point_at_parameter(t);
+                hit.normal = gradient(self.sdf, hit.p);
+                hit.material = self.material;
+                return true;
+            }
+            t += d.max(self.epsilon) / direction_len;
+            step += 1;
+        }
+        false
+    }
+}
+
+/// Outward normal of the field at `p`, estimated from its gradient.
+pub fn gradient(sdf: impl Copy + Sdf, p: Vec3) -> Vec3 {
+    let k0 = vec3(1.0, -1.0, -1.0);
+    let k1 = vec3(-1.0, -1.0, 1.0);
+    let k2 = vec3(-1.0, 1.0, -1.0);
+    let k3 = vec3(1.0, 1.0, 1.0);
+    unit_vector(
+        k0 * sdf.distance(p + k0 * GRADIENT_STEP)
+            + k1 * sdf.distance(p + k1 * GRADIENT_STEP)
+            + k2 * sdf.distance(p + k2 * GRADIENT_STEP)
+            + k3 * sdf.distance(p + k3 * GRADIENT_STEP),
+    )
+}
+
+/// Union of `a` and `b` blended over a distance of `k`, the plain union for `k <= 0`.
+pub fn smooth_union(a: f32, b: f32, k: f32) -> f32 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    mix(b, a, h) - k * h * (1.0 - h)
+}
+
+/// `a` with `b` carved out of it, blended over a distance of `k`, the plain subtraction for
+/// `k <= 0`.
+pub fn smooth_subtraction(a: f32, b: f32, k: f32) -> f32 {
+    if k <= 0.0 {
+        return a.max(-b);
+    }
+    let h = (0.5 - 0.5 * (a + b) / k).clamp(0.0, 1.0);
+    mix(a, -b, h) + k * h * (1.0 - h)
+}
+
+/// Intersection of `a` and `b` blended over a distance of `k`, the plain intersection for
+/// `k <= 0`.
+pub fn smooth_intersection(a: f32, b: f32, k: f32) -> f32 {
+    if k <= 0.0 {
+        return a.max(b);
+    }
+    let h = (0.5 - 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    mix(b, a, h) + k * h * (1.0 - h)
+}
+
+fn mix(x: f32, y: f32, a: f32) -> f32 {
+    x + (y - x) * a
+}
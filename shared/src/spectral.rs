@@ -0,0 +1,275 @@
+//! Spectral path tracing, where every path carries a single wavelength instead of an RGB triple.
+//!
+//! Wavelengths are in nanometres, except for the dispersion coefficients which are given in
+//! micrometres as is customary for glass catalogues.
+
+use crate::{
+    color_sky, Dielectric, Hit, HitData, Lambertian, Material, MaterialKind, Materials, Metal, Ray,
+    Rng,
+};
+use spirv_std::{
+    glam::{vec3, Vec3},
+    num_traits::Float,
+};
+
+pub const WAVELENGTH_MIN: f32 = 380.0;
+pub const WAVELENGTH_MAX: f32 = 780.0;
+
+/// Wavelength of the helium d-line, at which `Dielectric::ref_idx.x` is measured.
+pub const WAVELENGTH_D: f32 = 587.6;
+
+/// Integral of the Y matching function over the sampled range.
+const CIE_Y_INTEGRAL: f32 = 106.91973;
+
+/// Used to describe the surface of different materials for a single wavelength.
+pub trait SpectralMaterial {
+    /// Produce a scattered ray (or say it absorved the incident ray).
+    ///
+    /// If scattered, describes how much the ray should be attenuated at `wavelength`.
+    fn scatter_spectral(
+        self,
+        r_in: &Ray,
+        hit: &HitData,
+        wavelength: f32,
+        rng: &mut Rng,
+        attenuation: &mut f32,
+        r_out: &mut Ray,
+    ) -> bool;
+}
+
+impl Dielectric {
+    /// Glass following Cauchy's equation `n = a + b / λ² + c / λ⁴`, with `λ` in micrometres.
+    pub fn cauchy(a: f32, b: f32, c: f32) -> Self {
+        let x = inverse_square_micrometres(WAVELENGTH_D);
+        Self {
+            ref_idx: vec3(a + b * x + c * x * x, b, c),
+        }
+    }
+
+    /// Glass following the Sellmeier equation, with `c` in square micrometres.
+    ///
+    /// The curve is approximated by Cauchy's equation through the ends of the visible range and
+    /// the d-line, which is accurate to well under 0.001 for optical glasses.
+    pub fn sellmeier(b: Vec3, c: Vec3) -> Self {
+        let xd = inverse_square_micrometres(WAVELENGTH_D);
+        let x0 = inverse_square_micrometres(450.0);
+        let x1 = inverse_square_micrometres(700.0);
+        let nd = sellmeier_ior(b, c, WAVELENGTH_D);
+        let n0 = sellmeier_ior(b, c, 450.0) - nd;
+        let n1 = sellmeier_ior(b, c, 700.0) - nd;
+        // Solve `n - nd = B (x - xd) + C (x² - xd²)` for the two sample points.
+        let (a0, b0) = (x0 - xd, x0 * x0 - xd * xd);
+        let (a1, b1) = (x1 - xd, x1 * x1 - xd * xd);
+        let det = a0 * b1 - a1 * b0;
+        Self {
+            ref_idx: vec3(nd, (n0 * b1 - n1 * b0) / det, (a0 * n1 - a1 * n0) / det),
+        }
+    }
+
+    /// Schott N-BK7, the most common optical glass.
+    pub fn bk7() -> Self {
+        Self::sellmeier(
+            vec3(1.03961212, 0.231792344, 1.01046945),
+            vec3(0.00600069867, 0.0200179144, 103.560653),
+        )
+    }
+
+    /// Schott N-SF11, a dense flint glass with strong dispersion.
+    pub fn sf11() -> Self {
+        Self::sellmeier(
+            vec3(1.73759695, 0.313747346, 1.89878101),
+            vec3(0.013188707, 0.0623068142, 155.23629),
+        )
+    }
+
+    /// Index of refraction at `wavelength`.
+    pub fn ior(&self, wavelength: f32) -> f32 {
+        let xd = inverse_square_micrometres(WAVELENGTH_D);
+        let x = inverse_square_micrometres(wavelength);
+        self.ref_idx.x + self.ref_idx.y * (x - xd) + self.ref_idx.z * (x * x - xd * xd)
+    }
+}
+
+impl SpectralMaterial for Lambertian {
+    fn scatter_spectral(
+        self,
+        _: &Ray,
+        hit: &HitData,
+        wavelength: f32,
+        rng: &mut Rng,
+        attenuation: &mut f32,
+        ray_out: &mut Ray,
+    ) -> bool {
+        let mut albedo = Vec3::ZERO;
+        self.scatter_ray(hit, rng, &mut albedo, ray_out);
+        *attenuation = rgb_to_spectrum(albedo, wavelength);
+        true
+    }
+}
+
+impl SpectralMaterial for Metal {
+    fn scatter_spectral(
+        self,
+        ray_in: &Ray,
+        hit: &HitData,
+        wavelength: f32,
+        rng: &mut Rng,
+        attenuation: &mut f32,
+        ray_out: &mut Ray,
+    ) -> bool {
+        let mut albedo = Vec3::ZERO;
+        let scattered = Material::scatter(self, ray_in, hit, rng, &mut albedo, ray_out);
+        *attenuation = rgb_to_spectrum(albedo, wavelength);
+        scattered
+    }
+}
+
+impl SpectralMaterial for Dielectric {
+    fn scatter_spectral(
+        self,
+        ray_in: &Ray,
+        hit: &HitData,
+        wavelength: f32,
+        rng: &mut Rng,
+        attenuation: &mut f32,
+        ray_out: &mut Ray,
+    ) -> bool {
+        *attenuation = 1.0;
+        self.scatter_with_ior(ray_in, hit, self.ior(wavelength), rng, ray_out);
+        true
+    }
+}
+
+impl<'a, const NL: usize, const NM: usize, const ND: usize> SpectralMaterial
+    for &'a Materials<NL, NM, ND>
+{
+    fn scatter_spectral(
+        self,
+        ray_in: &Ray,
+        hit: &HitData,
+        wavelength: f32,
+        rng: &mut Rng,
+        attenuation: &mut f32,
+        ray_out: &mut Ray,
+    ) -> bool {
+        let index = hit.material.index;
+        match hit.material.kind {
+            MaterialKind::Lambertian => self.lambertian[index]
+                .scatter_spectral(ray_in, hit, wavelength, rng, attenuation, ray_out),
+            MaterialKind::Metal => self.metal[index]
+                .scatter_spectral(ray_in, hit, wavelength, rng, attenuation, ray_out),
+            MaterialKind::Dielectric => self.dielectric[index]
+                .scatter_spectral(ray_in, hit, wavelength, rng, attenuation, ray_out),
+        }
+    }
+}
+
+/// Spectral counterpart of `color`, giving the radiance carried by `ray` at `wavelength`.
+pub fn color_spectral(
+    ray_bounce_limit: u32,
+    rng: &mut Rng,
+    mut ray: Ray,
+    wavelength: f32,
+    world: impl Copy + Hit,
+    materials: impl Copy + SpectralMaterial,
+) -> f32 {
+    let mut hit = HitData::default();
+    let mut scattered = Ray::new(Vec3::ZERO, Vec3::ONE); // placeholder to initialise.
+    let mut attenuation = 0.0;
+
+    let min_f = 0.001;
+    let max_f = core::f32::MAX;
+    let mut color = 1.0;
+    let mut bounces = 0;
+    while world.hit(&ray, min_f, max_f, &mut hit) {
+        if bounces < ray_bounce_limit
+            && materials.scatter_spectral(&ray, &hit, wavelength, rng, &mut attenuation, &mut scattered)
+        {
+            color *= attenuation;
+            ray = scattered;
+        } else {
+            color = 0.0;
+            break;
+        }
+        bounces += 1;
+    }
+
+    let sky = rgb_to_spectrum(color_sky(&ray), wavelength);
+    sky * color
+}
+
+/// Traces `ray` at a single random wavelength and returns its contribution as linear RGB.
+///
+/// A single sample is very noisy in colour, averaging many of them converges to the same image
+/// as `color` except for the effects of dispersion.
+pub fn color_spectral_rgb(
+    ray_bounce_limit: u32,
+    rng: &mut Rng,
+    ray: Ray,
+    world: impl Copy + Hit,
+    materials: impl Copy + SpectralMaterial,
+) -> Vec3 {
+    let wavelength = sample_wavelength(rng);
+    let radiance = color_spectral(ray_bounce_limit, rng, ray, wavelength, world, materials);
+    wavelength_to_rgb(wavelength, radiance)
+}
+
+/// Uniformly sample a wavelength in the visible range.
+pub fn sample_wavelength(rng: &mut Rng) -> f32 {
+    WAVELENGTH_MIN + rng.gen() * (WAVELENGTH_MAX - WAVELENGTH_MIN)
+}
+
+/// Linear RGB estimate of a spectrum from a single `radiance` sample taken at a uniformly sampled
+/// `wavelength`.
+pub fn wavelength_to_rgb(wavelength: f32, radiance: f32) -> Vec3 {
+    let scale = (WAVELENGTH_MAX - WAVELENGTH_MIN) / CIE_Y_INTEGRAL;
+    // RGB of the equal energy spectrum, so that white surfaces stay white.
+    let white_balance = vec3(1.2005519, 0.9497651, 0.9076861);
+    xyz_to_linear_srgb(cie_xyz(wavelength)) * (radiance * scale) / white_balance
+}
+
+/// A smooth spectrum for the given linear RGB value, evaluated at `wavelength`.
+///
+/// Built from three overlapping ramps that always sum to one, so grey values stay flat.
+pub fn rgb_to_spectrum(rgb: Vec3, wavelength: f32) -> f32 {
+    let blue = ((540.0 - wavelength) / 80.0).clamp(0.0, 1.0);
+    let red = ((wavelength - 540.0) / 70.0).clamp(0.0, 1.0);
+    let green = 1.0 - blue - red;
+    rgb.x * red + rgb.y * green + rgb.z * blue
+}
+
+/// CIE 1931 colour matching functions, using the multi-lobe fit of Wyman, Sloan and Shirley.
+pub fn cie_xyz(wavelength: f32) -> Vec3 {
+    let l = wavelength;
+    vec3(
+        1.056 * lobe(l, 599.8, 37.9, 31.0) + 0.362 * lobe(l, 442.0, 16.0, 26.7)
+            - 0.065 * lobe(l, 501.1, 20.4, 26.2),
+        0.821 * lobe(l, 568.8, 46.9, 40.5) + 0.286 * lobe(l, 530.9, 16.3, 31.1),
+        1.217 * lobe(l, 437.0, 11.8, 36.0) + 0.681 * lobe(l, 459.0, 26.0, 13.8),
+    )
+}
+
+/// Convert CIE XYZ to linear sRGB with a D65 white point.
+pub fn xyz_to_linear_srgb(xyz: Vec3) -> Vec3 {
+    vec3(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+}
+
+fn lobe(x: f32, mu: f32, sigma_low: f32, sigma_high: f32) -> f32 {
+    let t = (x - mu) / if x < mu { sigma_low } else { sigma_high };
+    (-0.5 * t * t).exp()
+}
+
+fn sellmeier_ior(b: Vec3, c: Vec3, wavelength: f32) -> f32 {
+    let l2 = 1.0 / inverse_square_micrometres(wavelength);
+    let n2 = 1.0 + l2 * (b / (Vec3::splat(l2) - c)).dot(Vec3::ONE);
+    n2.sqrt()
+}
+
+fn inverse_square_micrometres(wavelength: f32) -> f32 {
+    let l = wavelength * 0.001;
+    1.0 / (l * l)
+}
@@ -0,0 +1,109 @@
+//! State passed between the kernels of the wavefront path tracer.
+//!
+//! Instead of following a path from the camera to the sky in a single invocation, every stage of
+//! the path (intersection, material shading, shadow rays) runs as its own kernel over a queue of
+//! paths, so that invocations in a workgroup run the same code.
+
+use crate::{HitData, MaterialInfo, MaterialKind, Ray, Rng};
+use spirv_std::glam::{Vec2, Vec3};
+
+/// Index in the counters buffer of the number of paths in the ray queue.
+pub const RAY_COUNT: usize = 0;
+/// Index in the counters buffer of the number of paths pushed for the next bounce.
+pub const NEXT_RAY_COUNT: usize = 1;
+/// Index in the counters buffer of the number of paths in the material queue.
+pub const MATERIAL_COUNT: usize = 2;
+/// Index in the counters buffer of the number of paths in the shadow queue.
+pub const SHADOW_COUNT: usize = 3;
+pub const COUNTERS_LEN: usize = 4;
+
+/// A path being traced, one per pixel.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct PathState {
+    pub origin: [f32; 3],
+    pub pixel: u32,
+    pub direction: [f32; 3],
+    pub bounces: u32,
+    /// Product of the attenuations of all bounces so far.
+    pub throughput: [f32; 3],
+    /// Non-zero when the last bounce was diffuse, in which case the sky was already gathered by a
+    /// shadow ray and must not be added again when the path escapes.
+    pub after_diffuse: u32,
+    pub rng_seed: [f32; 2],
+    pub hit_t: f32,
+    pub material_kind: u32,
+    pub hit_normal: [f32; 3],
+    pub material_index: u32,
+}
+
+/// Light from the sky that reaches a pixel unless something is in the way.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct ShadowRay {
+    pub origin: [f32; 3],
+    pub pixel: u32,
+    pub direction: [f32; 3],
+    pub contribution: [f32; 3],
+}
+
+impl PathState {
+    pub fn ray(&self) -> Ray {
+        Ray::new(Vec3::from(self.origin), Vec3::from(self.direction))
+    }
+
+    pub fn set_ray(&mut self, ray: &Ray) {
+        self.origin = ray.origin().into();
+        self.direction = ray.direction().into();
+    }
+
+    pub fn throughput(&self) -> Vec3 {
+        Vec3::from(self.throughput)
+    }
+
+    pub fn rng(&self) -> Rng {
+        Rng {
+            seed: Vec2::from(self.rng_seed),
+        }
+    }
+
+    pub fn set_rng(&mut self, rng: &Rng) {
+        self.rng_seed = rng.seed.into();
+    }
+
+    /// The hit stored by the intersection kernel.
+    pub fn hit(&self) -> HitData {
+        HitData {
+            t: self.hit_t,
+            p: self.ray().point_at_parameter(self.hit_t),
+            normal: Vec3::from(self.hit_normal),
+            material: MaterialInfo {
+                kind: material_kind(self.material_kind),
+                index: self.material_index as usize,
+            },
+        }
+    }
+
+    pub fn set_hit(&mut self, hit: &HitData) {
+        self.hit_t = hit.t;
+        self.hit_normal = hit.normal.into();
+        self.material_kind = hit.material.kind as u32;
+        self.material_index = hit.material.index as u32;
+    }
+}
+
+impl ShadowRay {
+    pub fn ray(&self) -> Ray {
+        Ray::new(Vec3::from(self.origin), Vec3::from(self.direction))
+    }
+}
+
+fn material_kind(kind: u32) -> MaterialKind {
+    match kind {
+        1 => MaterialKind::Metal,
+        2 => MaterialKind::Dielectric,
+        _ => MaterialKind::Lambertian,
+    }
+}